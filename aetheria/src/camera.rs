@@ -69,8 +69,4 @@ impl Camera {
 
         self.update_buffer();
     }
-
-    pub fn get_rotation(&self) -> Quat {
-        Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), self.theta)
-    }
 }
@@ -0,0 +1,98 @@
+use glam::Vec4;
+
+use super::{
+    components::{self, Container, HAlign, Padding, Text, VList, VPair},
+    text_input::TextInput,
+};
+use crate::{
+    data::chat::{Chat, ChatLine},
+    ui,
+};
+
+/// Width of the chat panel's contents
+const WIDTH: u32 = 200;
+const VISIBLE_LINES: usize = 8;
+
+pub type Component = Container<Padding<VPair<VList<Text>, TextInput>>>;
+
+/// Replaces anything the font can't draw, other players can send any text
+fn sanitize(content: &str) -> String {
+    content
+        .chars()
+        .map(|c| if components::is_supported(c) { c } else { ' ' })
+        .collect()
+}
+
+/// Splits a line into rows that fit in the panel, breaking between words where possible
+fn wrap(content: &str) -> Vec<String> {
+    let mut rows = vec![String::new()];
+
+    for word in content.split(' ') {
+        let row = rows.last_mut().expect("Rows always has at least one row");
+        let candidate = if row.is_empty() {
+            word.to_owned()
+        } else {
+            format!("{row} {word}")
+        };
+
+        if components::text_width(&candidate) <= WIDTH {
+            *row = candidate;
+            continue;
+        }
+
+        // Words too long for a row of their own are broken wherever they run out of room
+        let mut row = String::new();
+        for c in word.chars() {
+            if components::text_width(&format!("{row}{c}")) > WIDTH {
+                rows.push(std::mem::take(&mut row));
+            }
+            row.push(c);
+        }
+        rows.push(row);
+    }
+
+    rows.retain(|row| !row.is_empty());
+    rows
+}
+
+fn rows(line: &ChatLine) -> Vec<Text> {
+    let (color, content) = line.sender.as_ref().map_or_else(
+        || (ui::color::get_highlight(), line.message.clone()),
+        |sender| (Vec4::ONE, format!("{sender} {}", line.message)),
+    );
+
+    wrap(&sanitize(&content))
+        .into_iter()
+        .map(|content| Text { color, content })
+        .collect()
+}
+
+impl Component {
+    /// The chat log with an input underneath, `None` while there's nothing to show
+    pub fn new(chat: &Chat, focused: bool) -> Option<Self> {
+        if chat.is_empty() && !focused {
+            return None;
+        }
+
+        let mut children = chat
+            .visible(VISIBLE_LINES)
+            .flat_map(rows)
+            .collect::<Vec<Text>>();
+        // Long lines wrap onto several rows, only the newest fit
+        children.drain(..children.len().saturating_sub(VISIBLE_LINES));
+
+        let log = VList {
+            children,
+            separation: 2,
+            align: HAlign::Left,
+        };
+        let input = TextInput::new(&chat.draft, "Press enter to chat", focused, WIDTH);
+
+        Some(Self {
+            child: Padding::new_uniform(VPair::new(log, input, HAlign::Left, 3), 2),
+            color: ui::color::get_background(),
+            border_radius: 1,
+            border_color: ui::color::get_highlight(),
+        })
+    }
+}
@@ -275,6 +275,24 @@ static CHARACTER_MAP: [(char, u32); 38] = [
     ('/', 5),
 ];
 
+fn char_width(c: char) -> Option<u32> {
+    CHARACTER_MAP.iter().find(|a| a.0 == c).map(|a| a.1)
+}
+
+/// Whether the font can draw `c`, `Text` panics on anything else
+pub fn is_supported(c: char) -> bool {
+    c.to_uppercase().all(|c| char_width(c).is_some())
+}
+
+/// Width of `content` when drawn by `Text`, including the gap after each character
+pub fn text_width(content: &str) -> u32 {
+    content
+        .to_uppercase()
+        .chars()
+        .map(|c| char_width(c).expect(&format!("Character {} not in font", c)))
+        .fold(0, |acc, w| acc + w + 1)
+}
+
 #[derive(Clone, Debug)]
 pub struct Text {
     pub color: Vec4,
@@ -283,18 +301,7 @@ pub struct Text {
 
 impl Element for Text {
     fn layout(&mut self, constraint: SizeConstraints) -> UVec2 {
-        let width = self
-            .content
-            .to_uppercase()
-            .chars()
-            .map(|c| {
-                CHARACTER_MAP
-                    .iter()
-                    .find(|a| a.0 == c)
-                    .expect(&format!("Character {} not in font", c))
-                    .1
-            })
-            .fold(0, |acc, w| acc + w + 1);
+        let width = text_width(&self.content);
 
         UVec2::new(
             width.max(constraint.min.x),
@@ -523,3 +530,38 @@ impl<H: Handler> Element for Button<'_, H> {
         self.component.paint(region, scene)
     }
 }
+
+#[derive(Clone, Debug)]
+pub struct ProgressBar {
+    /// From 0 to 1
+    pub progress: f32,
+    pub width: u32,
+    pub color: Vec4,
+    pub background: Vec4,
+}
+
+impl Element for ProgressBar {
+    fn layout(&mut self, constraint: SizeConstraints) -> UVec2 {
+        UVec2::new(
+            self.width.max(constraint.min.x),
+            CHAR_HEIGHT.max(constraint.min.y),
+        )
+    }
+
+    fn paint(&mut self, region: Region, scene: &mut Vec<Rectangle>) {
+        scene.push(Rectangle {
+            color: self.background,
+            origin: region.origin,
+            extent: region.size,
+            ..Default::default()
+        });
+
+        let filled = (region.size.x as f32 * self.progress.clamp(0.0, 1.0)) as u32;
+        scene.push(Rectangle {
+            color: self.color,
+            origin: region.origin,
+            extent: UVec2::new(filled, region.size.y),
+            ..Default::default()
+        });
+    }
+}
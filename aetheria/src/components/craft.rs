@@ -1,10 +1,10 @@
-use common::item::ItemStack;
+use common::recipe::Recipe;
 
 use super::components::{
     Button, Container, HAlign, HPair, Handler, Padding, Text, VAlign, VList, VPair,
 };
 use crate::{
-    data::{inventory::Inventory, Data, Recipe},
+    data::{inventory::Inventory, Data},
     input::Mouse,
     ui::{self, Element},
 };
@@ -18,23 +18,14 @@ pub struct CraftButtonHandler<'a> {
 
 impl Handler for CraftButtonHandler<'_> {
     fn handle(&mut self) {
-        if !self
-            .recipe
-            .has_ingredients(&self.data.lock().unwrap().inventory)
-        {
+        let mut data = self.data.lock().unwrap();
+        if !self.recipe.has_ingredients(data.inventory.get_items()) {
             return;
         }
 
-        self.recipe
-            .ingredients
-            .iter()
-            .for_each(|stack| self.data.lock().unwrap().inventory.remove(*stack));
-        self.recipe
-            .outputs
-            .iter()
-            .for_each(|stack| self.data.lock().unwrap().inventory.add(*stack));
-
-        self.data.lock().unwrap().current_recipe = None;
+        // The server checks the ingredients again and replies with the new stacks
+        data.inventory.craft(&self.recipe.id);
+        data.current_recipe = None;
     }
 }
 
@@ -60,7 +51,7 @@ impl<'a> Component<'a> {
         let color = if data
             .current_recipe
             .as_ref()?
-            .has_ingredients(&data.inventory)
+            .has_ingredients(data.inventory.get_items())
         {
             ui::color::get_success()
         } else {
@@ -116,6 +107,14 @@ impl<'a> Component<'a> {
                     content: format!("{}", output),
                 })
             });
+        text.push(Text {
+            color: Vec4::ZERO,
+            content: String::new(),
+        });
+        text.push(Text {
+            color: ui::color::get_highlight(),
+            content: format!("Takes {:.0}s", data.current_recipe.as_ref()?.craft_time),
+        });
 
         let text = VList {
             children: text,
@@ -0,0 +1,136 @@
+use common::{
+    furnace::{Furnace, FurnaceSlot},
+    item::ItemStack,
+};
+
+use super::components::{
+    Button, Container, HAlign, HPair, Handler, Padding, ProgressBar, Text, VAlign, VList, VPair,
+};
+use crate::{data::Data, input::Mouse, ui};
+use std::sync::{Arc, Mutex};
+
+pub struct LoadHandler<'a> {
+    slot: FurnaceSlot,
+    data: Arc<Mutex<&'a mut Data>>,
+}
+
+impl Handler for LoadHandler<'_> {
+    fn handle(&mut self) {
+        let data = self.data.lock().unwrap();
+        let Some(stack) = data
+            .inventory
+            .get_items()
+            .iter()
+            .find(|stack| Furnace::accepts(self.slot, &stack.item))
+        else {
+            return;
+        };
+
+        data.inventory.load_furnace(self.slot, stack.clone());
+    }
+}
+
+pub struct TakeHandler<'a> {
+    data: Arc<Mutex<&'a mut Data>>,
+}
+
+impl Handler for TakeHandler<'_> {
+    fn handle(&mut self) {
+        self.data.lock().unwrap().inventory.take_furnace_output();
+    }
+}
+
+pub struct CloseHandler<'a> {
+    data: Arc<Mutex<&'a mut Data>>,
+}
+
+impl Handler for CloseHandler<'_> {
+    fn handle(&mut self) {
+        self.data.lock().unwrap().furnace = None;
+    }
+}
+
+type Buttons<'a> = HPair<
+    HPair<Button<'a, CloseHandler<'a>>, Button<'a, LoadHandler<'a>>>,
+    HPair<Button<'a, LoadHandler<'a>>, Button<'a, TakeHandler<'a>>>,
+>;
+
+pub type Component<'a> = Container<Padding<VPair<VPair<VList<Text>, ProgressBar>, Buttons<'a>>>>;
+
+fn describe(name: &str, stack: Option<&ItemStack>) -> Text {
+    Text {
+        color: ui::color::get_highlight(),
+        content: stack.map_or_else(
+            || format!("{name} Empty"),
+            |stack| format!("{name} {stack}"),
+        ),
+    }
+}
+
+impl<'a> Component<'a> {
+    pub fn new(data: &'a mut Data, mouse: &'a Mouse) -> Option<Self> {
+        let furnace = data.furnace.as_ref()?;
+
+        let text = VList {
+            children: vec![
+                describe("Input", furnace.input.as_ref()),
+                describe("Fuel", furnace.fuel.as_ref()),
+                describe("Output", furnace.output.as_ref()),
+            ],
+            separation: 2,
+            align: HAlign::Left,
+        };
+        let progress = ProgressBar {
+            progress: furnace.progress_fraction(),
+            width: 80,
+            color: ui::color::get_success(),
+            background: ui::color::get_background(),
+        };
+
+        let data_mutex = Arc::new(Mutex::new(data));
+        let close_button = Button::new(
+            mouse,
+            "Close",
+            CloseHandler {
+                data: data_mutex.clone(),
+            },
+        );
+        let smelt_button = Button::new(
+            mouse,
+            "Smelt",
+            LoadHandler {
+                slot: FurnaceSlot::Input,
+                data: data_mutex.clone(),
+            },
+        );
+        let fuel_button = Button::new(
+            mouse,
+            "Fuel",
+            LoadHandler {
+                slot: FurnaceSlot::Fuel,
+                data: data_mutex.clone(),
+            },
+        );
+        let take_button = Button::new(mouse, "Take", TakeHandler { data: data_mutex });
+
+        let buttons = HPair::new(
+            HPair::new(close_button, smelt_button, VAlign::Top, 4),
+            HPair::new(fuel_button, take_button, VAlign::Top, 4),
+            VAlign::Top,
+            4,
+        );
+        let pair = VPair::new(
+            VPair::new(text, progress, HAlign::Left, 4),
+            buttons,
+            HAlign::Center,
+            6,
+        );
+
+        Some(Self {
+            child: Padding::new_uniform(pair, 2),
+            color: ui::color::get_background(),
+            border_color: ui::color::get_highlight(),
+            border_radius: 1,
+        })
+    }
+}
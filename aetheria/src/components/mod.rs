@@ -1,5 +1,8 @@
+pub mod chat;
 pub mod components;
 pub mod craft;
+pub mod furnace;
 pub mod interact;
 pub mod inventory;
 pub mod recipe_selector;
+pub mod text_input;
@@ -1,9 +1,6 @@
 use super::components::{Button, Container, HAlign, Handler, Padding, VList};
-use crate::{
-    data::{Data, Recipe},
-    input::Mouse,
-    ui,
-};
+use crate::{data::Data, input::Mouse, ui};
+use common::recipe::Recipe;
 use std::sync::{Arc, Mutex};
 
 pub type Component<'a> = Container<Padding<VList<Button<'a, RecipeSelectorHandler<'a>>>>>;
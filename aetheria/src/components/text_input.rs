@@ -0,0 +1,117 @@
+use glam::{UVec2, Vec4};
+
+use super::components::{self, Text, CHAR_HEIGHT};
+use crate::ui::{self, Element, Rectangle, Region, SizeConstraints};
+
+const PADDING: u32 = 2;
+
+/// What happened to a text input this frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edit {
+    Editing,
+    Submitted,
+    Cancelled,
+}
+
+/// Applies the characters typed this frame to `value`, anything the font can't draw is ignored
+pub fn edit(value: &mut String, typed: &str, max_length: usize) -> Edit {
+    for c in typed.chars() {
+        match c {
+            '\r' => return Edit::Submitted,
+            '\u{1b}' => return Edit::Cancelled,
+            '\u{8}' => {
+                value.pop();
+            }
+            c if components::is_supported(c) && value.chars().count() < max_length => {
+                value.push(c);
+            }
+            _ => {}
+        }
+    }
+
+    Edit::Editing
+}
+
+/// Single line of editable text, shows a cursor while it has the keyboard
+#[derive(Clone, Debug)]
+pub struct TextInput {
+    text: Text,
+    focused: bool,
+    width: u32,
+}
+
+impl TextInput {
+    pub fn new(value: &str, placeholder: &str, focused: bool, width: u32) -> Self {
+        let text = if value.is_empty() && !focused {
+            Text {
+                color: ui::color::get_highlight(),
+                content: placeholder.to_owned(),
+            }
+        } else {
+            Text {
+                color: Vec4::ONE,
+                content: value.to_owned(),
+            }
+        };
+
+        Self {
+            text,
+            focused,
+            width,
+        }
+    }
+}
+
+impl Element for TextInput {
+    fn layout(&mut self, constraint: SizeConstraints) -> UVec2 {
+        UVec2::new(
+            self.width.max(constraint.min.x),
+            (CHAR_HEIGHT + PADDING * 2).max(constraint.min.y),
+        )
+    }
+
+    fn paint(&mut self, region: Region, scene: &mut Vec<Rectangle>) {
+        let border = if self.focused {
+            ui::color::get_highlight()
+        } else {
+            ui::color::get_background()
+        };
+        scene.push(Rectangle {
+            color: border,
+            origin: region.origin,
+            extent: region.size,
+            radius: 1,
+            ..Default::default()
+        });
+        scene.push(Rectangle {
+            color: ui::color::get_background(),
+            origin: region.origin + UVec2::ONE,
+            extent: region.size - UVec2::new(2, 2),
+            ..Default::default()
+        });
+
+        // Keep the end of the text in view once it's wider than the input
+        let available = region.size.x - PADDING * 2;
+        while !self.text.content.is_empty()
+            && components::text_width(&self.text.content) + 2 > available
+        {
+            self.text.content.remove(0);
+        }
+
+        let size = self.text.layout(SizeConstraints {
+            min: UVec2::ZERO,
+            max: UVec2::new(available, CHAR_HEIGHT),
+        });
+        let origin = region.origin + UVec2::new(PADDING, PADDING);
+        self.text.paint(Region { origin, size }, scene);
+
+        if self.focused {
+            scene.push(Rectangle {
+                color: Vec4::ONE,
+                origin: origin + UVec2::new(size.x, 0),
+                extent: UVec2::new(1, CHAR_HEIGHT),
+                ..Default::default()
+            });
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use common::net;
+use std::{collections::VecDeque, sync::Arc};
+use tracing::warn;
+
+use crate::socket::Socket;
+
+/// Older lines are forgotten
+const MAX_LINES: usize = 100;
+
+#[derive(Clone, Debug)]
+pub struct ChatLine {
+    /// `None` for messages from the server
+    pub sender: Option<String>,
+    pub message: String,
+}
+
+#[derive(Clone)]
+pub struct Chat {
+    lines: VecDeque<ChatLine>,
+    /// Lines scrolled back from the newest
+    scroll: usize,
+    /// What's being typed, sent on enter
+    pub draft: String,
+    socket: Arc<Socket>,
+}
+
+impl Chat {
+    pub const fn new(socket: Arc<Socket>) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            scroll: 0,
+            draft: String::new(),
+            socket,
+        }
+    }
+
+    pub fn push(&mut self, line: ChatLine) {
+        if self.lines.len() == MAX_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+
+        // Keep the same lines in view while scrolled back
+        if self.scroll > 0 {
+            self.scroll = (self.scroll + 1).min(self.lines.len() - 1);
+        }
+    }
+
+    pub fn scroll(&mut self, lines: isize) {
+        self.scroll = self
+            .scroll
+            .saturating_add_signed(lines)
+            .min(self.lines.len().saturating_sub(1));
+    }
+
+    /// Up to `count` lines ending at the scroll position, oldest first
+    pub fn visible(&self, count: usize) -> impl Iterator<Item = &ChatLine> {
+        let end = self.lines.len() - self.scroll;
+        self.lines.range(end.saturating_sub(count)..end)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Sends the draft to the server, which echoes it back to everyone once it's been checked
+    pub fn send(&mut self) {
+        let message = std::mem::take(&mut self.draft);
+        if message.trim().is_empty() {
+            return;
+        }
+
+        self.scroll = 0;
+        let packet = net::server::Packet::ChatMessage(net::server::ChatMessage { message });
+        if let Err(e) = self.socket.send(&packet) {
+            warn!("Failed to send chat message due to {}", e);
+        }
+    }
+}
@@ -1,12 +1,11 @@
-use common::{
-    item::{Item, ItemStack},
-    net,
-};
+use common::{furnace::FurnaceSlot, item::ItemStack, net};
 use std::sync::Arc;
 use tracing::warn;
 
 use crate::socket::Socket;
 
+/// Mirror of the inventory the arbiter holds, changes are requested from the server and only
+/// applied once it replies
 #[derive(Clone)]
 pub struct Inventory {
     inventory: Vec<ItemStack>,
@@ -21,66 +20,49 @@ impl Inventory {
         }
     }
 
-    fn update(&self, item: Item) {
-        let Some(stack) = self.inventory.iter().find(|s| s.item == item) else {
-            warn!("Tried to update stack {:?} that doesn't exist", item);
-            return;
-        };
-
-        let packet = net::server::Packet::ModifyInventory(net::server::ModifyInventory {
-            stack: stack.clone(),
+    pub fn craft(&self, recipe_id: &str) {
+        let packet = net::server::Packet::Craft(net::server::Craft {
+            recipe_id: recipe_id.to_owned(),
         });
         if let Err(e) = self.socket.send(&packet) {
-            warn!("Failed to update stack {:?} due to {}", item, e);
-            return;
+            warn!("Failed to craft recipe {} due to {}", recipe_id, e);
         }
     }
 
-    pub fn add(&mut self, stack: ItemStack) {
-        if let Some(existing) = self.inventory.iter_mut().find(|s| s.item == stack.item) {
-            existing.amount += stack.amount;
-        } else {
-            self.inventory.push(stack);
+    pub fn gather(&self, node_id: u32) {
+        let packet = net::server::Packet::Gather(net::server::Gather { node_id });
+        if let Err(e) = self.socket.send(&packet) {
+            warn!("Failed to gather node {} due to {}", node_id, e);
         }
-
-        self.update(stack.item);
     }
 
-    pub fn remove(&mut self, stack: ItemStack) {
-        if let Some((i, existing)) = self
-            .inventory
-            .iter_mut()
-            .enumerate()
-            .find(|(_, s)| s.item == stack.item)
-        {
-            if existing.amount < stack.amount {
-                warn!(
-                    "Removing {} from inventory would give negative items",
-                    stack
-                );
-                return;
-            }
-
-            existing.amount -= stack.amount;
+    pub fn open_furnace(&self) {
+        if let Err(e) = self.socket.send(&net::server::Packet::OpenFurnace) {
+            warn!("Failed to open furnace due to {}", e);
+        }
+    }
 
-            if existing.amount == 0 {
-                self.inventory.remove(i);
-            }
-        } else {
-            warn!("Tried to remove {} but no such stack existed", stack);
+    pub fn load_furnace(&self, slot: FurnaceSlot, stack: ItemStack) {
+        let packet = net::server::Packet::LoadFurnace(net::server::LoadFurnace { slot, stack });
+        if let Err(e) = self.socket.send(&packet) {
+            warn!("Failed to load furnace due to {}", e);
         }
+    }
 
-        self.update(stack.item);
+    pub fn take_furnace_output(&self) {
+        if let Err(e) = self.socket.send(&net::server::Packet::TakeFurnaceOutput) {
+            warn!("Failed to take furnace output due to {}", e);
+        }
     }
 
     pub fn set(&mut self, stack: ItemStack) {
-        if let Some(existing) = self.inventory.iter_mut().find(|s| s.item == stack.item) {
+        if stack.amount == 0 {
+            self.inventory.retain(|s| s.item != stack.item);
+        } else if let Some(existing) = self.inventory.iter_mut().find(|s| s.item == stack.item) {
             existing.amount = stack.amount;
         } else {
             self.inventory.push(stack);
         }
-
-        self.update(stack.item);
     }
 
     pub fn get_items(&self) -> &[ItemStack] {
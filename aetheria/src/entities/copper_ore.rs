@@ -9,13 +9,13 @@ use crate::{
 };
 use ash::vk;
 use assets::{ModelRegistry, Transform};
-use common::item::{Item, ItemStack};
 use glam::Vec3;
 use std::sync::{Arc, Mutex};
 
 pub struct CopperOre {
     render: RenderObject,
-    gathered: bool,
+    id: u32,
+    pub depleted: bool,
 }
 
 impl CopperOre {
@@ -23,6 +23,7 @@ impl CopperOre {
         renderer: &mut Renderer,
         systems: &mut Systems,
         model_registry: &mut ModelRegistry,
+        id: u32,
         transform: Transform,
     ) -> Result<Arc<Mutex<Self>>, vk::Result> {
         let render = RenderObject {
@@ -32,7 +33,8 @@ impl CopperOre {
 
         let ore = Arc::new(Mutex::new(Self {
             render,
-            gathered: false,
+            id,
+            depleted: false,
         }));
 
         systems.render.add(ore.clone());
@@ -44,10 +46,10 @@ impl CopperOre {
 
 impl Renderable for CopperOre {
     fn get_objects(&self) -> Vec<RenderObject> {
-        if self.gathered {
-            Vec::new()
-        } else {
+        if !self.depleted {
             vec![self.render.clone()]
+        } else {
+            Vec::new()
         }
     }
 }
@@ -66,14 +68,10 @@ impl Positioned for CopperOre {
 
 impl Interactable for CopperOre {
     fn active(&self) -> bool {
-        !self.gathered
+        !self.depleted
     }
 
     fn interact(&mut self, data: &mut Data) {
-        data.inventory.add(ItemStack {
-            item: Item::CopperOre,
-            amount: 1,
-        });
-        self.gathered = true;
+        data.inventory.gather(self.id);
     }
 }
@@ -1,5 +1,5 @@
 use crate::{
-    data::{Data, Recipe},
+    data::Data,
     renderer::Renderer,
     systems::{
         interact::Interactable,
@@ -9,7 +9,7 @@ use crate::{
 };
 use ash::vk;
 use assets::{ModelRegistry, Transform};
-use common::item::{Item, ItemStack};
+use common::recipe::{get_recipe_book, Station};
 use glam::Vec3;
 use std::sync::{Arc, Mutex};
 
@@ -62,39 +62,11 @@ impl Interactable for CraftingBench {
     }
 
     fn interact(&mut self, data: &mut Data) {
-        data.recipe_selections = Some(vec![
-            Recipe {
-                ingredients: vec![
-                    ItemStack {
-                        item: Item::Wood,
-                        amount: 3,
-                    },
-                    ItemStack {
-                        item: Item::Fireglow,
-                        amount: 2,
-                    },
-                ],
-                outputs: vec![ItemStack {
-                    item: Item::Lamp,
-                    amount: 1,
-                }],
-            },
-            Recipe {
-                ingredients: vec![
-                    ItemStack {
-                        item: Item::Wood,
-                        amount: 2,
-                    },
-                    ItemStack {
-                        item: Item::CopperIngot,
-                        amount: 2,
-                    },
-                ],
-                outputs: vec![ItemStack {
-                    item: Item::CopperSword,
-                    amount: 1,
-                }],
-            },
-        ])
+        data.recipe_selections = Some(
+            get_recipe_book()
+                .for_station(Station::CraftingBench)
+                .cloned()
+                .collect(),
+        );
     }
 }
@@ -16,7 +16,6 @@ use crate::{
     },
     time::Time,
 };
-use common::item::{Item, ItemStack};
 
 const FIREFLY_SPEED: f32 = 60.0;
 
@@ -25,7 +24,8 @@ pub struct Firefly {
     velocity: Vec3,
     origin: Vec3,
     render: RenderObject,
-    gathered: bool,
+    id: u32,
+    pub depleted: bool,
 }
 
 impl Firefly {
@@ -33,6 +33,7 @@ impl Firefly {
         renderer: &mut Renderer,
         systems: &mut Systems,
         model_registry: &mut ModelRegistry,
+        id: u32,
         translation: Vec3,
         color: Vec3,
     ) -> Result<Arc<Mutex<Self>>, vk::Result> {
@@ -60,7 +61,8 @@ impl Firefly {
             velocity,
             origin: translation,
             render,
-            gathered: false,
+            id,
+            depleted: false,
         }));
 
         systems.render.add(firefly.clone());
@@ -70,14 +72,14 @@ impl Firefly {
     }
 
     pub fn frame_finished(&mut self, sun: &Sun, time: &Time) {
-        if sun.get_theta() > (std::f32::consts::PI / 3.0)
+        if !self.depleted
+            && sun.get_theta() > (std::f32::consts::PI / 3.0)
             && sun.get_theta() < (std::f32::consts::PI * (5.0 / 3.0))
         {
             self.light.strength = 300.0
                 * ((sun.get_theta() / 2.0).sin() - sun.get_theta().cos())
                     .powf(1.5)
-                    .min(1.0)
-                * !self.gathered as u32 as f32;
+                    .min(1.0);
         } else {
             self.light.strength = 0.0
         }
@@ -113,7 +115,7 @@ impl Emissive for Firefly {
 
 impl Renderable for Firefly {
     fn get_objects(&self) -> Vec<RenderObject> {
-        if self.light.strength != 0.0 && !self.gathered {
+        if self.light.strength != 0.0 {
             vec![self.render.clone()]
         } else {
             vec![]
@@ -135,14 +137,10 @@ impl Positioned for Firefly {
 
 impl Interactable for Firefly {
     fn interact(&mut self, data: &mut crate::data::Data) {
-        data.inventory.add(ItemStack {
-            item: Item::Fireglow,
-            amount: 1,
-        });
-        self.gathered = true;
+        data.inventory.gather(self.id);
     }
 
     fn active(&self) -> bool {
-        !self.gathered && self.light.strength > 0.0
+        self.light.strength > 0.0
     }
 }
@@ -1,5 +1,5 @@
 use crate::{
-    data::{Data, Recipe},
+    data::Data,
     renderer::Renderer,
     systems::{
         interact::Interactable,
@@ -9,7 +9,6 @@ use crate::{
 };
 use ash::vk;
 use assets::{ModelRegistry, Transform};
-use common::item::{Item, ItemStack};
 use glam::Vec3;
 use std::sync::{Arc, Mutex};
 
@@ -65,16 +64,8 @@ impl Positioned for Furnace {
 
 impl Interactable for Furnace {
     fn interact(&mut self, data: &mut Data) {
-        data.current_recipe = Some(Recipe {
-            ingredients: vec![ItemStack {
-                item: Item::CopperOre,
-                amount: 3,
-            }],
-            outputs: vec![ItemStack {
-                item: Item::CopperIngot,
-                amount: 1,
-            }],
-        })
+        // The window opens once the server replies with the furnace's state
+        data.inventory.open_furnace();
     }
 
     fn active(&self) -> bool {
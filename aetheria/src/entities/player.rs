@@ -1,15 +1,16 @@
 use std::{
-    f32::consts::PI,
+    collections::VecDeque,
     sync::{Arc, Mutex},
 };
 
 use ash::vk;
 use assets::{ModelRegistry, Transform};
 use common::{
-    item::{Item, ItemStack},
+    movement::{self, MovementInput, MovementKeys, MovementState, STEP},
     net,
 };
 use glam::{Vec2, Vec3};
+use tracing::warn;
 use winit::event::VirtualKeyCode;
 
 use crate::{
@@ -25,15 +26,94 @@ use crate::{
     time::Time,
 };
 
-const PLAYER_SPEED: f32 = 100.0;
-const JUMP_HEIGHT: f32 = 100.0;
-const JUMP_SPEED: f32 = 4.0;
-const DASH_DISTANCE: f32 = 100.0;
+/// Max time simulated in one frame, a quarter of a second's worth of steps
+const MAX_ACCUMULATED: f32 = 0.25;
+/// Prediction errors smaller than this are just float noise
+const EPSILON: f32 = 0.01;
+
+/// Client-side prediction, inputs are simulated straight away and replayed on top of whatever
+/// the server sends back
+#[derive(Clone)]
+struct Prediction {
+    /// Predicted state after every input sent so far, the server has the final say
+    state: MovementState,
+    /// State one step behind, rendering blends between the two so movement is smooth at any
+    /// frame rate
+    previous: MovementState,
+    sequence: u32,
+    /// Newest input the server has said it simulated
+    acknowledged: u32,
+    /// Inputs the server hasn't simulated yet, replayed when it sends back its state
+    pending: VecDeque<(u32, MovementInput)>,
+}
+
+impl Prediction {
+    fn new(position: Vec3) -> Self {
+        let state = MovementState::new(position);
+        Self {
+            state,
+            previous: state,
+            sequence: 0,
+            acknowledged: 0,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Simulates one step, returning the sequence number the input should be sent with
+    fn step(&mut self, input: MovementInput) -> u32 {
+        self.sequence += 1;
+        self.previous = self.state;
+        self.state = movement::simulate(self.state, &input);
+        self.pending.push_back((self.sequence, input));
+        self.sequence
+    }
+
+    /// Position `alpha` of the way through the current step
+    fn position(&self, alpha: f32) -> Vec3 {
+        self.previous.position.lerp(self.state.position, alpha)
+    }
+
+    /// Rewinds to the state the server sent and replays the inputs it hasn't got to yet
+    fn reconcile(&mut self, sequence: u32, state: MovementState) {
+        // Snapshots are unreliable, an older one can arrive after a newer one
+        if sequence < self.acknowledged {
+            return;
+        }
+        self.acknowledged = sequence;
+
+        while self
+            .pending
+            .front()
+            .is_some_and(|(pending, _)| *pending <= sequence)
+        {
+            self.pending.pop_front();
+        }
+
+        let predicted = self.state;
+        self.state = self
+            .pending
+            .iter()
+            .fold(state, |state, (_, input)| movement::simulate(state, input));
+        if (predicted.position - self.state.position).length() > EPSILON {
+            warn!(
+                "Mispredicted movement by {}",
+                predicted.position.distance(self.state.position)
+            );
+            // Move the blend along with the correction so it doesn't stretch across the jump
+            self.previous.position += self.state.position - predicted.position;
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Player {
     pub player: RenderObject,
-    jump_t: f32,
+    prediction: Prediction,
+    /// Time that hasn't been simulated yet
+    accumulator: f32,
+    /// Whether space was pressed since the last step, presses shorter than a step would be lost
+    /// otherwise
+    space_pressed: bool,
     pub light: Light,
 }
 
@@ -44,6 +124,7 @@ impl Player {
         model_registry: &mut ModelRegistry,
         transform: Transform,
     ) -> Result<Arc<Mutex<Self>>, vk::Result> {
+        let prediction = Prediction::new(transform.translation);
         let player = RenderObject {
             model: model_registry.load("player.glb"),
             transform,
@@ -51,7 +132,9 @@ impl Player {
 
         let player = Arc::new(Mutex::new(Self {
             player,
-            jump_t: 0.0,
+            prediction,
+            accumulator: 0.0,
+            space_pressed: false,
             light: Light::new(Vec3::ZERO, 5000.0, Vec3::new(1.0, 1.0, 1.0)),
         }));
 
@@ -70,45 +153,42 @@ impl Player {
         viewport: Vec2,
         socket: &Socket,
     ) {
-        let old_translation = self.player.transform.translation.clone();
-
-        // Dash
-        if keyboard.is_key_pressed(VirtualKeyCode::Space) && self.jump_t >= (PI / 4.0) {
-            let mouse_direction = (mouse.position - (viewport / 2.0)).normalize_or_zero();
-            let mouse_direction =
-                camera.get_rotation() * Vec3::new(mouse_direction.x, 0.0, mouse_direction.y);
-            self.player.transform.translation += mouse_direction * DASH_DISTANCE
-        }
+        self.space_pressed |= keyboard.is_key_pressed(VirtualKeyCode::Space);
+        // Don't try to catch up on more than a few frames after a hitch
+        self.accumulator = (self.accumulator + time.delta_seconds()).min(MAX_ACCUMULATED);
 
-        // Jump
-        if keyboard.is_key_pressed(VirtualKeyCode::Space) && self.jump_t == 0.0 {
-            self.jump_t = std::f32::consts::PI - 0.0001;
-        }
+        while self.accumulator >= STEP {
+            let input = MovementInput {
+                keys: MovementKeys {
+                    forward: keyboard.is_key_down(VirtualKeyCode::W),
+                    back: keyboard.is_key_down(VirtualKeyCode::S),
+                    left: keyboard.is_key_down(VirtualKeyCode::A),
+                    right: keyboard.is_key_down(VirtualKeyCode::D),
+                },
+                jump: self.space_pressed,
+                dash: self.space_pressed,
+                camera_theta: camera.theta,
+                mouse_direction: mouse.position - (viewport / 2.0),
+            };
+            self.space_pressed = false;
+            self.accumulator -= STEP;
 
-        self.player.transform.translation.y = self.jump_t.sin().powf(0.6) * JUMP_HEIGHT;
-        self.jump_t -= time.delta_seconds() * JUMP_SPEED;
-        self.jump_t = self.jump_t.max(0.0);
-
-        // Movement
-        let z = keyboard.is_key_down(VirtualKeyCode::W) as i32
-            - keyboard.is_key_down(VirtualKeyCode::S) as i32;
-        let x = keyboard.is_key_down(VirtualKeyCode::D) as i32
-            - keyboard.is_key_down(VirtualKeyCode::A) as i32;
-        if x != 0 || z != 0 {
-            let delta = Vec3::new(x as f32, 0.0, z as f32).normalize()
-                * PLAYER_SPEED
-                * time.delta_seconds();
-            self.player.transform.translation += camera.get_rotation() * delta;
+            let sequence = self.prediction.step(input);
+            let packet =
+                net::server::Packet::PlayerInput(net::server::PlayerInput { sequence, input });
+            if let Err(e) = socket.send(&packet) {
+                warn!("Failed to send input due to {}", e);
+            }
         }
 
+        let alpha = self.accumulator / STEP;
+        self.player.transform.translation = self.prediction.position(alpha);
         self.light.position = self.player.transform.translation + Vec3::new(0.0, 15.0, 0.0);
+    }
 
-        if old_translation != self.player.transform.translation {
-            let packet = net::server::Packet::Move(net::server::Move {
-                position: self.player.transform.translation.clone(),
-            });
-            socket.send(&packet).unwrap();
-        }
+    /// Rewinds to the state the server sent and replays the inputs it hasn't got to yet
+    pub fn reconcile(&mut self, sequence: u32, state: MovementState) {
+        self.prediction.reconcile(sequence, state);
     }
 }
 
@@ -118,8 +198,7 @@ impl Emissive for Player {
             .inventory
             .get_items()
             .iter()
-            .find(|stack| stack.item == Item::Lamp)
-            .is_some()
+            .any(|stack| stack.item.has_tag("light"))
         {
             vec![self.light]
         } else {
@@ -139,3 +218,101 @@ impl Positioned for Player {
         self.player.transform.translation
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forward() -> MovementInput {
+        MovementInput {
+            keys: MovementKeys {
+                forward: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Runs the inputs through the shared simulation, what the server would end up with
+    fn server(from: MovementState, inputs: &[MovementInput]) -> MovementState {
+        inputs.iter().fold(from, movement::simulate)
+    }
+
+    #[test]
+    fn agreeing_with_the_server_changes_nothing() {
+        let mut prediction = Prediction::new(Vec3::ZERO);
+        let inputs = [forward(); 5];
+        for input in inputs {
+            prediction.step(input);
+        }
+        let predicted = prediction.state;
+
+        prediction.reconcile(3, server(MovementState::new(Vec3::ZERO), &inputs[..3]));
+
+        assert_eq!(prediction.state, predicted);
+        assert_eq!(prediction.pending.len(), 2);
+    }
+
+    #[test]
+    fn replays_pending_inputs_after_a_correction() {
+        let mut prediction = Prediction::new(Vec3::ZERO);
+        let inputs = [forward(); 5];
+        for input in inputs {
+            prediction.step(input);
+        }
+
+        // The server had the player somewhere else by the time it got to input 2
+        let corrected = MovementState::new(Vec3::new(10.0, 0.0, 10.0));
+        prediction.reconcile(2, corrected);
+
+        let expected = server(corrected, &inputs[2..]);
+        assert_eq!(prediction.state, expected);
+        assert_eq!(
+            prediction
+                .pending
+                .iter()
+                .map(|(sequence, _)| *sequence)
+                .collect::<Vec<_>>(),
+            vec![3, 4, 5]
+        );
+        // The blend moves with the correction instead of stretching across it
+        assert!(
+            (prediction.state.position - prediction.previous.position).length()
+                < (expected.position - Vec3::ZERO).length()
+        );
+    }
+
+    #[test]
+    fn out_of_order_states_are_ignored() {
+        let mut prediction = Prediction::new(Vec3::ZERO);
+        let inputs = [forward(); 4];
+        for input in inputs {
+            prediction.step(input);
+        }
+
+        let newer = server(MovementState::new(Vec3::ZERO), &inputs[..3]);
+        prediction.reconcile(3, newer);
+        let predicted = prediction.state;
+
+        // State for input 1 turns up after the one for input 3
+        prediction.reconcile(1, MovementState::new(Vec3::splat(100.0)));
+
+        assert_eq!(prediction.state, predicted);
+        assert_eq!(prediction.acknowledged, 3);
+    }
+
+    #[test]
+    fn acknowledging_everything_clears_pending() {
+        let mut prediction = Prediction::new(Vec3::ZERO);
+        let inputs = [forward(); 3];
+        for input in inputs {
+            prediction.step(input);
+        }
+
+        let state = MovementState::new(Vec3::new(1.0, 0.0, 1.0));
+        prediction.reconcile(3, state);
+
+        assert!(prediction.pending.is_empty());
+        assert_eq!(prediction.state, state);
+    }
+}
@@ -13,11 +13,11 @@ use crate::{
         Named, Positioned, Systems,
     },
 };
-use common::item::{Item, ItemStack};
 
 pub struct Tree {
     pub tree: RenderObject,
-    gathered: bool,
+    id: u32,
+    pub depleted: bool,
 }
 
 impl Tree {
@@ -25,6 +25,7 @@ impl Tree {
         renderer: &mut Renderer,
         systems: &mut Systems,
         model_registry: &mut ModelRegistry,
+        id: u32,
         transform: Transform,
     ) -> Result<Arc<Mutex<Tree>>, vk::Result> {
         let tree = RenderObject {
@@ -34,7 +35,8 @@ impl Tree {
 
         let tree = Arc::new(Mutex::new(Self {
             tree,
-            gathered: false,
+            id,
+            depleted: false,
         }));
 
         systems.render.add(tree.clone());
@@ -46,7 +48,7 @@ impl Tree {
 
 impl Renderable for Tree {
     fn get_objects(&self) -> Vec<RenderObject> {
-        if !self.gathered {
+        if !self.depleted {
             vec![self.tree.clone()]
         } else {
             Vec::new()
@@ -68,14 +70,10 @@ impl Positioned for Tree {
 
 impl Interactable for Tree {
     fn interact(&mut self, data: &mut Data) {
-        data.inventory.add(ItemStack {
-            item: Item::Wood,
-            amount: 1,
-        });
-        self.gathered = true;
+        data.inventory.gather(self.id);
     }
 
     fn active(&self) -> bool {
-        !self.gathered
+        !self.depleted
     }
 }
@@ -1,10 +1,17 @@
 use glam::{UVec2, Vec2};
 use std::collections::{HashMap, HashSet};
+use winit::event::VirtualKeyCode;
 
 #[derive(Default)]
 pub struct Keyboard {
     down: HashSet<winit::event::VirtualKeyCode>,
     pressed: HashSet<winit::event::VirtualKeyCode>,
+    /// Whether a text input has the keyboard, keys aren't reported as down or pressed while it
+    /// does so typing doesn't move the player
+    focused: bool,
+    /// Characters typed this frame while focused, enter, escape and backspace are included as
+    /// control characters
+    typed: String,
 }
 
 impl Keyboard {
@@ -13,11 +20,29 @@ impl Keyboard {
     }
 
     pub fn is_key_down(&self, key: winit::event::VirtualKeyCode) -> bool {
-        self.down.contains(&key)
+        !self.focused && self.down.contains(&key)
     }
 
     pub fn is_key_pressed(&self, key: winit::event::VirtualKeyCode) -> bool {
-        self.pressed.contains(&key)
+        !self.focused && self.pressed.contains(&key)
+    }
+
+    pub const fn focus(&mut self) {
+        self.focused = true;
+    }
+
+    pub fn unfocus(&mut self) {
+        self.focused = false;
+        // The key that closed the text input shouldn't also be seen by the game this frame
+        self.pressed.clear();
+    }
+
+    pub const fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    pub fn typed(&self) -> &str {
+        &self.typed
     }
 
     pub fn on_event(&mut self, event: &winit::event::Event<()>) {
@@ -26,6 +51,16 @@ impl Keyboard {
                 if let Some(keycode) = key.virtual_keycode {
                     match key.state {
                         winit::event::ElementState::Pressed => {
+                            // Platforms disagree on whether these send characters, so they're
+                            // always taken from key presses instead
+                            if self.focused {
+                                match keycode {
+                                    VirtualKeyCode::Return => self.typed.push('\r'),
+                                    VirtualKeyCode::Escape => self.typed.push('\u{1b}'),
+                                    VirtualKeyCode::Back => self.typed.push('\u{8}'),
+                                    _ => {}
+                                }
+                            }
                             self.down.insert(keycode);
                             self.pressed.insert(keycode)
                         }
@@ -40,18 +75,30 @@ impl Keyboard {
                 self.down.clear();
                 self.pressed.clear();
             }
+
+            if let winit::event::WindowEvent::ReceivedCharacter(c) = event {
+                if self.focused && !c.is_control() {
+                    self.typed.push(*c);
+                }
+            }
         }
     }
 
     pub fn frame_finished(&mut self) {
         self.pressed.clear();
+        self.typed.clear();
     }
 }
 
+/// Touchpads scroll in pixels, this turns them into roughly the same speed as a wheel
+const PIXELS_PER_LINE: f32 = 20.0;
+
 #[derive(Default)]
 pub struct Mouse {
     pub delta: Vec2,
     pub position: Vec2,
+    /// Lines scrolled this frame, positive is up
+    pub scroll: f32,
     down: HashSet<winit::event::MouseButton>,
     pressed: HashSet<winit::event::MouseButton>,
     scale_factor: Vec2,
@@ -82,6 +129,15 @@ impl Mouse {
                 };
             }
 
+            if let winit::event::WindowEvent::MouseWheel { delta, .. } = event {
+                self.scroll += match delta {
+                    winit::event::MouseScrollDelta::LineDelta(_, y) => *y,
+                    winit::event::MouseScrollDelta::PixelDelta(position) => {
+                        position.y as f32 / PIXELS_PER_LINE
+                    }
+                };
+            }
+
             if let winit::event::WindowEvent::CursorMoved { position, .. } = event {
                 self.position = Vec2::new(position.x as f32, position.y as f32);
             }
@@ -110,5 +166,6 @@ impl Mouse {
     pub fn frame_finished(&mut self) {
         self.pressed.clear();
         self.delta = Vec2::ZERO;
+        self.scroll = 0.0;
     }
 }
@@ -0,0 +1,215 @@
+use common::net::TICK_RATE;
+use glam::Vec3;
+use std::{collections::VecDeque, time::Instant};
+
+/// Remote players are drawn this many ticks behind the server so there's usually a snapshot
+/// either side of them, even if one gets lost
+const INTERPOLATION_DELAY: f64 = 2.0;
+/// How many ticks a player keeps moving along its last velocity once snapshots stop arriving
+const MAX_EXTRAPOLATION: f64 = 4.0;
+const BUFFER_LENGTH: usize = 32;
+/// Fraction of the error the clock corrects on each snapshot, stops jitter shaking players about
+const CLOCK_SMOOTHING: f64 = 0.1;
+/// Past this many ticks out the clock jumps straight to the server's tick
+const CLOCK_SNAP: f64 = 10.0;
+
+/// Estimate of which tick the server is on, fractional between ticks
+pub struct ServerClock {
+    start: Instant,
+    offset: Option<f64>,
+}
+
+impl ServerClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            offset: None,
+        }
+    }
+
+    fn local_ticks(&self, now: Instant) -> f64 {
+        (now - self.start).as_secs_f64() * f64::from(TICK_RATE)
+    }
+
+    pub fn on_snapshot(&mut self, tick: u32, now: Instant) {
+        let target = f64::from(tick) - self.local_ticks(now);
+
+        self.offset = Some(match self.offset {
+            Some(offset) if (target - offset).abs() < CLOCK_SNAP => {
+                (target - offset).mul_add(CLOCK_SMOOTHING, offset)
+            }
+            _ => target,
+        });
+    }
+
+    /// Tick remote players should be drawn at, `None` until the first snapshot arrives
+    pub fn render_tick(&self, now: Instant) -> Option<f64> {
+        self.offset
+            .map(|offset| self.local_ticks(now) + offset - INTERPOLATION_DELAY)
+    }
+}
+
+/// Recent positions of a remote player, oldest first
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<(u32, Vec3)>,
+}
+
+impl SnapshotBuffer {
+    pub fn new() -> Self {
+        Self {
+            snapshots: VecDeque::with_capacity(BUFFER_LENGTH),
+        }
+    }
+
+    /// Snapshots are unreliable, anything arriving after a newer one is dropped
+    pub fn push(&mut self, tick: u32, position: Vec3) {
+        if self
+            .snapshots
+            .back()
+            .is_some_and(|(newest, _)| *newest >= tick)
+        {
+            return;
+        }
+
+        if self.snapshots.len() == BUFFER_LENGTH {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back((tick, position));
+    }
+
+    /// Interpolates between the snapshots either side of `tick`, or extrapolates from the last
+    /// two if it's past the newest one
+    pub fn sample(&self, tick: f64) -> Option<Vec3> {
+        let (first_tick, first) = *self.snapshots.front()?;
+        if tick <= f64::from(first_tick) {
+            return Some(first);
+        }
+
+        let after = self
+            .snapshots
+            .iter()
+            .position(|(snapshot_tick, _)| f64::from(*snapshot_tick) >= tick);
+
+        if let Some(index) = after {
+            let (from_tick, from) = self.snapshots[index - 1];
+            let (to_tick, to) = self.snapshots[index];
+            let t = (tick - f64::from(from_tick)) / f64::from(to_tick - from_tick);
+            return Some(from.lerp(to, t as f32));
+        }
+
+        let (last_tick, last) = *self.snapshots.back()?;
+        let Some((previous_tick, previous)) = self
+            .snapshots
+            .len()
+            .checked_sub(2)
+            .map(|index| self.snapshots[index])
+        else {
+            return Some(last);
+        };
+
+        let velocity = (last - previous) / (last_tick - previous_tick) as f32;
+        let ahead = (tick - f64::from(last_tick)).min(MAX_EXTRAPOLATION);
+        Some(last + velocity * ahead as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn ticks(ticks: f64) -> Duration {
+        Duration::from_secs_f64(ticks / f64::from(TICK_RATE))
+    }
+
+    fn from_snapshots(snapshots: &[(u32, f32)]) -> SnapshotBuffer {
+        let mut buffer = SnapshotBuffer::new();
+        for (tick, x) in snapshots {
+            buffer.push(*tick, Vec3::new(*x, 0.0, 0.0));
+        }
+        buffer
+    }
+
+    fn x(buffer: &SnapshotBuffer, tick: f64) -> Option<f32> {
+        buffer.sample(tick).map(|position| position.x)
+    }
+
+    #[test]
+    fn interpolates_between_snapshots() {
+        let buffer = from_snapshots(&[(10, 0.0), (12, 4.0), (13, 5.0)]);
+
+        assert_eq!(x(&buffer, 11.0), Some(2.0));
+        assert_eq!(x(&buffer, 12.5), Some(4.5));
+        assert_eq!(x(&buffer, 5.0), Some(0.0));
+        assert_eq!(SnapshotBuffer::new().sample(0.0), None);
+    }
+
+    #[test]
+    fn late_snapshots_are_dropped() {
+        // 11 arrives after 12, so the buffer carries on blending 10 into 12
+        let buffer = from_snapshots(&[(10, 0.0), (12, 4.0), (11, 100.0), (12, 100.0)]);
+
+        assert_eq!(x(&buffer, 11.0), Some(2.0));
+        assert_eq!(x(&buffer, 12.0), Some(4.0));
+    }
+
+    #[test]
+    fn only_keeps_the_newest_snapshots() {
+        let snapshots: Vec<(u32, f32)> = (0..40_u16)
+            .map(|tick| (u32::from(tick), f32::from(tick)))
+            .collect();
+        let buffer = from_snapshots(&snapshots);
+
+        assert_eq!(x(&buffer, 0.0), Some(8.0));
+        assert_eq!(x(&buffer, 20.5), Some(20.5));
+    }
+
+    #[test]
+    fn extrapolation_is_limited() {
+        let buffer = from_snapshots(&[(10, 0.0), (12, 4.0)]);
+
+        assert_eq!(x(&buffer, 13.0), Some(6.0));
+        assert_eq!(x(&buffer, 12.0 + MAX_EXTRAPOLATION), Some(12.0));
+        assert_eq!(x(&buffer, 100.0), Some(12.0));
+        // Nothing to work out a velocity from
+        assert_eq!(x(&from_snapshots(&[(12, 4.0)]), 100.0), Some(4.0));
+    }
+
+    #[test]
+    fn clock_renders_behind_the_server() {
+        let mut clock = ServerClock::new();
+        let start = clock.start;
+        assert_eq!(clock.render_tick(start), None);
+
+        clock.on_snapshot(100, start);
+        assert_eq!(clock.render_tick(start), Some(100.0 - INTERPOLATION_DELAY));
+        assert_eq!(
+            clock.render_tick(start + ticks(5.0)),
+            Some(105.0 - INTERPOLATION_DELAY)
+        );
+    }
+
+    #[test]
+    fn clock_smooths_jitter() {
+        let mut clock = ServerClock::new();
+        let start = clock.start;
+        clock.on_snapshot(100, start);
+
+        // A snapshot that took two ticks longer than usual only nudges the clock
+        clock.on_snapshot(101, start + ticks(3.0));
+        let tick = clock.render_tick(start + ticks(3.0)).unwrap_or_default();
+        let expected = 2.0f64.mul_add(-CLOCK_SMOOTHING, 103.0) - INTERPOLATION_DELAY;
+        assert!((tick - expected).abs() < 1e-6, "{tick} != {expected}");
+    }
+
+    #[test]
+    fn clock_snaps_when_far_out() {
+        let mut clock = ServerClock::new();
+        let start = clock.start;
+        clock.on_snapshot(100, start);
+
+        clock.on_snapshot(500, start + ticks(1.0));
+        let tick = clock.render_tick(start + ticks(1.0)).unwrap_or_default();
+        assert!((tick - (500.0 - INTERPOLATION_DELAY)).abs() < 1e-6);
+    }
+}
@@ -11,9 +11,11 @@ mod components;
 mod data;
 mod entities;
 mod input;
+mod interpolation;
 mod macros;
 mod renderer;
 mod scenes;
+mod session;
 mod socket;
 mod systems;
 mod time;
@@ -25,11 +27,12 @@ use assets::{ModelRegistry, ShaderRegistry, TextureRegistry, Transform};
 use bytemuck::cast_slice;
 use camera::Camera;
 use common::{
-    item::{Item, ItemStack},
-    net, Observable, Observer,
+    item::{self, Item, ItemStack},
+    net, recipe, Observable, Observer,
 };
 use glam::{IVec2, Quat, UVec2, Vec2, Vec3, Vec4};
 use input::{Keyboard, Mouse};
+use interpolation::{ServerClock, SnapshotBuffer};
 use num_traits::FromPrimitive;
 use std::{
     collections::HashMap,
@@ -41,7 +44,7 @@ use std::{
     time::Instant,
 };
 use time::Time;
-use tracing::info;
+use tracing::{info, warn};
 use vulkan::Context;
 use winit::{
     event::{MouseButton, VirtualKeyCode},
@@ -49,8 +52,12 @@ use winit::{
 };
 
 use crate::{
-    components::{craft, recipe_selector},
-    data::{inventory::Inventory, Data},
+    components::{chat, craft, furnace, recipe_selector, text_input},
+    data::{
+        chat::{Chat, ChatLine},
+        inventory::Inventory,
+        Data,
+    },
     entities::{Player, Tree},
     renderer::{Renderer, RENDER_HEIGHT, RENDER_WIDTH},
     scenes::RootScene,
@@ -81,6 +88,15 @@ const CAMERA_SENSITIVITY: f32 = 250.0;
 fn main() {
     tracing_subscriber::fmt::init();
 
+    if let Err(e) = load_data() {
+        dialog::Message::new(format!("Failed to load game data: {e}"))
+            .title("Data error")
+            .show()
+            .expect("Failed to show error dialog box");
+
+        return;
+    }
+
     let mut ip = dialog::Input::new("Enter Server IP:")
         .title("IP")
         .show()
@@ -92,9 +108,17 @@ fn main() {
     }
 
     let remote = SocketAddr::new(IpAddr::V4(ip.trim().parse().unwrap()), 8000);
-    let socket: Arc<Socket> = Arc::new(UdpSocket::bind("[::]:0").unwrap().into());
-    socket.connect(remote).unwrap();
-    socket.set_nonblocking(true).unwrap();
+    let socket = match socket::pinned_key().and_then(|key| Socket::connect(remote, key)) {
+        Ok(socket) => Arc::new(socket),
+        Err(e) => {
+            dialog::Message::new(format!("Failed to connect to {remote}: {e}"))
+                .title("Connection error")
+                .show()
+                .expect("Failed to show error dialog box");
+
+            return;
+        }
+    };
 
     let username = dialog::Input::new("Enter username:")
         .title("Username")
@@ -109,46 +133,22 @@ fn main() {
 
         return;
     }
-    let username = username.unwrap();
+    let username = username.unwrap().trim().to_owned();
 
-    let password = dialog::Password::new("Enter password:")
-        .title("Password")
-        .show()
-        .expect("Failed to show password dialog box");
-
-    if password.is_none() || password.as_ref().unwrap().trim().is_empty() {
-        dialog::Message::new("Password cannot be empty")
-            .title("Password error")
-            .show()
-            .expect("Failed to show error dialog box");
+    // Sent on the same channel as logging in, so the server always sees it first
+    let hello = net::server::Packet::Hello(net::server::Hello {
+        protocol_version: net::PROTOCOL_VERSION,
+        build_id: env!("CARGO_PKG_VERSION").to_owned(),
+        data_version: common::data_version(),
+    });
+    socket.send(&hello).unwrap();
 
+    if let Some(token) = session::load(&username) {
+        let resume = net::server::Packet::Resume(net::server::Resume { token });
+        socket.send(&resume).unwrap();
+    } else if !login(&socket, &username) {
         return;
     }
-    let password = password.unwrap();
-
-    match dialog::Question::new("Do you have an existing account")
-        .title("Existing account")
-        .show()
-        .unwrap()
-    {
-        dialog::Choice::Yes => {
-            let login = net::server::Packet::Login(net::server::Login {
-                username: username.trim().to_owned(),
-                password: password.trim().to_owned(),
-            });
-
-            socket.send(&login).unwrap();
-        }
-        dialog::Choice::No => {
-            let signup = net::server::Packet::Signup(net::server::Signup {
-                username: username.trim().to_owned(),
-                password: password.trim().to_owned(),
-            });
-
-            socket.send(&signup).unwrap();
-        }
-        dialog::Choice::Cancel => return,
-    };
 
     let (event_loop, window) = create_window();
     let window = Arc::new(window);
@@ -170,6 +170,8 @@ fn main() {
         inventory: Inventory::new(socket.clone()),
         current_recipe: None,
         recipe_selections: None,
+        furnace: None,
+        chat: Chat::new(socket.clone()),
     };
 
     let ui_pass = Arc::new(Mutex::new(
@@ -206,6 +208,8 @@ fn main() {
         .set_player(root.player.clone());
 
     let mut players: HashMap<String, Arc<Mutex<Player>>> = HashMap::new();
+    let mut snapshots: HashMap<String, SnapshotBuffer> = HashMap::new();
+    let mut server_clock = ServerClock::new();
     let mut last_heartbeat: Instant = Instant::now();
 
     let mut inventory_open = false;
@@ -220,72 +224,123 @@ fn main() {
         keyboard.on_event(&event);
         mouse.on_event(&event);
 
-        let mut buf = [0; 4096];
-        match socket.recv(&mut buf) {
-            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
-            Err(e) => panic!("{e}"),
-            Ok(_) => {
-                let packet: net::client::Packet = postcard::from_bytes(&buf).unwrap();
-
-                match packet {
-                    net::client::Packet::SpawnPlayer(packet) => {
-                        info!("Spawning player");
-                        players.insert(
-                            packet.username,
-                            Player::new(
+        match socket.recv() {
+            Ok(None) => {}
+            Err(e) => warn!("Dropping packet from server due to {}", e),
+            Ok(Some(packets)) => {
+                for packet in packets {
+                    match packet {
+                        net::client::Packet::Rejected(packet) => {
+                            warn!("Server rejected us due to {}", packet.reason);
+                            dialog::Message::new(packet.reason)
+                                .title("Rejected")
+                                .show()
+                                .unwrap();
+                            control_flow.set_exit();
+                            return;
+                        }
+                        net::client::Packet::SpawnPlayer(packet) => {
+                            info!("Spawning player");
+                            snapshots.insert(packet.username.clone(), SnapshotBuffer::new());
+                            players.insert(
+                                packet.username,
+                                Player::new(
+                                    &mut renderer,
+                                    &mut Systems {
+                                        render: &mut render_system.lock().unwrap(),
+                                        interact: &mut interact_system.lock().unwrap(),
+                                    },
+                                    &mut model_registry,
+                                    Transform {
+                                        translation: packet.position,
+                                        rotation: Quat::IDENTITY,
+                                        scale: Vec3::ONE,
+                                    },
+                                )
+                                .unwrap(),
+                            );
+                        }
+                        net::client::Packet::Snapshot(packet) => {
+                            server_clock.on_snapshot(packet.tick, Instant::now());
+                            root.player
+                                .lock()
+                                .unwrap()
+                                .reconcile(packet.sequence, packet.state);
+                            // Snapshots are unreliable so can arrive before a peer is spawned
+                            for state in packet.players {
+                                if let Some(buffer) = snapshots.get_mut(&state.username) {
+                                    buffer.push(packet.tick, state.position.into());
+                                }
+                            }
+                        }
+                        net::client::Packet::DespawnPlayer(packet) => {
+                            info!("Deleting peer player");
+                            players.remove(&packet.username);
+                            snapshots.remove(&packet.username);
+                        }
+                        net::client::Packet::NotifyDisconnection(packet) => {
+                            info!("Disconnecting due to {}", packet.reason);
+                            control_flow.set_exit();
+                            return;
+                        }
+                        net::client::Packet::ModifyInventory(packet) => {
+                            info!("Setting {:?} to {}", packet.stack.item, packet.stack.amount);
+                            data.inventory.set(packet.stack);
+                        }
+                        net::client::Packet::DisplayError(packet) => {
+                            dialog::Message::new(packet.message)
+                                .title("Error")
+                                .show()
+                                .unwrap();
+                            if packet.fatal {
+                                // The saved session may be why we were rejected, make the next
+                                // launch ask for a password instead
+                                if let Err(e) = session::clear() {
+                                    warn!("Failed to clear session due to {}", e);
+                                }
+                                control_flow.set_exit();
+                                return;
+                            }
+                        }
+                        net::client::Packet::SessionToken(packet) => {
+                            info!("Saving session token");
+                            if let Err(e) = session::save(&username, &packet.token) {
+                                warn!("Failed to save session due to {}", e);
+                            }
+                        }
+                        net::client::Packet::WorldSeed(packet) => {
+                            root.load_world(
                                 &mut renderer,
                                 &mut Systems {
                                     render: &mut render_system.lock().unwrap(),
                                     interact: &mut interact_system.lock().unwrap(),
                                 },
                                 &mut model_registry,
-                                Transform {
-                                    translation: packet.position,
-                                    rotation: Quat::IDENTITY,
-                                    scale: Vec3::ONE,
-                                },
+                                packet.seed,
                             )
-                            .unwrap(),
-                        );
-                    }
-                    net::client::Packet::Move(packet) => {
-                        info!("Moving peer player");
-                        players
-                            .get_mut(&packet.username)
-                            .expect("Peer not found")
-                            .lock()
-                            .unwrap()
-                            .player
-                            .transform
-                            .translation = packet.position;
-                    }
-                    net::client::Packet::DespawnPlayer(packet) => {
-                        info!("Deleting peer player");
-                        players.remove(&packet.username);
-                    }
-                    net::client::Packet::NotifyDisconnection(packet) => {
-                        info!("Disconnecting due to {}", packet.reason);
-                        control_flow.set_exit();
-                        return;
-                    }
-                    net::client::Packet::ModifyInventory(packet) => {
-                        info!("Setting {:?} to {}", packet.stack.item, packet.stack.amount);
-                        data.inventory.set(packet.stack);
-                    }
-                    net::client::Packet::DisplayError(packet) => {
-                        dialog::Message::new(packet.message)
-                            .title("Error")
-                            .show()
                             .unwrap();
-                        if packet.fatal {
-                            control_flow.set_exit();
-                            return;
+                        }
+                        net::client::Packet::UpdateNode(packet) => {
+                            root.update_node(packet.id, packet.depleted);
+                        }
+                        net::client::Packet::FurnaceState(packet) => {
+                            data.furnace = Some(packet.furnace);
+                        }
+                        net::client::Packet::ChatMessage(packet) => {
+                            data.chat.push(ChatLine {
+                                sender: packet.sender,
+                                message: packet.message,
+                            });
                         }
                     }
                 }
             }
         };
 
+        if let Err(e) = socket.retransmit() {
+            warn!("Failed to retransmit packets due to {}", e);
+        }
+
         if last_heartbeat.elapsed().as_secs_f32() > 10.0 {
             heartbeat(&socket).unwrap();
             last_heartbeat = Instant::now();
@@ -327,6 +382,31 @@ fn main() {
                     inventory_open = !inventory_open;
                 }
 
+                if keyboard.is_key_pressed(VirtualKeyCode::Return) {
+                    keyboard.focus();
+                } else if keyboard.is_key_pressed(VirtualKeyCode::Slash) {
+                    // The slash itself was typed before the chat had focus
+                    data.chat.draft.push('/');
+                    keyboard.focus();
+                } else if keyboard.is_focused() {
+                    match text_input::edit(
+                        &mut data.chat.draft,
+                        keyboard.typed(),
+                        net::MAX_CHAT_LENGTH,
+                    ) {
+                        text_input::Edit::Editing => {}
+                        text_input::Edit::Submitted => {
+                            data.chat.send();
+                            keyboard.unfocus();
+                        }
+                        text_input::Edit::Cancelled => {
+                            data.chat.draft.clear();
+                            keyboard.unfocus();
+                        }
+                    }
+                    data.chat.scroll(mouse.scroll.round() as isize);
+                }
+
                 renderer.wait_for_frame();
                 render_system
                     .lock()
@@ -354,6 +434,20 @@ fn main() {
                     )
                 }
 
+                if let Some(mut component) = furnace::Component::new(&mut data, &mouse) {
+                    let size = component.layout(SizeConstraints {
+                        min: UVec2::new(0, 0),
+                        max: UVec2::new(480, 270),
+                    });
+                    component.paint(
+                        Region {
+                            origin: UVec2::new(0, 0),
+                            size,
+                        },
+                        &mut scene,
+                    )
+                }
+
                 if let Some(mut component) = recipe_selector::Component::new(&mut data, &mouse) {
                     let size = component.layout(SizeConstraints {
                         min: UVec2::new(0, 0),
@@ -368,6 +462,21 @@ fn main() {
                     )
                 }
 
+                if let Some(mut component) = chat::Component::new(&data.chat, keyboard.is_focused())
+                {
+                    let size = component.layout(SizeConstraints {
+                        min: UVec2::new(0, 0),
+                        max: UVec2::new(RENDER_WIDTH, RENDER_HEIGHT),
+                    });
+                    component.paint(
+                        Region {
+                            origin: UVec2::new(2, RENDER_HEIGHT - (size.y + 2)),
+                            size,
+                        },
+                        &mut scene,
+                    );
+                }
+
                 if inventory_open {
                     let mut inventory_window =
                         components::inventory::Component::new(&data.inventory);
@@ -396,6 +505,20 @@ fn main() {
                 );
 
                 root.frame_finished(&keyboard, &mouse, &camera, &time, viewport, &socket);
+                if let Some(tick) = server_clock.render_tick(Instant::now()) {
+                    for (username, buffer) in &snapshots {
+                        let (Some(peer), Some(position)) =
+                            (players.get(username), buffer.sample(tick))
+                        else {
+                            continue;
+                        };
+                        peer.lock().unwrap().player.transform.translation = position;
+                    }
+                }
+                // Predict smelting between updates so the progress bar moves smoothly
+                if let Some(furnace) = &mut data.furnace {
+                    furnace.advance(time.delta_seconds());
+                }
                 time.frame_finished();
                 keyboard.frame_finished();
                 camera.frame_finished();
@@ -414,6 +537,55 @@ fn main() {
     });
 }
 
+fn load_data() -> Result<()> {
+    item::load_registry(item::REGISTRY_PATH)?;
+    recipe::load_recipe_book(recipe::RECIPE_BOOK_PATH)?;
+    Ok(())
+}
+
+fn login(socket: &Socket, username: &str) -> bool {
+    let password = dialog::Password::new("Enter password:")
+        .title("Password")
+        .show()
+        .expect("Failed to show password dialog box");
+
+    if password.is_none() || password.as_ref().unwrap().trim().is_empty() {
+        dialog::Message::new("Password cannot be empty")
+            .title("Password error")
+            .show()
+            .expect("Failed to show error dialog box");
+
+        return false;
+    }
+    let password = password.unwrap();
+
+    match dialog::Question::new("Do you have an existing account")
+        .title("Existing account")
+        .show()
+        .unwrap()
+    {
+        dialog::Choice::Yes => {
+            let login = net::server::Packet::Login(net::server::Login {
+                username: username.to_owned(),
+                password: password.trim().to_owned(),
+            });
+
+            socket.send(&login).unwrap();
+        }
+        dialog::Choice::No => {
+            let signup = net::server::Packet::Signup(net::server::Signup {
+                username: username.to_owned(),
+                password: password.trim().to_owned(),
+            });
+
+            socket.send(&signup).unwrap();
+        }
+        dialog::Choice::Cancel => return false,
+    };
+
+    true
+}
+
 fn heartbeat(socket: &Socket) -> Result<()> {
     let packet = net::server::Packet::Heartbeat;
     socket.send(&packet)?;
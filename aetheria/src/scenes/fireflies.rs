@@ -1,55 +1,51 @@
 use std::{
+    collections::HashMap,
     ops::{Deref, DerefMut},
     sync::{Arc, Mutex},
 };
 
 use ash::vk;
-use assets::{ModelRegistry, Transform};
+use assets::ModelRegistry;
 use glam::Vec3;
-use rand::Rng;
 
 use crate::{entities::Firefly, renderer::Renderer, systems::Systems};
 
-const NUM_FIREFLIES: u32 = 10;
-
+/// Fireflies from the world layout, keyed by node ID
 pub struct Fireflies {
-    fireflies: Vec<Arc<Mutex<Firefly>>>,
+    fireflies: HashMap<u32, Arc<Mutex<Firefly>>>,
 }
 
 impl Fireflies {
-    pub fn new(
+    pub fn new() -> Self {
+        Self {
+            fireflies: HashMap::new(),
+        }
+    }
+
+    pub fn spawn(
+        &mut self,
         renderer: &mut Renderer,
         systems: &mut Systems,
         model_registry: &mut ModelRegistry,
-    ) -> Result<Self, vk::Result> {
-        let mut fireflies = Vec::new();
-
-        let mut rng = rand::thread_rng();
-
-        for _ in 0..NUM_FIREFLIES {
-            let position = Vec3::new(
-                rng.gen_range(-400.0..400.0),
-                50.0,
-                rng.gen_range(-400.0..400.0),
-            );
-            fireflies.push(
-                Firefly::new(
-                    renderer,
-                    systems,
-                    model_registry,
-                    position,
-                    Vec3::new(1.0, 1.0, 1.0),
-                )
-                .unwrap(),
-            );
-        }
-
-        Ok(Self { fireflies })
+        id: u32,
+        position: Vec3,
+    ) -> Result<(), vk::Result> {
+        let firefly = Firefly::new(
+            renderer,
+            systems,
+            model_registry,
+            id,
+            position,
+            Vec3::new(1.0, 1.0, 1.0),
+        )?;
+        self.fireflies.insert(id, firefly);
+
+        Ok(())
     }
 }
 
 impl Deref for Fireflies {
-    type Target = Vec<Arc<Mutex<Firefly>>>;
+    type Target = HashMap<u32, Arc<Mutex<Firefly>>>;
 
     fn deref(&self) -> &Self::Target {
         &self.fireflies
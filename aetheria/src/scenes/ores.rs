@@ -1,5 +1,5 @@
 use std::{
-    f32::consts::PI,
+    collections::HashMap,
     ops::{Deref, DerefMut},
     sync::{Arc, Mutex},
 };
@@ -8,53 +8,51 @@ use crate::{entities::CopperOre, renderer::Renderer, systems::Systems};
 use ash::vk;
 use assets::{ModelRegistry, Transform};
 use glam::{Quat, Vec3};
-use rand::Rng;
-
-const NUM_ORES: u32 = 10;
 
+/// Ores from the world layout, keyed by node ID
 pub struct Ores {
-    trees: Vec<Arc<Mutex<CopperOre>>>,
+    ores: HashMap<u32, Arc<Mutex<CopperOre>>>,
 }
 
 impl Ores {
-    pub fn new(
+    pub fn new() -> Self {
+        Self {
+            ores: HashMap::new(),
+        }
+    }
+
+    pub fn spawn(
+        &mut self,
         renderer: &mut Renderer,
         systems: &mut Systems,
         model_registry: &mut ModelRegistry,
-    ) -> Result<Self, vk::Result> {
-        let mut trees = Vec::new();
-
-        let mut rng = rand::thread_rng();
-
-        for _ in 0..NUM_ORES {
-            let translation = Vec3::new(
-                rng.gen_range(-400.0..400.0),
-                0.0,
-                rng.gen_range(-400.0..400.0),
-            );
-            let rotation = Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), rng.gen_range(-PI..PI));
-            let transform = Transform {
-                translation,
-                rotation,
-                scale: Vec3::new(0.1, 0.1, 0.1),
-            };
-            trees.push(CopperOre::new(renderer, systems, model_registry, transform).unwrap());
-        }
-
-        Ok(Self { trees })
+        id: u32,
+        translation: Vec3,
+        yaw: f32,
+    ) -> Result<(), vk::Result> {
+        let rotation = Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), yaw);
+        let transform = Transform {
+            translation,
+            rotation,
+            scale: Vec3::new(0.1, 0.1, 0.1),
+        };
+        let ore = CopperOre::new(renderer, systems, model_registry, id, transform)?;
+        self.ores.insert(id, ore);
+
+        Ok(())
     }
 }
 
 impl Deref for Ores {
-    type Target = Vec<Arc<Mutex<CopperOre>>>;
+    type Target = HashMap<u32, Arc<Mutex<CopperOre>>>;
 
     fn deref(&self) -> &Self::Target {
-        &self.trees
+        &self.ores
     }
 }
 
 impl DerefMut for Ores {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.trees
+        &mut self.ores
     }
 }
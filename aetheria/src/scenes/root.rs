@@ -5,6 +5,7 @@ use std::{
 
 use ash::vk;
 use assets::{ModelRegistry, Transform};
+use common::{node::NodeKind, recipe::Station, worldgen};
 use glam::{Quat, Vec2, Vec3};
 
 use crate::{
@@ -25,8 +26,8 @@ pub struct RootScene {
     pub grass: Arc<Mutex<Grass>>,
     pub trees: Trees,
     pub fireflies: Fireflies,
-    pub furnace: Arc<Mutex<Furnace>>,
-    pub crafting_bench: Arc<Mutex<CraftingBench>>,
+    pub furnace: Option<Arc<Mutex<Furnace>>>,
+    pub crafting_bench: Option<Arc<Mutex<CraftingBench>>>,
     pub ores: Ores,
 }
 
@@ -51,44 +52,105 @@ impl RootScene {
         );
         let grass = Grass::new(renderer, systems, model_registry, Transform::IDENTITY).unwrap();
 
-        let trees = Trees::new(renderer, systems, model_registry)?;
-        let fireflies = Fireflies::new(renderer, systems, model_registry)?;
-
-        let furnace = Furnace::new(
-            renderer,
-            systems,
-            model_registry,
-            Transform {
-                translation: Vec3::new(100.0, 0.0, 100.0),
-                scale: Vec3::new(0.2, 0.2, 0.2),
-                ..Default::default()
-            },
-        )?;
-
-        let ores = Ores::new(renderer, systems, model_registry)?;
-
-        let crafting_bench = CraftingBench::new(
-            renderer,
-            systems,
-            model_registry,
-            Transform {
-                translation: Vec3::new(100.0, 0.0, 30.0),
-                rotation: Quat::IDENTITY,
-                scale: Vec3::new(0.1, 0.1, 0.1),
-            },
-        )?;
         Ok(Self {
             player,
             sun,
             grass,
-            trees,
-            fireflies,
-            furnace,
-            crafting_bench,
-            ores,
+            trees: Trees::new(),
+            fireflies: Fireflies::new(),
+            furnace: None,
+            crafting_bench: None,
+            ores: Ores::new(),
         })
     }
 
+    /// Places the nodes and stations generated from the world seed, replacing any from a
+    /// previous login. Systems only hold weak references, so dropping the old ones is enough to
+    /// remove them.
+    pub fn load_world(
+        &mut self,
+        renderer: &mut Renderer,
+        systems: &mut Systems,
+        model_registry: &mut ModelRegistry,
+        seed: u32,
+    ) -> Result<(), vk::Result> {
+        let layout = worldgen::generate(seed);
+
+        self.trees.clear();
+        self.ores.clear();
+        self.fireflies.clear();
+
+        for (id, node) in layout.nodes() {
+            match node.kind {
+                NodeKind::Tree => self.trees.spawn(
+                    renderer,
+                    systems,
+                    model_registry,
+                    id,
+                    node.position,
+                    node.yaw,
+                )?,
+                NodeKind::CopperOre => self.ores.spawn(
+                    renderer,
+                    systems,
+                    model_registry,
+                    id,
+                    node.position,
+                    node.yaw,
+                )?,
+                NodeKind::Firefly => {
+                    self.fireflies
+                        .spawn(renderer, systems, model_registry, id, node.position)?
+                }
+            }
+        }
+
+        for placement in layout.stations {
+            match placement.station {
+                Station::CraftingBench => {
+                    self.crafting_bench = Some(CraftingBench::new(
+                        renderer,
+                        systems,
+                        model_registry,
+                        Transform {
+                            translation: placement.position,
+                            rotation: Quat::IDENTITY,
+                            scale: Vec3::new(0.1, 0.1, 0.1),
+                        },
+                    )?);
+                }
+                Station::Furnace => {
+                    self.furnace = Some(Furnace::new(
+                        renderer,
+                        systems,
+                        model_registry,
+                        Transform {
+                            translation: placement.position,
+                            scale: Vec3::new(0.2, 0.2, 0.2),
+                            ..Default::default()
+                        },
+                    )?);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Depleted nodes stay in the scene but are hidden and can't be interacted with until they
+    /// respawn
+    pub fn update_node(&mut self, id: u32, depleted: bool) {
+        if let Some(tree) = self.trees.get(&id) {
+            tree.lock().unwrap().depleted = depleted;
+        }
+        if let Some(ore) = self.ores.get(&id) {
+            ore.lock().unwrap().depleted = depleted;
+        }
+        if let Some(firefly) = self.fireflies.get(&id) {
+            firefly.lock().unwrap().depleted = depleted;
+        }
+    }
+
     pub fn frame_finished(
         &mut self,
         keyboard: &Keyboard,
@@ -103,7 +165,7 @@ impl RootScene {
             .unwrap()
             .frame_finished(keyboard, mouse, camera, time, viewport, socket);
         self.sun.lock().unwrap().frame_finished(time);
-        self.fireflies.iter_mut().for_each(|firefly| {
+        self.fireflies.values_mut().for_each(|firefly| {
             firefly
                 .lock()
                 .unwrap()
@@ -1,5 +1,5 @@
 use std::{
-    f32::consts::PI,
+    collections::HashMap,
     ops::{Deref, DerefMut},
     sync::{Arc, Mutex},
 };
@@ -7,47 +7,45 @@ use std::{
 use ash::vk;
 use assets::{ModelRegistry, Transform};
 use glam::{Quat, Vec3};
-use rand::Rng;
 
 use crate::{entities::Tree, renderer::Renderer, systems::Systems};
 
-const NUM_TREES: u32 = 10;
-
+/// Trees from the world layout, keyed by node ID
 pub struct Trees {
-    trees: Vec<Arc<Mutex<Tree>>>,
+    trees: HashMap<u32, Arc<Mutex<Tree>>>,
 }
 
 impl Trees {
-    pub fn new(
+    pub fn new() -> Self {
+        Self {
+            trees: HashMap::new(),
+        }
+    }
+
+    pub fn spawn(
+        &mut self,
         renderer: &mut Renderer,
         systems: &mut Systems,
         model_registry: &mut ModelRegistry,
-    ) -> Result<Self, vk::Result> {
-        let mut trees = Vec::new();
-
-        let mut rng = rand::thread_rng();
-
-        for _ in 0..NUM_TREES {
-            let translation = Vec3::new(
-                rng.gen_range(-400.0..400.0),
-                0.0,
-                rng.gen_range(-400.0..400.0),
-            );
-            let rotation = Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), rng.gen_range(-PI..PI));
-            let transform = Transform {
-                translation,
-                rotation,
-                scale: Vec3::new(0.1, 0.1, 0.1),
-            };
-            trees.push(Tree::new(renderer, systems, model_registry, transform).unwrap());
-        }
-
-        Ok(Self { trees })
+        id: u32,
+        translation: Vec3,
+        yaw: f32,
+    ) -> Result<(), vk::Result> {
+        let rotation = Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), yaw);
+        let transform = Transform {
+            translation,
+            rotation,
+            scale: Vec3::new(0.1, 0.1, 0.1),
+        };
+        let tree = Tree::new(renderer, systems, model_registry, id, transform)?;
+        self.trees.insert(id, tree);
+
+        Ok(())
     }
 }
 
 impl Deref for Trees {
-    type Target = Vec<Arc<Mutex<Tree>>>;
+    type Target = HashMap<u32, Arc<Mutex<Tree>>>;
 
     fn deref(&self) -> &Self::Target {
         &self.trees
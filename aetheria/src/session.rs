@@ -0,0 +1,27 @@
+use std::{fs, io, path::Path};
+
+const SESSION_PATH: &str = ".session";
+
+/// Returns the saved session token if one was stored for this username
+pub fn load(username: &str) -> Option<String> {
+    let contents = fs::read_to_string(SESSION_PATH).ok()?;
+    let (saved_username, token) = contents.trim().split_once(' ')?;
+
+    if saved_username == username {
+        Some(token.to_owned())
+    } else {
+        None
+    }
+}
+
+pub fn save(username: &str, token: &str) -> io::Result<()> {
+    fs::write(SESSION_PATH, format!("{username} {token}"))
+}
+
+pub fn clear() -> io::Result<()> {
+    if Path::new(SESSION_PATH).exists() {
+        fs::remove_file(SESSION_PATH)?;
+    }
+
+    Ok(())
+}
@@ -1,8 +1,20 @@
-use common::net;
+use common::net::{
+    self,
+    reliable::{Delivery, Endpoint, EndpointError},
+    secure::{self, Frame, Handshake, SecureError, Session},
+};
 use std::{
-    net::UdpSocket,
-    ops::{Deref, DerefMut},
+    io,
+    net::{SocketAddr, UdpSocket},
+    sync::Mutex,
+    time::Duration,
 };
+use tracing::{info, warn};
+
+const HANDSHAKE_ATTEMPTS: u32 = 5;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+/// The server's public key, the arbiter writes it next to itself and its operator hands it out
+const SERVER_KEY_PATH: &str = "server.pub";
 
 #[derive(thiserror::Error, Debug)]
 pub enum PacketSendError {
@@ -10,36 +22,175 @@ pub enum PacketSendError {
     IOError(#[from] std::io::Error),
     #[error("Error encoding packet")]
     PostcardError(#[from] postcard::Error),
+    #[error("Error wrapping packet")]
+    EndpointError(#[from] EndpointError),
+    #[error("Error sealing packet")]
+    SecureError(#[from] SecureError),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PacketReceiveError {
+    #[error("Error receiving packet")]
+    IOError(#[from] std::io::Error),
+    #[error("Error decoding packet")]
+    PostcardError(#[from] postcard::Error),
+    #[error("Error unwrapping packet")]
+    EndpointError(#[from] EndpointError),
+    #[error("Error opening packet")]
+    SecureError(#[from] SecureError),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConnectError {
+    #[error("Error connecting")]
+    IOError(#[from] std::io::Error),
+    #[error("Handshake failed")]
+    SecureError(#[from] SecureError),
+    #[error("Server didn't respond to handshake")]
+    TimedOut,
+    #[error(
+        "Can't read the server's key from {SERVER_KEY_PATH}, ask the server's operator for it"
+    )]
+    MissingServerKey(#[source] std::io::Error),
+    #[error("{SERVER_KEY_PATH} doesn't contain a valid key")]
+    InvalidServerKey,
+}
+
+/// The key the server has to prove it holds, so nobody in between can pretend to be it
+pub fn pinned_key() -> Result<[u8; 32], ConnectError> {
+    let contents =
+        std::fs::read_to_string(SERVER_KEY_PATH).map_err(ConnectError::MissingServerKey)?;
+    secure::parse_public_key(&contents).ok_or(ConnectError::InvalidServerKey)
+}
+
+struct State {
+    session: Session,
+    endpoint: Endpoint,
 }
 
 pub struct Socket {
     inner: UdpSocket,
+    state: Mutex<State>,
 }
 
 impl Socket {
+    /// Binds a local socket and performs the key exchange with the server, which has to hold
+    /// `server_key`. The returned socket is non-blocking.
+    pub fn connect(remote: SocketAddr, server_key: [u8; 32]) -> Result<Self, ConnectError> {
+        let inner = UdpSocket::bind("[::]:0")?;
+        inner.connect(remote)?;
+        inner.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+
+        let handshake = Handshake::new(server_key);
+        let hello = handshake.hello()?;
+        let mut buf = [0; net::MAX_DATAGRAM_SIZE];
+
+        for attempt in 1..=HANDSHAKE_ATTEMPTS {
+            info!("Sending handshake to {}, attempt {}", remote, attempt);
+            inner.send(&hello)?;
+
+            match inner.recv(&mut buf) {
+                Ok(length) => {
+                    let session = handshake.complete(&buf[..length])?;
+                    inner.set_read_timeout(None)?;
+                    inner.set_nonblocking(true)?;
+
+                    return Ok(Self {
+                        inner,
+                        state: Mutex::new(State {
+                            session,
+                            endpoint: Endpoint::new(),
+                        }),
+                    });
+                }
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Err(ConnectError::TimedOut)
+    }
+
     pub fn send(&self, packet: &net::server::Packet) -> Result<(), PacketSendError> {
         let bytes = postcard::to_stdvec(packet)?;
-        self.inner.send(&bytes)?;
+        let sealed = {
+            let mut state = self.state.lock().unwrap();
+            state
+                .endpoint
+                .send(packet.channel(), bytes)?
+                .iter()
+                .map(|message| state.session.seal(message))
+                .collect::<Result<Vec<Vec<u8>>, SecureError>>()?
+        };
+
+        for datagram in sealed {
+            self.inner.send(&datagram)?;
+        }
+
         Ok(())
     }
-}
 
-impl Deref for Socket {
-    type Target = UdpSocket;
+    /// Resends reliable packets the server hasn't acknowledged yet, call this every frame
+    pub fn retransmit(&self) -> Result<(), PacketSendError> {
+        let sealed = {
+            let mut state = self.state.lock().unwrap();
+            state
+                .endpoint
+                .retransmit()
+                .iter()
+                .map(|message| state.session.seal(message))
+                .collect::<Result<Vec<Vec<u8>>, SecureError>>()?
+        };
 
-    fn deref(&self) -> &Self::Target {
-        &self.inner
-    }
-}
+        for datagram in sealed {
+            self.inner.send(&datagram)?;
+        }
 
-impl DerefMut for Socket {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.inner
+        Ok(())
     }
-}
 
-impl From<UdpSocket> for Socket {
-    fn from(value: UdpSocket) -> Self {
-        Self { inner: value }
+    /// Returns the packets made ready by the next datagram, or `None` once there is nothing left
+    /// to read
+    pub fn recv(&self) -> Result<Option<Vec<net::client::Packet>>, PacketReceiveError> {
+        let mut buf = [0; net::MAX_DATAGRAM_SIZE];
+        let length = match self.inner.recv(&mut buf) {
+            Ok(length) => length,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let Frame::Sealed {
+            counter,
+            ciphertext,
+        } = postcard::from_bytes(&buf[..length])?
+        else {
+            warn!("Dropping unexpected frame from server");
+            return Ok(Some(Vec::new()));
+        };
+
+        let (received, reply) = {
+            let mut state = self.state.lock().unwrap();
+            let message = state.session.open(counter, &ciphertext)?;
+            let received = state.endpoint.receive(&message)?;
+            let reply = received
+                .reply
+                .as_ref()
+                .map(|reply| state.session.seal(reply))
+                .transpose()?;
+            (received.payloads, reply)
+        };
+
+        if let Some(reply) = reply {
+            self.inner.send(&reply)?;
+        }
+
+        Ok(Some(
+            received
+                .iter()
+                .map(|payload| postcard::from_bytes(payload))
+                .collect::<Result<Vec<net::client::Packet>, postcard::Error>>()?,
+        ))
     }
 }
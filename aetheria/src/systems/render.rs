@@ -67,10 +67,37 @@ struct MeshData {
     transform: [f32; 16],
 }
 
+/// Where an image's pixels start in the texel buffer and its size, `offset` is -1 for none
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct TextureRegion {
+    offset: i32,
+    width: i32,
+    height: i32,
+    _padding: [i32; 1],
+}
+
+impl TextureRegion {
+    const NONE: Self = Self {
+        offset: -1,
+        width: 0,
+        height: 0,
+        _padding: [0],
+    };
+}
+
+impl Default for TextureRegion {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
 pub struct Material {
     albedo: Vec4,
+    albedo_texture: TextureRegion,
+    normal_texture: TextureRegion,
 }
 
 #[repr(C)]
@@ -146,6 +173,7 @@ impl System {
             .add(vk::DescriptorType::STORAGE_BUFFER)
             .add(vk::DescriptorType::STORAGE_BUFFER)
             .add(vk::DescriptorType::STORAGE_BUFFER)
+            .add(vk::DescriptorType::STORAGE_BUFFER)
             .build()?;
         let mut geometry_pool = Pool::new(ctx.device.clone(), geometry_layout.clone(), 1)?;
         let geometry_set = geometry_pool.allocate()?;
@@ -191,8 +219,33 @@ impl System {
         let mut vertices: Vec<Vertex> = Vec::new();
         let mut indices: Vec<i32> = Vec::new();
         let mut materials: Vec<Material> = Vec::new();
+        // Every model's images packed one after another, one RGBA8 texel per u32
+        let mut texels: Vec<u32> = Vec::new();
 
         let mut mesh_to_index: HashMap<Uuid, i32> = HashMap::new();
+        let mut image_to_region: HashMap<Uuid, TextureRegion> = HashMap::new();
+
+        for image in model_registry
+            .get_models()
+            .iter()
+            .flat_map(|model| &model.images)
+        {
+            image_to_region.insert(
+                image.id,
+                TextureRegion {
+                    offset: texels.len() as i32,
+                    width: image.width as i32,
+                    height: image.height as i32,
+                    ..TextureRegion::NONE
+                },
+            );
+            texels.extend(
+                image
+                    .pixels
+                    .chunks_exact(4)
+                    .map(|pixel| u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]])),
+            );
+        }
 
         for mesh in model_registry
             .get_models()
@@ -211,14 +264,14 @@ impl System {
             vertices.append(&mut mesh.vertices.clone());
         }
 
-        for (i, (mesh, transform)) in objects
+        for (i, (model, mesh, transform)) in objects
             .iter()
             .flat_map(|object| {
                 object
                     .model
                     .meshes
                     .iter()
-                    .map(|mesh| (mesh, object.transform.clone()))
+                    .map(|mesh| (&object.model, mesh, object.transform.clone()))
             })
             .enumerate()
         {
@@ -237,7 +290,19 @@ impl System {
                 ..Default::default()
             };
             meshes.push(mesh_data);
-            materials.push(Material { albedo: mesh.color });
+
+            let region = |image: Option<usize>| {
+                image
+                    .and_then(|image| model.images.get(image))
+                    .and_then(|image| image_to_region.get(&image.id))
+                    .copied()
+                    .unwrap_or(TextureRegion::NONE)
+            };
+            materials.push(Material {
+                albedo: mesh.material.base_color,
+                albedo_texture: region(mesh.material.base_color_texture),
+                normal_texture: region(mesh.material.normal_texture),
+            });
         }
 
         let mut mesh_data = cast_slice::<i32, u8>(&[meshes.len() as i32, 0, 0, 0]).to_vec();
@@ -269,6 +334,17 @@ impl System {
         )
         .unwrap();
 
+        // Storage buffers can't be empty
+        if texels.is_empty() {
+            texels.push(0);
+        }
+        let texel_buffer = Buffer::new(
+            &renderer,
+            cast_slice::<u32, u8>(&texels),
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+        )
+        .unwrap();
+
         let mut light_data = cast_slice::<Light, u8>(&lights).to_vec();
         let mut light_buffer = cast_slice::<i32, u8>(&[lights.len() as i32, 0, 0, 0]).to_vec();
         light_buffer.append(&mut light_data);
@@ -289,6 +365,8 @@ impl System {
             .update_buffer(&renderer.device, 4, &material_buffer);
         self.geometry_set
             .update_buffer(&renderer.device, 5, &light_buffer);
+        self.geometry_set
+            .update_buffer(&renderer.device, 6, &texel_buffer);
     }
 
     pub fn get_texture(&self) -> &'_ Texture {
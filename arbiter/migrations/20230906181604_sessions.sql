@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS sessions
+(
+  id INTEGER PRIMARY KEY NOT NULL UNIQUE,
+  token TEXT NOT NULL UNIQUE,
+  owner INTEGER NOT NULL,
+  expires INTEGER NOT NULL,
+  FOREIGN KEY (owner) REFERENCES users (id)
+);
@@ -0,0 +1,4 @@
+-- Stacks used to be overwritten across every character, keep one row per character and item
+DELETE FROM items WHERE id NOT IN (SELECT MAX(id) FROM items GROUP BY owner, item);
+DELETE FROM items WHERE quantity <= 0;
+CREATE UNIQUE INDEX IF NOT EXISTS items_owner_item ON items (owner, item);
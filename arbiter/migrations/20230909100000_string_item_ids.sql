@@ -0,0 +1,24 @@
+-- Items are now identified by their registry ID rather than the old enum discriminant
+CREATE TABLE items_new
+(
+  id INTEGER PRIMARY KEY NOT NULL UNIQUE,
+  item TEXT NOT NULL,
+  quantity INTEGER NOT NULL,
+  owner INTEGER NOT NULL,
+  FOREIGN KEY (owner) REFERENCES characters (id)
+);
+INSERT INTO items_new (id, item, quantity, owner)
+SELECT id,
+  CASE item
+    WHEN 0 THEN 'wood'
+    WHEN 1 THEN 'fireglow'
+    WHEN 2 THEN 'lamp'
+    WHEN 3 THEN 'copper_ore'
+    WHEN 4 THEN 'copper_ingot'
+    WHEN 5 THEN 'copper_sword'
+  END,
+  quantity, owner
+FROM items WHERE item BETWEEN 0 AND 5;
+DROP TABLE items;
+ALTER TABLE items_new RENAME TO items;
+CREATE UNIQUE INDEX IF NOT EXISTS items_owner_item ON items (owner, item);
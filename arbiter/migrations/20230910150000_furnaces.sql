@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS furnaces
+(
+  id INTEGER PRIMARY KEY NOT NULL UNIQUE,
+  owner INTEGER NOT NULL UNIQUE,
+  input_item TEXT,
+  input_amount INTEGER NOT NULL,
+  fuel_item TEXT,
+  fuel_amount INTEGER NOT NULL,
+  output_item TEXT,
+  output_amount INTEGER NOT NULL,
+  burn_remaining REAL NOT NULL,
+  progress REAL NOT NULL,
+  updated INTEGER NOT NULL,
+  FOREIGN KEY (owner) REFERENCES characters (id)
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS nodes
+(
+  id INTEGER PRIMARY KEY NOT NULL UNIQUE,
+  kind TEXT NOT NULL,
+  position_x REAL NOT NULL,
+  position_y REAL NOT NULL,
+  position_z REAL NOT NULL,
+  respawn_at INTEGER
+);
@@ -0,0 +1,14 @@
+-- Node layout now comes from the world seed, only depletion needs storing
+DROP TABLE IF EXISTS nodes;
+
+CREATE TABLE IF NOT EXISTS world
+(
+  id INTEGER PRIMARY KEY NOT NULL CHECK (id = 0),
+  seed INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS depleted_nodes
+(
+  id INTEGER PRIMARY KEY NOT NULL UNIQUE,
+  respawn_at INTEGER NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN permission INTEGER NOT NULL DEFAULT 0;
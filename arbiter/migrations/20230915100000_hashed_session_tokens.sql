@@ -0,0 +1,3 @@
+-- Tokens are only stored hashed now, the plaintext ones can't be kept so everyone logs in again
+DELETE FROM sessions;
+ALTER TABLE sessions RENAME COLUMN token TO token_hash;
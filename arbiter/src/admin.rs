@@ -0,0 +1,231 @@
+use crate::command::Permission;
+use anyhow::{anyhow, Result};
+use async_std::{
+    channel::{self, Sender},
+    io::{self, prelude::BufReadExt, BufReader, ReadExt, WriteExt},
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use common::item::Item;
+use std::{net::SocketAddr, str::FromStr};
+use tracing::{info, warn};
+
+const HELP: &str = "Commands: list, kick PLAYER [REASON], give PLAYER ITEM [AMOUNT], say MESSAGE, \
+                    inventory PLAYER, permission PLAYER player|moderator|admin, shutdown";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Request {
+    Help,
+    /// Everyone in `Server.online`
+    List,
+    Kick {
+        player: String,
+        reason: Option<String>,
+    },
+    Give {
+        player: String,
+        item: Item,
+        amount: u32,
+    },
+    /// Sends a chat message from the server to everyone
+    Say(String),
+    Inventory(String),
+    Permission {
+        player: String,
+        permission: Permission,
+    },
+    /// Saves everything, tells clients and stops the arbiter
+    Shutdown,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum RequestError {
+    #[error("Unknown command {0}, try help")]
+    Unknown(String),
+    #[error("Usage: {0}")]
+    Usage(&'static str),
+}
+
+impl FromStr for Request {
+    type Err = RequestError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let args = rest.split_whitespace().collect::<Vec<&str>>();
+
+        match (name, args.as_slice()) {
+            ("help", _) => Ok(Self::Help),
+            ("list", []) => Ok(Self::List),
+            ("kick", [player, reason @ ..]) => Ok(Self::Kick {
+                player: (*player).to_owned(),
+                reason: (!reason.is_empty()).then(|| reason.join(" ")),
+            }),
+            ("kick", _) => Err(RequestError::Usage("kick PLAYER [REASON]")),
+            ("give", [player, item]) => Ok(Self::Give {
+                player: (*player).to_owned(),
+                item: Item::new(item),
+                amount: 1,
+            }),
+            ("give", [player, item, amount]) => Ok(Self::Give {
+                player: (*player).to_owned(),
+                item: Item::new(item),
+                amount: amount
+                    .parse()
+                    .ok()
+                    .filter(|amount| *amount > 0)
+                    .ok_or(RequestError::Usage("give PLAYER ITEM [AMOUNT]"))?,
+            }),
+            ("give", _) => Err(RequestError::Usage("give PLAYER ITEM [AMOUNT]")),
+            ("say", [_, ..]) => Ok(Self::Say(rest.trim().to_owned())),
+            ("say", []) => Err(RequestError::Usage("say MESSAGE")),
+            ("inventory", [player]) => Ok(Self::Inventory((*player).to_owned())),
+            ("inventory", _) => Err(RequestError::Usage("inventory PLAYER")),
+            ("permission", [player, permission]) => Ok(Self::Permission {
+                player: (*player).to_owned(),
+                permission: permission.parse().map_err(|()| {
+                    RequestError::Usage("permission PLAYER player|moderator|admin")
+                })?,
+            }),
+            ("permission", _) => Err(RequestError::Usage(
+                "permission PLAYER player|moderator|admin",
+            )),
+            ("shutdown", []) => Ok(Self::Shutdown),
+            _ => Err(RequestError::Unknown(name.to_owned())),
+        }
+    }
+}
+
+/// A request for the server loop along with where to write its output, the request is finished
+/// once `output` is dropped
+pub struct Message {
+    pub request: Request,
+    pub output: Sender<String>,
+}
+
+/// Reads requests line by line, writing each one's output before reading the next
+async fn session<R, W>(reader: R, mut writer: W, requests: Sender<Message>) -> io::Result<()>
+where
+    R: io::BufRead + Unpin,
+    W: io::Write + Unpin,
+{
+    let mut lines = reader.lines();
+    while let Some(line) = lines.next().await {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request = match line.parse::<Request>() {
+            Ok(Request::Help) => {
+                writer.write_all(format!("{HELP}\n").as_bytes()).await?;
+                continue;
+            }
+            Ok(request) => request,
+            Err(e) => {
+                writer.write_all(format!("{e}\n").as_bytes()).await?;
+                continue;
+            }
+        };
+
+        let (output, replies) = channel::unbounded();
+        if requests.send(Message { request, output }).await.is_err() {
+            // The server has stopped
+            return Ok(());
+        }
+        while let Ok(reply) = replies.recv().await {
+            writer.write_all(format!("{reply}\n").as_bytes()).await?;
+        }
+        writer.flush().await?;
+    }
+
+    Ok(())
+}
+
+/// Takes requests from the terminal the arbiter was started in
+pub fn spawn_console(requests: Sender<Message>) {
+    task::spawn(async move {
+        let stdin = BufReader::new(io::stdin());
+        if let Err(e) = session(stdin, io::stdout(), requests).await {
+            warn!("Admin console stopped due to {}", e);
+        }
+    });
+}
+
+/// Compares in constant time so the token can't be guessed a byte at a time
+fn token_matches(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+async fn accept(stream: TcpStream, token: String, requests: Sender<Message>) -> io::Result<()> {
+    let peer = stream.peer_addr()?;
+    let mut reader = BufReader::new(stream.clone());
+    let mut writer = stream;
+
+    let mut given = String::new();
+    // Bound the first line so an unauthenticated client can't make us buffer forever
+    (&mut reader)
+        .take(token.len() as u64 + 2)
+        .read_line(&mut given)
+        .await?;
+    if !token_matches(given.trim_end(), &token) {
+        warn!("Admin connection from {} gave a bad token", peer);
+        writer.write_all(b"Bad token\n").await?;
+        return Ok(());
+    }
+
+    info!("Admin connected from {}", peer);
+    writer.write_all(b"Authenticated\n").await?;
+    session(reader, writer, requests).await?;
+    info!("Admin disconnected from {}", peer);
+    Ok(())
+}
+
+/// Accepts remote admin connections, each must send the token as its first line
+pub async fn listen(addr: SocketAddr, token: String, requests: Sender<Message>) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Admin interface listening on {}", addr);
+
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(stream) = incoming.next().await {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("Failed to accept admin connection due to {}", e);
+                    continue;
+                }
+            };
+
+            let token = token.clone();
+            let requests = requests.clone();
+            task::spawn(async move {
+                if let Err(e) = accept(stream, token, requests).await {
+                    warn!("Admin connection failed due to {}", e);
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+/// `ADMIN_ADDR` turns on the remote interface, it's refused without an `ADMIN_TOKEN` to go
+/// with it
+pub fn remote_from_env() -> Result<Option<(SocketAddr, String)>> {
+    let Ok(addr) = std::env::var("ADMIN_ADDR") else {
+        return Ok(None);
+    };
+    let token = std::env::var("ADMIN_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| anyhow!("ADMIN_ADDR is set but ADMIN_TOKEN isn't"))?;
+
+    Ok(Some((addr.parse()?, token)))
+}
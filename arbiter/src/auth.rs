@@ -0,0 +1,198 @@
+use crate::time::unix_time;
+use anyhow::Result;
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::{
+    fmt::Write,
+    time::{Duration, SystemTime},
+};
+use tracing::info;
+
+const SESSION_LIFETIME: Duration = Duration::from_hours(7 * 24);
+const TOKEN_LENGTH: usize = 32;
+
+#[derive(thiserror::Error, Debug)]
+pub enum AuthError {
+    #[error("Password hashing failed")]
+    Hash(#[from] argon2::password_hash::Error),
+    #[error("Database error")]
+    Database(#[from] sqlx::Error),
+}
+
+// Argon2 is deliberately slow, keep it off the executor threads
+pub async fn hash_password(password: String) -> Result<String, AuthError> {
+    async_std::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Ok(Argon2::default()
+            .hash_password(password.as_bytes(), &salt)?
+            .to_string())
+    })
+    .await
+}
+
+pub async fn verify_password(password: String, hash: String) -> Result<bool, AuthError> {
+    async_std::task::spawn_blocking(move || {
+        let hash = PasswordHash::new(&hash)?;
+        Ok(Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok())
+    })
+    .await
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+fn generate_token() -> String {
+    let mut bytes = [0_u8; TOKEN_LENGTH];
+    OsRng.fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+/// Only this is stored, so a leaked database can't be used to resume anyone's session. Tokens are
+/// random so they don't need a slow or salted hash like passwords do.
+fn hash_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+/// Starts a new session for the user, signing them out anywhere else they were logged in
+pub async fn create_session(pool: &SqlitePool, user_id: i64) -> Result<String, AuthError> {
+    let token = generate_token();
+    let token_hash = hash_token(&token);
+    let expires = unix_time(SystemTime::now() + SESSION_LIFETIME);
+
+    let mut tx = pool.begin().await?;
+    sqlx::query!("DELETE FROM sessions WHERE owner = ?", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        "INSERT INTO sessions (token_hash, owner, expires) VALUES (?, ?, ?)",
+        token_hash,
+        user_id,
+        expires
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(token)
+}
+
+/// Returns the user ID the token belongs to if it exists and hasn't expired
+pub async fn resume_session(pool: &SqlitePool, token: &str) -> Result<Option<i64>, AuthError> {
+    let now = unix_time(SystemTime::now());
+
+    sqlx::query!("DELETE FROM sessions WHERE expires < ?", now)
+        .execute(pool)
+        .await?;
+
+    let token_hash = hash_token(token);
+    let session = sqlx::query!(
+        "SELECT owner FROM sessions WHERE token_hash = ?",
+        token_hash
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(session.map(|session| session.owner))
+}
+
+/// Hashes any passwords that were stored in plaintext before hashing was introduced
+pub async fn upgrade_passwords(pool: &SqlitePool) -> Result<()> {
+    let users = sqlx::query!("SELECT id, password_hash FROM users")
+        .fetch_all(pool)
+        .await?;
+
+    for user in users {
+        if PasswordHash::new(&user.password_hash).is_ok() {
+            continue;
+        }
+
+        let hash = hash_password(user.password_hash).await?;
+        sqlx::query!(
+            "UPDATE users SET password_hash = ? WHERE id = ?",
+            hash,
+            user.id
+        )
+        .execute(pool)
+        .await?;
+
+        info!("Upgraded plaintext password for user {}", user.id);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    async fn sessions(pool: &SqlitePool, user: i64) -> Result<Vec<String>> {
+        Ok(
+            sqlx::query!("SELECT token_hash FROM sessions WHERE owner = ?", user)
+                .fetch_all(pool)
+                .await?
+                .into_iter()
+                .map(|session| session.token_hash)
+                .collect(),
+        )
+    }
+
+    #[async_std::test]
+    async fn only_the_token_hash_is_stored() -> Result<()> {
+        let pool = testing::pool().await?;
+        let user = testing::user(&pool, "jw").await?;
+
+        let token = create_session(&pool, user).await?;
+        assert_eq!(sessions(&pool, user).await?, [hash_token(&token)]);
+        assert_eq!(resume_session(&pool, &token).await?, Some(user));
+        // Someone reading the database can't use what's in it
+        assert_eq!(resume_session(&pool, &hash_token(&token)).await?, None);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn logging_in_revokes_earlier_sessions() -> Result<()> {
+        let pool = testing::pool().await?;
+        let user = testing::user(&pool, "jw").await?;
+        let other = testing::user(&pool, "other").await?;
+
+        let first = create_session(&pool, user).await?;
+        let other_token = create_session(&pool, other).await?;
+        let second = create_session(&pool, user).await?;
+
+        assert_eq!(resume_session(&pool, &first).await?, None);
+        assert_eq!(resume_session(&pool, &second).await?, Some(user));
+        assert_eq!(sessions(&pool, user).await?.len(), 1);
+        assert_eq!(resume_session(&pool, &other_token).await?, Some(other));
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn expired_sessions_are_removed() -> Result<()> {
+        let pool = testing::pool().await?;
+        let user = testing::user(&pool, "jw").await?;
+
+        let token = create_session(&pool, user).await?;
+        sqlx::query!("UPDATE sessions SET expires = 0")
+            .execute(&pool)
+            .await?;
+
+        assert_eq!(resume_session(&pool, &token).await?, None);
+        assert!(sessions(&pool, user).await?.is_empty());
+
+        Ok(())
+    }
+}
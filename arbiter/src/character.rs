@@ -0,0 +1,24 @@
+use glam::Vec3;
+use sqlx::SqlitePool;
+
+/// Writes the positions of several characters in a single transaction
+pub async fn save_positions(
+    pool: &SqlitePool,
+    positions: &[(i64, Vec3)],
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    for (character_id, position) in positions {
+        sqlx::query!(
+            "UPDATE characters SET position_x = ?, position_y = ?, position_z = ? WHERE id = ?",
+            position.x,
+            position.y,
+            position.z,
+            character_id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await
+}
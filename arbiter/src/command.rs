@@ -0,0 +1,178 @@
+use common::item::Item;
+use glam::Vec3;
+use std::str::FromStr;
+
+/// What a user is allowed to run, stored as a number in the users table
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    Player,
+    Moderator,
+    Admin,
+}
+
+impl Permission {
+    pub const fn from_level(level: i64) -> Self {
+        match level {
+            i64::MIN..=0 => Self::Player,
+            1 => Self::Moderator,
+            _ => Self::Admin,
+        }
+    }
+
+    pub const fn level(self) -> i64 {
+        match self {
+            Self::Player => 0,
+            Self::Moderator => 1,
+            Self::Admin => 2,
+        }
+    }
+}
+
+impl FromStr for Permission {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "player" => Ok(Self::Player),
+            "moderator" => Ok(Self::Moderator),
+            "admin" => Ok(Self::Admin),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Name, who can run it and how, in the order `/help` lists them
+const COMMANDS: [(&str, Permission, &str); 5] = [
+    ("help", Permission::Player, "/help"),
+    ("who", Permission::Player, "/who"),
+    (
+        "tp",
+        Permission::Moderator,
+        "/tp X Z or /tp PLAYER or /tp PLAYER X Z",
+    ),
+    ("kick", Permission::Moderator, "/kick PLAYER REASON"),
+    ("give", Permission::Admin, "/give PLAYER ITEM AMOUNT"),
+];
+
+#[derive(thiserror::Error, Debug)]
+pub enum CommandError {
+    #[error("Unknown command {0} try /help")]
+    Unknown(String),
+    #[error("Usage {0}")]
+    Usage(&'static str),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Destination {
+    Position(Vec3),
+    Player(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Help,
+    Who,
+    /// Moves `player`, or whoever ran the command if it's `None`
+    Teleport {
+        player: Option<String>,
+        destination: Destination,
+    },
+    Kick {
+        player: String,
+        reason: Option<String>,
+    },
+    Give {
+        player: String,
+        item: Item,
+        amount: u32,
+    },
+}
+
+impl Command {
+    const fn name(&self) -> &'static str {
+        match self {
+            Self::Help => "help",
+            Self::Who => "who",
+            Self::Teleport { .. } => "tp",
+            Self::Kick { .. } => "kick",
+            Self::Give { .. } => "give",
+        }
+    }
+
+    pub fn permission(&self) -> Permission {
+        COMMANDS
+            .iter()
+            .find(|(name, ..)| *name == self.name())
+            .map_or(Permission::Admin, |(_, permission, _)| *permission)
+    }
+}
+
+/// Usage of every command someone with `permission` can run
+pub fn available(permission: Permission) -> impl Iterator<Item = &'static str> {
+    COMMANDS
+        .iter()
+        .filter(move |(_, required, _)| *required <= permission)
+        .map(|(.., usage)| *usage)
+}
+
+fn position(x: &str, z: &str) -> Option<Vec3> {
+    let x = x.parse::<f32>().ok().filter(|x| x.is_finite())?;
+    let z = z.parse::<f32>().ok().filter(|z| z.is_finite())?;
+    Some(Vec3::new(x, 0.0, z))
+}
+
+impl FromStr for Command {
+    type Err = CommandError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut words = line.trim().trim_start_matches('/').split_whitespace();
+        let name = words.next().unwrap_or_default().to_lowercase();
+        let args = words.collect::<Vec<&str>>();
+
+        let Some((.., usage)) = COMMANDS.iter().find(|(command, ..)| *command == name) else {
+            return Err(CommandError::Unknown(name));
+        };
+        let invalid = || CommandError::Usage(usage);
+
+        match (name.as_str(), args.as_slice()) {
+            ("help", []) => Ok(Self::Help),
+            ("who", []) => Ok(Self::Who),
+            ("tp", [x, z]) => Ok(Self::Teleport {
+                player: None,
+                destination: Destination::Position(position(x, z).ok_or_else(invalid)?),
+            }),
+            ("tp", [player]) => Ok(Self::Teleport {
+                player: None,
+                destination: Destination::Player((*player).to_owned()),
+            }),
+            ("tp", [player, x, z]) => Ok(Self::Teleport {
+                player: Some((*player).to_owned()),
+                destination: Destination::Position(position(x, z).ok_or_else(invalid)?),
+            }),
+            ("kick", [player, reason @ ..]) => Ok(Self::Kick {
+                player: (*player).to_owned(),
+                reason: (!reason.is_empty()).then(|| reason.join(" ")),
+            }),
+            ("give", [player, rest @ ..]) => {
+                // Chat can't type underscores, so item IDs are given as separate words
+                let (item, amount) = match rest
+                    .split_last()
+                    .map(|(amount, item)| (item, amount.parse::<u32>()))
+                {
+                    Some((item, Ok(amount))) => (item, amount),
+                    _ => (rest, 1),
+                };
+                if item.is_empty() || amount == 0 {
+                    return Err(invalid());
+                }
+
+                Ok(Self::Give {
+                    player: (*player).to_owned(),
+                    item: Item::new(&item.join("_").to_lowercase()),
+                    amount,
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
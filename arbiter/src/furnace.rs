@@ -0,0 +1,179 @@
+use crate::{inventory, time::unix_millis};
+use common::{
+    furnace::{Furnace, FurnaceSlot},
+    item::{Item, ItemStack},
+};
+use sqlx::{SqliteConnection, SqlitePool};
+use std::time::{Duration, SystemTime};
+
+fn stack(item: Option<String>, amount: i64) -> Option<ItemStack> {
+    Some(ItemStack {
+        item: Item::new(&item?),
+        amount: u32::try_from(amount).ok().filter(|amount| *amount > 0)?,
+    })
+}
+
+/// The database only has 64 bit floats, but these columns are only ever written from `f32`s so
+/// narrowing them back loses nothing
+#[allow(clippy::cast_possible_truncation)]
+const fn narrow(value: f64) -> f32 {
+    value as f32
+}
+
+fn columns(stack: Option<&ItemStack>) -> (Option<&str>, i64) {
+    stack.map_or((None, 0), |stack| {
+        (Some(stack.item.id()), i64::from(stack.amount))
+    })
+}
+
+/// The character's furnace caught up to the current time, furnaces that were never used start
+/// empty. Save it afterwards or the catching up is done again next time.
+pub async fn load(conn: &mut SqliteConnection, owner: i64) -> Result<Furnace, sqlx::Error> {
+    let Some(row) = sqlx::query!(
+        "SELECT input_item, input_amount, fuel_item, fuel_amount, output_item, output_amount,
+         burn_remaining, progress, updated FROM furnaces WHERE owner = ?",
+        owner
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    else {
+        return Ok(Furnace::default());
+    };
+
+    let mut furnace = Furnace {
+        input: stack(row.input_item, row.input_amount),
+        fuel: stack(row.fuel_item, row.fuel_amount),
+        output: stack(row.output_item, row.output_amount),
+        burn_remaining: narrow(row.burn_remaining),
+        progress: narrow(row.progress),
+    };
+
+    // A clock that went backwards counts as no time passing
+    let elapsed = u64::try_from(unix_millis(SystemTime::now()) - row.updated).unwrap_or(0);
+    furnace.advance(Duration::from_millis(elapsed).as_secs_f32());
+
+    Ok(furnace)
+}
+
+pub async fn save(
+    conn: &mut SqliteConnection,
+    owner: i64,
+    furnace: &Furnace,
+) -> Result<(), sqlx::Error> {
+    let (input_item, input_amount) = columns(furnace.input.as_ref());
+    let (fuel_item, fuel_amount) = columns(furnace.fuel.as_ref());
+    let (output_item, output_amount) = columns(furnace.output.as_ref());
+    let updated = unix_millis(SystemTime::now());
+
+    sqlx::query!(
+        "INSERT INTO furnaces (owner, input_item, input_amount, fuel_item, fuel_amount,
+         output_item, output_amount, burn_remaining, progress, updated)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT (owner) DO UPDATE SET input_item = excluded.input_item,
+         input_amount = excluded.input_amount, fuel_item = excluded.fuel_item,
+         fuel_amount = excluded.fuel_amount, output_item = excluded.output_item,
+         output_amount = excluded.output_amount, burn_remaining = excluded.burn_remaining,
+         progress = excluded.progress, updated = excluded.updated",
+        owner,
+        input_item,
+        input_amount,
+        fuel_item,
+        fuel_amount,
+        output_item,
+        output_amount,
+        furnace.burn_remaining,
+        furnace.progress,
+        updated
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Moves the stack from the owner's inventory into the furnace slot. Returns the furnace and the
+/// owner's remaining stack, or a message for the player if it isn't allowed.
+pub async fn load_into(
+    pool: &SqlitePool,
+    owner: i64,
+    slot: FurnaceSlot,
+    stack: &ItemStack,
+) -> Result<Result<(Furnace, ItemStack), &'static str>, sqlx::Error> {
+    if stack.amount == 0 || !Furnace::accepts(slot, &stack.item) {
+        return Ok(Err("That can't go in there"));
+    }
+
+    let mut tx = pool.begin().await?;
+    let mut furnace = load(&mut tx, owner).await?;
+
+    let amount = match furnace.slot(slot) {
+        Some(existing) if existing.item != stack.item => {
+            return Ok(Err("That slot has something else in it"))
+        }
+        Some(existing) => existing.amount + stack.amount,
+        None => stack.amount,
+    };
+    if amount > stack.item.max_stack() {
+        return Ok(Err("There isn't enough room in the furnace"));
+    }
+
+    let delta = -i64::from(stack.amount);
+    let Some(remaining) = inventory::adjust(&mut tx, owner, &stack.item, delta).await? else {
+        return Ok(Err("You don't have enough of that"));
+    };
+
+    *furnace.slot_mut(slot) = Some(ItemStack {
+        item: stack.item.clone(),
+        amount,
+    });
+    save(&mut tx, owner, &furnace).await?;
+    tx.commit().await?;
+
+    Ok(Ok((
+        furnace,
+        ItemStack {
+            item: stack.item.clone(),
+            amount: remaining,
+        },
+    )))
+}
+
+/// Moves everything in the output slot into the owner's inventory. Returns the furnace and the
+/// owner's new stack, or a message for the player if it isn't allowed.
+pub async fn take_output(
+    pool: &SqlitePool,
+    owner: i64,
+) -> Result<Result<(Furnace, ItemStack), &'static str>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let mut furnace = load(&mut tx, owner).await?;
+
+    let Some(output) = furnace.output.take() else {
+        return Ok(Err("There's nothing to take"));
+    };
+
+    let delta = i64::from(output.amount);
+    let Some(amount) = inventory::adjust(&mut tx, owner, &output.item, delta).await? else {
+        return Ok(Err("There isn't enough room in your inventory"));
+    };
+
+    save(&mut tx, owner, &furnace).await?;
+    tx.commit().await?;
+
+    Ok(Ok((
+        furnace,
+        ItemStack {
+            item: output.item,
+            amount,
+        },
+    )))
+}
+
+/// Catches the furnace up and saves it so the client gets a fresh view
+pub async fn open(pool: &SqlitePool, owner: i64) -> Result<Furnace, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let furnace = load(&mut tx, owner).await?;
+    save(&mut tx, owner, &furnace).await?;
+    tx.commit().await?;
+
+    Ok(furnace)
+}
@@ -0,0 +1,46 @@
+use anyhow::{anyhow, Result};
+use common::net::secure::{self, ServerKey};
+use std::{fs, io::Write, path::Path};
+use tracing::info;
+
+/// Where the arbiter keeps its long-term key, overridden by `SERVER_KEY`
+const SECRET_PATH: &str = "server.key";
+/// Clients read the key they pin from here, give it to players along with the address
+const PUBLIC_PATH: &str = "server.pub";
+
+fn write_secret(path: &Path, key: &ServerKey) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options.open(path)?.write_all(&key.to_bytes())?;
+    Ok(())
+}
+
+/// Loads the key the arbiter proves itself with during handshakes, generating one on the first
+/// run. Its public half is written to `server.pub` every time so it can't go stale.
+pub fn load() -> Result<ServerKey> {
+    let secret_path = std::env::var("SERVER_KEY").unwrap_or_else(|_| SECRET_PATH.to_owned());
+    let secret_path = Path::new(&secret_path);
+
+    let key = if secret_path.exists() {
+        let bytes = fs::read(secret_path)?;
+        ServerKey::from_bytes(
+            bytes
+                .try_into()
+                .map_err(|_| anyhow!("{} isn't a 32 byte key", secret_path.display()))?,
+        )
+    } else {
+        let key = ServerKey::generate();
+        write_secret(secret_path, &key)?;
+        info!("Generated a new server key in {}", secret_path.display());
+        key
+    };
+
+    let public_key = secure::encode_public_key(&key.public_key());
+    fs::write(PUBLIC_PATH, format!("{public_key}\n"))?;
+    info!("Server public key is {}", public_key);
+
+    Ok(key)
+}
@@ -0,0 +1,97 @@
+use glam::{Vec2, Vec3};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+/// Default for how far away players and nodes are replicated, overridden by `INTEREST_RADIUS`
+pub const DEFAULT_RADIUS: f32 = 500.0;
+/// Things only drop out of view this far past the radius, so walking along the edge doesn't
+/// spawn and despawn them every move
+pub const MARGIN: f32 = 50.0;
+
+pub fn radius_from_env() -> f32 {
+    std::env::var("INTEREST_RADIUS")
+        .ok()
+        .and_then(|radius| radius.parse().ok())
+        .unwrap_or(DEFAULT_RADIUS)
+}
+
+/// Buckets keys by their position on the XZ plane so nearby lookups don't have to check
+/// everything
+pub struct Grid<K> {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), HashSet<K>>,
+    positions: HashMap<K, Vec3>,
+}
+
+impl<K> Grid<K>
+where
+    K: Eq + Hash + Copy,
+{
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn cell(&self, position: Vec3) -> (i32, i32) {
+        (
+            (position.x / self.cell_size).floor() as i32,
+            (position.z / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// Inserts the key or moves it if it's already in the grid
+    pub fn insert(&mut self, key: K, position: Vec3) {
+        self.remove(&key);
+        let cell = self.cell(position);
+        self.cells.entry(cell).or_default().insert(key);
+        self.positions.insert(key, position);
+    }
+
+    pub fn remove(&mut self, key: &K) {
+        let Some(position) = self.positions.remove(key) else {
+            return;
+        };
+
+        let cell = self.cell(position);
+        if let Some(keys) = self.cells.get_mut(&cell) {
+            keys.remove(key);
+            if keys.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    pub fn position(&self, key: &K) -> Option<Vec3> {
+        self.positions.get(key).copied()
+    }
+
+    /// Keys within `radius` of `position` on the XZ plane
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn nearby(&self, position: Vec3, radius: f32) -> Vec<K> {
+        let (x, z) = self.cell(position);
+        let reach = (radius / self.cell_size).ceil() as i32;
+
+        (x - reach..=x + reach)
+            .flat_map(|x| (z - reach..=z + reach).map(move |z| (x, z)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .filter(|key| {
+                self.positions
+                    .get(key)
+                    .is_some_and(|other| distance(position, *other) <= radius)
+            })
+            .copied()
+            .collect()
+    }
+}
+
+/// Players and nodes are replicated by distance along the ground, height doesn't matter
+pub fn distance(a: Vec3, b: Vec3) -> f32 {
+    Vec2::new(a.x - b.x, a.z - b.z).length()
+}
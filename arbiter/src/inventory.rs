@@ -0,0 +1,204 @@
+use common::item::{Item, ItemStack};
+use sqlx::{SqliteConnection, SqlitePool};
+use tracing::error;
+
+/// Every stack the character owns, stacks with unknown item IDs are skipped
+pub async fn load(pool: &SqlitePool, owner: i64) -> Result<Vec<ItemStack>, sqlx::Error> {
+    let rows = sqlx::query!("SELECT item, quantity FROM items WHERE owner = ?", owner)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            let item = Item::new(&row.item);
+            if item.def().is_none() {
+                error!("Unknown item ID in database {}", row.item);
+                return None;
+            }
+
+            Some(ItemStack {
+                item,
+                amount: u32::try_from(row.quantity).unwrap_or(0),
+            })
+        })
+        .collect())
+}
+
+/// Changes the owner's stack of `item` by `delta`, returning the new amount or `None` if there
+/// aren't enough items to take away or the stack would go over the item's max stack size.
+/// Stacks that reach zero are deleted. Run this inside a
+/// transaction and roll back on `None`, the row is left negative otherwise.
+pub async fn adjust(
+    conn: &mut SqliteConnection,
+    owner: i64,
+    item: &Item,
+    delta: i64,
+) -> Result<Option<u32>, sqlx::Error> {
+    let id = item.id();
+
+    let quantity = sqlx::query_scalar!(
+        "INSERT INTO items (item, quantity, owner) VALUES (?, ?, ?)
+         ON CONFLICT (owner, item) DO UPDATE SET quantity = quantity + excluded.quantity
+         RETURNING quantity",
+        id,
+        delta,
+        owner
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let Ok(amount) = u32::try_from(quantity) else {
+        return Ok(None);
+    };
+
+    if amount > item.max_stack() {
+        return Ok(None);
+    }
+
+    if amount == 0 {
+        sqlx::query!("DELETE FROM items WHERE owner = ? AND item = ?", owner, id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok(Some(amount))
+}
+
+/// Applies all the changes or none of them, returns the resulting stacks or `None` if the
+/// owner didn't have enough of something
+pub async fn apply(
+    pool: &SqlitePool,
+    owner: i64,
+    changes: &[(Item, i64)],
+) -> Result<Option<Vec<ItemStack>>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let mut stacks = Vec::new();
+
+    for (item, delta) in changes {
+        let Some(amount) = adjust(&mut tx, owner, item, *delta).await? else {
+            // Dropping the transaction rolls it back
+            return Ok(None);
+        };
+
+        stacks.push(ItemStack {
+            item: item.clone(),
+            amount,
+        });
+    }
+
+    tx.commit().await?;
+
+    Ok(Some(stacks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use anyhow::Result;
+
+    async fn stacks(pool: &SqlitePool, owner: i64) -> Result<Vec<(String, i64)>, sqlx::Error> {
+        let mut stacks = sqlx::query!("SELECT item, quantity FROM items WHERE owner = ?", owner)
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(|row| (row.item, row.quantity))
+            .collect::<Vec<(String, i64)>>();
+        stacks.sort();
+        Ok(stacks)
+    }
+
+    #[async_std::test]
+    async fn upserts_one_row_per_owner_and_item() -> Result<()> {
+        testing::load_data()?;
+        let pool = testing::pool().await?;
+        let alice = testing::character(&pool, "alice").await?;
+        let bob = testing::character(&pool, "bob").await?;
+        let wood = Item::new("wood");
+
+        apply(&pool, alice, &[(wood.clone(), 5)]).await?;
+        apply(&pool, alice, &[(wood.clone(), 3)]).await?;
+        apply(&pool, bob, &[(wood.clone(), 2)]).await?;
+
+        assert_eq!(stacks(&pool, alice).await?, [("wood".to_owned(), 8)]);
+        assert_eq!(stacks(&pool, bob).await?, [("wood".to_owned(), 2)]);
+        assert_eq!(
+            load(&pool, alice).await?,
+            [ItemStack {
+                item: wood,
+                amount: 8
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn rolls_back_when_going_negative() -> Result<()> {
+        testing::load_data()?;
+        let pool = testing::pool().await?;
+        let owner = testing::character(&pool, "alice").await?;
+        apply(&pool, owner, &[(Item::new("copper_ore"), 2)]).await?;
+
+        // The ingot would be added before the ore runs out, neither change may stick
+        let result = apply(
+            &pool,
+            owner,
+            &[
+                (Item::new("copper_ingot"), 1),
+                (Item::new("copper_ore"), -3),
+            ],
+        )
+        .await?;
+
+        assert_eq!(result, None);
+        assert_eq!(stacks(&pool, owner).await?, [("copper_ore".to_owned(), 2)]);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn rolls_back_past_the_stack_limit() -> Result<()> {
+        testing::load_data()?;
+        let pool = testing::pool().await?;
+        let owner = testing::character(&pool, "alice").await?;
+        let lamp = Item::new("lamp");
+        assert_eq!(lamp.max_stack(), 1);
+
+        apply(&pool, owner, &[(lamp.clone(), 1)]).await?;
+        let result = apply(&pool, owner, &[(Item::new("wood"), 4), (lamp, 1)]).await?;
+
+        assert_eq!(result, None);
+        assert_eq!(stacks(&pool, owner).await?, [("lamp".to_owned(), 1)]);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn deletes_stacks_that_reach_zero() -> Result<()> {
+        testing::load_data()?;
+        let pool = testing::pool().await?;
+        let owner = testing::character(&pool, "alice").await?;
+        let wood = Item::new("wood");
+        apply(
+            &pool,
+            owner,
+            &[(wood.clone(), 4), (Item::new("fireglow"), 1)],
+        )
+        .await?;
+
+        let result = apply(&pool, owner, &[(wood.clone(), -4)]).await?;
+
+        assert_eq!(
+            result,
+            Some(vec![ItemStack {
+                item: wood,
+                amount: 0
+            }])
+        );
+        assert_eq!(stacks(&pool, owner).await?, [("fireglow".to_owned(), 1)]);
+
+        Ok(())
+    }
+}
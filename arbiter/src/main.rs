@@ -3,33 +3,84 @@
 #![deny(clippy::unwrap_used)]
 #![warn(clippy::expect_used)]
 
+mod admin;
+mod auth;
+mod character;
+mod command;
+mod furnace;
+mod identity;
+mod interest;
+mod inventory;
+mod movement;
+mod signal;
+#[cfg(test)]
+mod testing;
+mod time;
+mod world;
+
 use anyhow::Result;
-use async_std::net::UdpSocket;
+use async_std::{
+    channel::{self, Receiver},
+    net::UdpSocket,
+};
+use command::{Command, Destination, Permission};
 use common::{
-    item::{Item, ItemStack},
-    net,
+    furnace::Furnace,
+    item::{self, Item, ItemStack},
+    movement::MovementState,
+    net::{
+        self,
+        reliable::{Delivery, Endpoint, EndpointError},
+        secure::{Frame, SecureError, ServerKey, Session},
+    },
+    recipe::{self, Recipe, Station},
 };
 use glam::Vec3;
-use num_traits::{FromPrimitive, ToPrimitive};
+use interest::Grid;
+use movement::InputQueue;
 use sqlx::SqlitePool;
 use std::{
     collections::{
-        hash_map::{Keys, Values},
-        HashMap,
+        hash_map::{Keys, Values, ValuesMut},
+        HashMap, HashSet,
     },
     hash::Hash,
     net::SocketAddr,
     ops::Deref,
-    time::Instant,
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
 };
 use tracing::{error, info, warn};
-
-#[derive(Clone, PartialEq, Eq)]
+use world::World;
+
+const TICK: Duration = Duration::from_millis(1000 / net::TICK_RATE as u64);
+/// Heartbeats and node respawns are checked once a second
+const SLOW_TICK_INTERVAL: u32 = net::TICK_RATE;
+/// Positions are only kept in memory between saves
+const SAVE_INTERVAL: u32 = net::TICK_RATE * 5;
+/// How long a disconnected peer's session is kept so the notice telling them why can be
+/// retransmitted, it's dropped sooner once they ack it
+const CLOSE_GRACE: Duration = Duration::from_secs(2);
+
+#[derive(Clone, PartialEq)]
 struct Connection {
     last_heartbeat: Instant,
+    /// Crafting takes time, no other crafts are accepted until then
+    busy_until: Instant,
     addr: SocketAddr,
     user_id: i64,
     character_id: i64,
+    username: String,
+    permission: Permission,
+    /// Authoritative movement state, only ever advanced by simulating the client's inputs
+    state: MovementState,
+    inputs: InputQueue,
+    /// Whether the position has changed since it was last saved
+    unsaved: bool,
+    /// Players this client has been told to spawn
+    visible: HashSet<SocketAddr>,
+    /// Nodes this client has been told are depleted, anything else it assumes is available
+    depleted_nodes: HashSet<u32>,
 }
 
 trait Unique {
@@ -89,6 +140,10 @@ where
         self.inner.values()
     }
 
+    pub fn values_mut(&mut self) -> ValuesMut<'_, T::Key, T> {
+        self.inner.values_mut()
+    }
+
     pub fn keys<'a>(&'a self) -> Keys<'a, T::Key, T> {
         self.inner.keys()
     }
@@ -111,10 +166,34 @@ where
     }
 }
 
+struct Peer {
+    session: Session,
+    endpoint: Endpoint,
+    last_seen: Instant,
+    /// Whether the peer sent a `Hello` we accepted
+    greeted: bool,
+    /// The `ClientHello` that started the session and our reply, resent if the client retries
+    client_public_key: [u8; 32],
+    server_hello: Vec<u8>,
+    /// Set once the player is disconnected, the session is only kept until this to deliver what
+    /// was last sent to them
+    closing: Option<Instant>,
+}
+
 struct Server {
     socket: UdpSocket,
+    key: ServerKey,
     online: IndexedMap<Connection>,
     pool: SqlitePool,
+    sessions: Mutex<HashMap<SocketAddr, Peer>>,
+    world: World,
+    players: Grid<SocketAddr>,
+    /// How far away players and nodes are replicated to each client
+    interest_radius: f32,
+    tick: u32,
+    /// Set by the admin console, the main loop shuts down once it sees this. SIGINT and SIGTERM
+    /// do the same through `signal::received`.
+    stopping: bool,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -123,14 +202,61 @@ enum SendError {
     IOError(#[from] std::io::Error),
     #[error("Encode error")]
     EncodeError(#[from] postcard::Error),
+    #[error("Reliability layer error")]
+    EndpointError(#[from] EndpointError),
+    #[error("Secure transport error")]
+    SecureError(#[from] SecureError),
+    #[error("No session for address")]
+    NoSession,
+}
+
+#[derive(thiserror::Error, Debug)]
+enum ReceiveError {
+    #[error("IO error")]
+    IOError(#[from] std::io::Error),
+    #[error("Decode error")]
+    DecodeError(#[from] postcard::Error),
+    #[error("Reliability layer error")]
+    EndpointError(#[from] EndpointError),
+    #[error("Secure transport error")]
+    SecureError(#[from] SecureError),
 }
 
 impl Server {
-    pub fn new(socket: UdpSocket, pool: SqlitePool) -> Self {
+    pub fn new(
+        socket: UdpSocket,
+        key: ServerKey,
+        pool: SqlitePool,
+        world: World,
+        interest_radius: f32,
+    ) -> Self {
         Self {
             socket,
+            key,
             online: IndexedMap::new(),
             pool,
+            sessions: Mutex::new(HashMap::new()),
+            world,
+            players: Grid::new(interest_radius),
+            interest_radius,
+            tick: 0,
+            stopping: false,
+        }
+    }
+
+    fn sessions(&self) -> MutexGuard<'_, HashMap<SocketAddr, Peer>> {
+        self.sessions
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn greeted(&self, addr: &SocketAddr) -> bool {
+        self.sessions().get(addr).is_some_and(|peer| peer.greeted)
+    }
+
+    fn greet(&self, addr: SocketAddr) {
+        if let Some(peer) = self.sessions().get_mut(&addr) {
+            peer.greeted = true;
         }
     }
 
@@ -140,331 +266,837 @@ impl Server {
         packet: &net::client::Packet,
     ) -> Result<(), SendError> {
         let bytes = postcard::to_stdvec(packet)?;
-        self.socket.send_to(&bytes, addr).await?;
+        let sealed = {
+            let mut sessions = self.sessions();
+            let peer = sessions.get_mut(addr).ok_or(SendError::NoSession)?;
+            peer.endpoint
+                .send(packet.channel(), bytes)?
+                .iter()
+                .map(|message| peer.session.seal(message))
+                .collect::<Result<Vec<Vec<u8>>, SecureError>>()?
+        };
+
+        for datagram in sealed {
+            self.socket.send_to(&datagram, addr).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Drops sessions of disconnected peers once they've acked everything or run out of time
+    fn reap_closed(&self) {
+        let now = Instant::now();
+        self.sessions().retain(|_, peer| {
+            peer.closing
+                .is_none_or(|deadline| now < deadline && !peer.endpoint.all_acked())
+        });
+    }
+
+    /// Resends reliable packets that peers haven't acknowledged yet
+    pub async fn retransmit(&self) -> Result<(), SendError> {
+        let sealed = self
+            .sessions()
+            .iter_mut()
+            .flat_map(|(addr, peer)| {
+                peer.endpoint
+                    .retransmit()
+                    .iter()
+                    .map(|message| peer.session.seal(message).map(|sealed| (*addr, sealed)))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Result<Vec<(SocketAddr, Vec<u8>)>, SecureError>>()?;
+
+        for (addr, datagram) in sealed {
+            self.socket.send_to(&datagram, addr).await?;
+        }
+
         Ok(())
     }
+
+    /// Completes handshakes and opens sealed datagrams, returning the packets that are ready.
+    /// Anything from an address without an established session is dropped.
+    pub async fn receive(
+        &self,
+        bytes: &[u8],
+        addr: SocketAddr,
+    ) -> Result<Vec<net::server::Packet>, ReceiveError> {
+        match postcard::from_bytes(bytes)? {
+            Frame::ClientHello { public_key } => {
+                // Starting over would reset a logged in player's keys, so an address keeps its
+                // session until it disconnects or times out. Retries of the same hello are
+                // answered again in case our reply was lost.
+                // A disconnected player reconnecting replaces the session that's closing
+                let existing = self
+                    .sessions()
+                    .get(&addr)
+                    .filter(|peer| peer.closing.is_none())
+                    .map(|peer| {
+                        (peer.client_public_key == public_key).then(|| peer.server_hello.clone())
+                    });
+                match existing {
+                    Some(Some(reply)) => {
+                        self.socket.send_to(&reply, addr).await?;
+                        return Ok(Vec::new());
+                    }
+                    Some(None) => {
+                        warn!(
+                            "Ignoring handshake from {} which already has a session",
+                            addr
+                        );
+                        return Ok(Vec::new());
+                    }
+                    None => {}
+                }
+
+                let (session, reply) = Session::accept(&self.key, public_key)?;
+                self.sessions().insert(
+                    addr,
+                    Peer {
+                        session,
+                        endpoint: Endpoint::new(),
+                        last_seen: Instant::now(),
+                        greeted: false,
+                        client_public_key: public_key,
+                        server_hello: reply.clone(),
+                        closing: None,
+                    },
+                );
+                self.socket.send_to(&reply, addr).await?;
+                info!("Completed handshake with {}", addr);
+                Ok(Vec::new())
+            }
+            Frame::Sealed {
+                counter,
+                ciphertext,
+            } => {
+                let (payloads, reply) = {
+                    let mut sessions = self.sessions();
+                    let Some(peer) = sessions.get_mut(&addr) else {
+                        warn!("Dropping datagram from {} which has no session", addr);
+                        return Ok(Vec::new());
+                    };
+                    let message = peer.session.open(counter, &ciphertext)?;
+                    peer.last_seen = Instant::now();
+
+                    let mut received = peer.endpoint.receive(&message)?;
+                    let reply = received
+                        .reply
+                        .as_ref()
+                        .map(|reply| peer.session.seal(reply))
+                        .transpose()?;
+                    // Closing sessions only stay around for acks, the player is already gone
+                    if peer.closing.is_some() {
+                        received.payloads.clear();
+                    }
+                    (received.payloads, reply)
+                };
+
+                if let Some(reply) = reply {
+                    self.socket.send_to(&reply, addr).await?;
+                }
+
+                Ok(payloads
+                    .iter()
+                    .map(|payload| postcard::from_bytes(payload))
+                    .collect::<Result<Vec<net::server::Packet>, postcard::Error>>()?)
+            }
+            Frame::ServerHello { .. } => Err(SecureError::UnexpectedFrame.into()),
+        }
+    }
 }
 
 #[async_std::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
+    signal::install()?;
 
+    let key = identity::load()?;
     let socket = UdpSocket::bind("0.0.0.0:8000").await?;
 
     let pool = SqlitePool::connect(&std::env::var("DATABASE_URL")?).await?;
     sqlx::migrate!().run(&mut pool.acquire().await?).await?;
+    auth::upgrade_passwords(&pool).await?;
+
+    let registry = item::load_registry(item::REGISTRY_PATH)?;
+    recipe::load_recipe_book(recipe::RECIPE_BOOK_PATH)?;
+    info!(
+        "Loaded {} items, data version {:016x}",
+        registry.items().count(),
+        common::data_version()
+    );
 
-    let mut server = Server::new(socket, pool);
+    let world = World::load(&pool).await?;
+    let mut server = Server::new(socket, key, pool, world, interest::radius_from_env());
     info!("Listening on 0.0.0.0:8000");
 
-    let mut last_heartbeat_check = Instant::now();
+    let (requests, admin) = channel::unbounded();
+    admin::spawn_console(requests.clone());
+    if let Some((addr, token)) = admin::remote_from_env()? {
+        admin::listen(addr, token, requests).await?;
+    }
 
-    loop {
-        let mut buf = [0; 4096];
-        match server.socket.recv_from(&mut buf).await {
-            Err(e) => panic!("{e}"),
-            Ok((_, addr)) => {
-                let packet = match postcard::from_bytes(&buf) {
-                    Ok(packet) => packet,
+    let mut next_tick = Instant::now() + TICK;
+
+    while !server.stopping && !signal::received() {
+        let mut buf = [0; net::MAX_DATAGRAM_SIZE];
+        // Time out at the next tick so the simulation keeps running when nothing arrives
+        let timeout = next_tick.saturating_duration_since(Instant::now());
+        match async_std::io::timeout(timeout, server.socket.recv_from(&mut buf)).await {
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+            // Some platforms report oversized datagrams as errors, they shouldn't take us down
+            Err(e) => warn!("Failed to receive datagram due to {}", e),
+            Ok((length, addr)) => {
+                let packets = match server.receive(&buf[..length], addr).await {
+                    Ok(packets) => packets,
                     Err(e) => {
-                        warn!("Failed to decode packet due to {}", e);
+                        warn!("Failed to receive packet from {} due to {}", addr, e);
                         continue;
                     }
                 };
 
-                if let Err(e) = handle_packet(&mut server, &packet, addr).await {
-                    warn!("Handling packet failed with {e}");
-                    continue;
+                for packet in packets {
+                    if let Err(e) = handle_packet(&mut server, &packet, addr).await {
+                        warn!("Handling packet failed with {e}");
+                    }
                 }
             }
         }
 
-        if last_heartbeat_check.elapsed().as_secs_f32() > 1.0 {
-            info!("Checking heartbeats");
-            check_heartbeats(&mut server).await?;
-            last_heartbeat_check = Instant::now();
+        handle_admin_requests(&mut server, &admin).await;
+
+        if Instant::now() < next_tick {
+            continue;
+        }
+
+        tick(&mut server).await;
+
+        next_tick += TICK;
+        // Skip ticks rather than running several back to back if we've fallen behind
+        if next_tick < Instant::now() {
+            warn!("Tick {} overran, skipping ahead", server.tick);
+            next_tick = Instant::now() + TICK;
         }
     }
+
+    shutdown(&mut server).await;
+    Ok(())
 }
 
-async fn check_heartbeats(server: &mut Server) -> Result<()> {
-    let dead = server
-        .online
-        .values()
-        .filter(|connection| connection.last_heartbeat.elapsed().as_secs_f32() > 20.0)
-        .map(|connection| connection.addr)
-        .collect::<Vec<SocketAddr>>();
+/// Runs once no more packets will be handled. Inventory changes are written as they happen, so
+/// positions are the only thing left to flush.
+async fn shutdown(server: &mut Server) {
+    info!(
+        "Shutting down with {} players online",
+        server.online.values().count()
+    );
+    save_positions(server).await;
 
-    for addr in dead {
-        if let Err(e) = disconnect(server, addr, Some("Heartbeat timeout".to_owned())).await {
-            warn!("Failed to disconnect {} due to {}", addr, e);
-            continue;
+    let packet = net::client::Packet::NotifyDisconnection(net::client::NotifyDisconnection {
+        reason: "Server is shutting down".to_owned(),
+    });
+    for connection in server.online.values() {
+        if let Err(e) = server.send(connection, &packet).await {
+            warn!(
+                "Failed to notify {} of shutdown due to {}",
+                connection.addr, e
+            );
         }
     }
 
-    Ok(())
+    flush(server, Instant::now() + CLOSE_GRACE).await;
 }
 
-async fn handle_login(server: &mut Server, packet: &net::server::Login, addr: SocketAddr) {
-    let Ok(user) = sqlx::query!(
-        "SELECT id, password FROM users WHERE username = ?",
-        packet.username
-    )
-    .fetch_optional(&server.pool)
-    .await
-    else {
-        error!("Fetching user {} failed", packet.username);
-        return;
-    };
+/// Keeps reading acks and retransmitting until every peer has acked what it was sent or the
+/// deadline passes, packets that arrive meanwhile are ignored
+async fn flush(server: &Server, deadline: Instant) {
+    loop {
+        let unacked = server
+            .sessions()
+            .values()
+            .any(|peer| !peer.endpoint.all_acked());
+        if !unacked {
+            return;
+        }
 
-    let Some(user) = user else {
-        let _ = send_error(server, addr, "No account found for that username", true).await;
-        return;
-    };
+        let now = Instant::now();
+        if now >= deadline {
+            warn!("Gave up waiting for acks from disconnected players");
+            return;
+        }
 
-    if user.password != packet.password {
-        let _ = send_error(server, addr, "Username or password is incorrect", true).await;
-        return;
+        let mut buf = [0; net::MAX_DATAGRAM_SIZE];
+        let timeout = (deadline - now).min(TICK);
+        match async_std::io::timeout(timeout, server.socket.recv_from(&mut buf)).await {
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(e) => warn!("Failed to receive datagram due to {}", e),
+            Ok((length, addr)) => {
+                if let Err(e) = server.receive(&buf[..length], addr).await {
+                    warn!("Failed to receive packet from {} due to {}", addr, e);
+                }
+            }
+        }
+
+        if let Err(e) = server.retransmit().await {
+            warn!("Retransmitting packets failed with {e}");
+        }
     }
+}
 
-    let Ok(character) = sqlx::query!(
-        "SELECT id, name, position_x, position_y, position_z FROM characters WHERE owner = ?",
-        user.id
-    )
-    .fetch_one(&server.pool)
-    .await
-    else {
-        error!("Fetching character for user {} failed", packet.username);
-        return;
-    };
-    let position = Vec3::new(
-        character.position_x as f32,
-        character.position_y as f32,
-        character.position_z as f32,
-    );
+/// Errors are logged rather than returned, one failed tick shouldn't take the server down
+async fn tick(server: &mut Server) {
+    server.tick = server.tick.wrapping_add(1);
 
-    server.online.insert(Connection {
-        last_heartbeat: Instant::now(),
-        addr,
-        user_id: user.id,
-        character_id: character.id,
-    });
+    if let Err(e) = server.retransmit().await {
+        warn!("Retransmitting packets failed with {e}");
+    }
+    server.reap_closed();
 
-    let connection = server
-        .online
-        .get(&addr)
-        .expect("Failed to get connection that was just inserted, this is very bad");
-
-    for peer in server.online.values() {
-        // Notify peers about new client
-        let packet = net::client::Packet::SpawnPlayer(net::client::SpawnPlayer {
-            username: packet.username.clone(),
-            position,
-        });
+    apply_inputs(server).await;
+    send_snapshots(server).await;
 
-        if let Err(e) = server.send(peer, &packet).await {
-            warn!("Failed to notify {} of new player due to {}", peer.addr, e);
+    if server.tick.is_multiple_of(SLOW_TICK_INTERVAL) {
+        for connection in server.online.values_mut() {
+            connection.inputs.forgive();
         }
+        check_heartbeats(server).await;
+        if let Err(e) = respawn_nodes(server).await {
+            error!("Respawning nodes failed due to {}", e);
+        }
+    }
 
-        let Ok(peer_user) = sqlx::query!("SELECT username FROM users WHERE id = ?", peer.user_id)
-            .fetch_one(&server.pool)
-            .await
-        else {
-            error!("Fetching peer user {} failed", peer.user_id);
-            continue;
-        };
+    if server.tick.is_multiple_of(SAVE_INTERVAL) {
+        save_positions(server).await;
+    }
+}
 
-        let Ok(peer_character) = sqlx::query!(
-            "SELECT position_x, position_y, position_z FROM characters WHERE id = ?",
-            peer.character_id
-        )
-        .fetch_one(&server.pool)
-        .await
-        else {
-            error!("Fetching peer character {} failed", peer.character_id);
+/// Simulates the inputs each client sent since the last tick, this is the only way players move
+async fn apply_inputs(server: &mut Server) {
+    let addrs = server
+        .online
+        .values()
+        .map(|connection| connection.addr)
+        .collect::<Vec<SocketAddr>>();
+
+    for addr in addrs {
+        let Some(connection) = server.online.get_mut(&addr) else {
             continue;
         };
+        let state = connection.inputs.apply(connection.state);
+        let moved = state.position != connection.state.position;
+        connection.state = state;
+        if !moved {
+            continue;
+        }
 
-        let peer_position = Vec3::new(
-            peer_character.position_x as f32,
-            peer_character.position_y as f32,
-            peer_character.position_z as f32,
-        );
+        connection.unsaved = true;
+        server.players.insert(addr, state.position);
 
-        // Notify new client about peers
-        let packet = net::client::Packet::SpawnPlayer(net::client::SpawnPlayer {
-            username: peer_user.username.clone(),
-            position: peer_position,
-        });
+        update_interest(server, addr).await;
+        sync_nodes(server, addr).await;
+    }
+}
 
+async fn send_snapshots(server: &Server) {
+    // Sent even with nobody in view, the client needs its own state to reconcile against
+    for connection in server.online.values() {
+        let players = connection
+            .visible
+            .iter()
+            .filter_map(|addr| server.online.get(addr))
+            .map(|peer| net::client::PlayerState {
+                username: peer.username.clone(),
+                position: peer.state.position.into(),
+            })
+            .collect();
+
+        let packet = net::client::Packet::Snapshot(net::client::Snapshot {
+            tick: server.tick,
+            sequence: connection.inputs.last_sequence(),
+            state: connection.state,
+            players,
+        });
         if let Err(e) = server.send(connection, &packet).await {
             warn!(
-                "Failed to notify new player {} of player {} due to {}",
-                addr, peer.addr, e
+                "Failed to send snapshot to {} due to {}",
+                connection.addr, e
             );
         }
     }
+}
 
-    let Ok(items) = sqlx::query!(
-        "SELECT item, quantity FROM items WHERE owner = ?",
-        character.id
-    )
-    .fetch_all(&server.pool)
-    .await
-    else {
-        error!(
-            "Fetching items for character {} user {} failed",
-            character.name, packet.username
-        );
+/// Writes every changed position in one transaction
+async fn save_positions(server: &mut Server) {
+    let (addrs, positions): (Vec<SocketAddr>, Vec<(i64, Vec3)>) = server
+        .online
+        .values()
+        .filter(|connection| connection.unsaved)
+        .map(|connection| {
+            (
+                connection.addr,
+                (connection.character_id, connection.state.position),
+            )
+        })
+        .unzip();
+    if positions.is_empty() {
         return;
-    };
+    }
 
-    // Set clients inventory
-    for stack in items {
-        let Some(item) = Item::from_i64(stack.item) else {
-            error!("Invalid item ID in database {}", stack.item);
-            continue;
-        };
+    if let Err(e) = character::save_positions(&server.pool, &positions).await {
+        error!("Saving {} positions failed due to {}", positions.len(), e);
+        return;
+    }
 
-        let inventory_packet = net::client::Packet::ModifyInventory(net::client::ModifyInventory {
-            stack: ItemStack {
-                item,
-                amount: stack.quantity as u32,
-            },
-        });
+    for addr in addrs {
+        if let Some(connection) = server.online.get_mut(&addr) {
+            connection.unsaved = false;
+        }
+    }
+}
 
-        if let Err(e) = server.send(connection, &inventory_packet).await {
-            warn!(
-                "Failed to update player {}'s inventory stack {:?} due to {}",
-                packet.username, stack, e
-            );
+async fn check_heartbeats(server: &mut Server) {
+    let dead = server
+        .online
+        .values()
+        .filter(|connection| connection.last_heartbeat.elapsed().as_secs_f32() > 20.0)
+        .map(|connection| connection.addr)
+        .collect::<Vec<SocketAddr>>();
+
+    for addr in dead {
+        if let Err(e) = disconnect(server, addr, Some("Heartbeat timeout".to_owned())).await {
+            warn!("Failed to disconnect {} due to {}", addr, e);
             continue;
         }
+    }
 
-        info!("Updating player {}'s stack {:?}", packet.username, stack);
+    // Handshakes that never went on to log in
+    let online = &server.online;
+    server.sessions().retain(|addr, peer| {
+        online.get(addr).is_some() || peer.last_seen.elapsed().as_secs_f32() < 20.0
+    });
+}
+
+async fn respawn_nodes(server: &mut Server) -> Result<()> {
+    for id in server.world.respawn(&server.pool).await? {
+        info!("Respawning node {}", id);
+        broadcast_node(server, id).await;
     }
 
-    info!("Added {} to connection list", packet.username);
+    Ok(())
 }
 
-async fn handle_move(server: &mut Server, packet: &net::server::Move, addr: SocketAddr) {
-    let Some(connection) = server.online.get_mut(&addr) else {
-        warn!("Cannot find client for addr {}", addr);
+/// Tells players near the node about its new state, everyone else catches up in `sync_nodes` once
+/// they get close
+async fn broadcast_node(server: &mut Server, id: u32) {
+    let Some(node) = server.world.get(id) else {
         return;
     };
+    let position = node.position;
 
-    if let Err(e) = sqlx::query!(
-        "UPDATE characters SET position_x = ?, position_y = ?, position_z = ? WHERE id = ?",
-        packet.position.x,
-        packet.position.y,
-        packet.position.z,
-        connection.character_id
-    )
-    .execute(&server.pool)
-    .await
-    {
-        error!(
-            "Updating position for character {} failed due to {}",
-            connection.character_id, e
-        );
-        return;
+    for addr in server.players.nearby(position, server.interest_radius) {
+        sync_node(server, addr, id).await;
     }
+}
 
-    info!(
-        "Updated position for {} to {:?}",
-        connection.character_id, packet.position
-    );
-
-    let Some(connection) = server.online.get(&addr) else {
-        warn!("Cannot find client for addr {}", addr);
+/// Sends the node's state to the client if it's out of date
+async fn sync_node(server: &mut Server, addr: SocketAddr, id: u32) {
+    let Some(depleted) = server.world.get(id).map(world::Node::is_depleted) else {
         return;
     };
-
-    let Ok(user) = sqlx::query!(
-        "SELECT username FROM users WHERE id = ?",
-        connection.user_id
-    )
-    .fetch_one(&server.pool)
-    .await
-    else {
-        error!("Failed to fetch user with id {}", connection.user_id);
+    let Some(connection) = server.online.get_mut(&addr) else {
         return;
     };
 
-    for peer in server.online.values().filter(|peer| peer != &connection) {
-        let packet = net::client::Packet::Move(net::client::Move {
-            username: user.username.clone(),
-            position: packet.position,
-        });
+    let changed = if depleted {
+        connection.depleted_nodes.insert(id)
+    } else {
+        connection.depleted_nodes.remove(&id)
+    };
+    if !changed {
+        return;
+    }
 
-        if let Err(e) = server.send(peer, &packet).await {
-            warn!(
-                "Failed to notify {} of {} moving due to {}",
-                peer.addr, user.username, e
-            );
-            continue;
-        }
+    let packet = net::client::Packet::UpdateNode(net::client::UpdateNode { id, depleted });
+    if let Err(e) = server.send(&addr, &packet).await {
+        warn!("Failed to update node {} for {} due to {}", id, addr, e);
     }
 }
 
-fn handle_heartbeat(server: &mut Server, addr: SocketAddr) {
-    let Some(connection) = server.online.get_mut(&addr) else {
-        warn!("Cannot find client for addr {}", addr);
+async fn sync_nodes(server: &mut Server, addr: SocketAddr) {
+    let Some(connection) = server.online.get(&addr) else {
         return;
     };
 
-    connection.last_heartbeat = Instant::now();
-    info!("{} heartbeat", connection.user_id);
-}
-
-async fn handle_packet(
-    server: &mut Server,
-    packet: &net::server::Packet,
-    addr: SocketAddr,
-) -> Result<()> {
-    match packet {
-        net::server::Packet::Login(packet) => handle_login(server, packet, addr).await,
-        net::server::Packet::Move(packet) => handle_move(server, packet, addr).await,
-        net::server::Packet::Heartbeat => handle_heartbeat(server, addr),
-        net::server::Packet::Disconnect => disconnect(server, addr, None).await?,
-        net::server::Packet::ModifyInventory(packet) => {
-            handle_modify_inventory(server, packet, addr).await
-        }
-        net::server::Packet::Signup(packet) => handle_signup(server, packet, addr).await,
-    };
-
-    Ok(())
+    for id in server
+        .world
+        .nearby(connection.state.position, server.interest_radius)
+    {
+        sync_node(server, addr, id).await;
+    }
 }
 
-async fn disconnect(server: &mut Server, addr: SocketAddr, reason: Option<String>) -> Result<()> {
+/// Spawns players for each other as they come into view and despawns them once they're out of
+/// view, players are only sent moves for peers they can see
+async fn update_interest(server: &mut Server, addr: SocketAddr) {
     let Some(connection) = server.online.get(&addr) else {
-        warn!("Cannot find client for addr {}", addr);
-        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Client not found").into());
+        return;
     };
-    info!("{} is disconnecting", connection.user_id);
-
-    let user = sqlx::query!(
-        "SELECT username FROM users WHERE id = ?",
-        connection.user_id
-    )
-    .fetch_one(&server.pool)
-    .await?;
+    let position = connection.state.position;
 
-    for peer in server.online.values().filter(|peer| peer != &connection) {
-        let packet = net::client::Packet::DespawnPlayer(net::client::DespawnPlayer {
-            username: user.username.clone(),
-        });
+    let entered = server
+        .players
+        .nearby(position, server.interest_radius)
+        .into_iter()
+        .filter(|other| *other != addr && !connection.visible.contains(other))
+        .collect::<Vec<SocketAddr>>();
+    let left = connection
+        .visible
+        .iter()
+        .filter(|other| {
+            server.players.position(other).is_none_or(|other| {
+                interest::distance(position, other) > server.interest_radius + interest::MARGIN
+            })
+        })
+        .copied()
+        .collect::<Vec<SocketAddr>>();
 
-        server.send(peer, &packet).await?;
+    for other in entered {
+        spawn_player_for(server, addr, other).await;
+        spawn_player_for(server, other, addr).await;
     }
 
-    if let Some(reason) = reason {
-        let packet =
-            net::client::Packet::NotifyDisconnection(net::client::NotifyDisconnection { reason });
-        server.send(&connection, &packet).await?;
+    for other in left {
+        despawn_player_for(server, addr, other).await;
+        despawn_player_for(server, other, addr).await;
     }
+}
 
-    server.online.remove(&connection.get_unique_key());
+/// Tells `viewer` about `player`
+async fn spawn_player_for(server: &mut Server, viewer: SocketAddr, player: SocketAddr) {
+    let Some(player) = server.online.get(&player).cloned() else {
+        return;
+    };
+    let Some(connection) = server.online.get_mut(&viewer) else {
+        return;
+    };
+    connection.visible.insert(player.addr);
+
+    let packet = net::client::Packet::SpawnPlayer(net::client::SpawnPlayer {
+        username: player.username.clone(),
+        position: player.state.position,
+    });
+    if let Err(e) = server.send(&viewer, &packet).await {
+        warn!(
+            "Failed to notify {} of player {} due to {}",
+            viewer, player.username, e
+        );
+    }
+}
+
+async fn despawn_player_for(server: &mut Server, viewer: SocketAddr, player: SocketAddr) {
+    let Some(username) = server
+        .online
+        .get(&player)
+        .map(|connection| connection.username.clone())
+    else {
+        return;
+    };
+    let Some(connection) = server.online.get_mut(&viewer) else {
+        return;
+    };
+    if !connection.visible.remove(&player) {
+        return;
+    }
+
+    let packet = net::client::Packet::DespawnPlayer(net::client::DespawnPlayer { username });
+    if let Err(e) = server.send(&viewer, &packet).await {
+        warn!("Failed to despawn player for {} due to {}", viewer, e);
+    }
+}
+
+/// Checks the client speaks the same protocol and has the same items and recipes as us, nothing
+/// else it sends is handled until this passes
+async fn handle_hello(server: &Server, packet: &net::server::Hello, addr: SocketAddr) {
+    let reason = if packet.protocol_version != net::PROTOCOL_VERSION {
+        Some(format!(
+            "Your game speaks protocol version {} but the server speaks {}, please update",
+            packet.protocol_version,
+            net::PROTOCOL_VERSION
+        ))
+    } else if packet.data_version != common::data_version() {
+        Some("Your game data doesn't match the server, please update".to_owned())
+    } else {
+        None
+    };
+
+    let Some(reason) = reason else {
+        info!("Accepted hello from {} running {}", addr, packet.build_id);
+        server.greet(addr);
+        return;
+    };
+
+    warn!(
+        "Rejecting {} running {} with protocol version {} and data version {:016x}",
+        addr, packet.build_id, packet.protocol_version, packet.data_version
+    );
+    let packet = net::client::Packet::Rejected(net::client::Rejected { reason });
+    if let Err(e) = server.send(&addr, &packet).await {
+        warn!("Failed to reject {} due to {}", addr, e);
+    }
+}
+
+async fn handle_login(server: &mut Server, packet: &net::server::Login, addr: SocketAddr) {
+    let Ok(user) = sqlx::query!(
+        "SELECT id, password_hash FROM users WHERE username = ?",
+        packet.username
+    )
+    .fetch_optional(&server.pool)
+    .await
+    else {
+        error!("Fetching user {} failed", packet.username);
+        return;
+    };
+
+    let Some(user) = user else {
+        let _ = send_error(server, addr, "No account found for that username", true).await;
+        return;
+    };
+
+    match auth::verify_password(packet.password.clone(), user.password_hash).await {
+        Ok(true) => {}
+        Ok(false) => {
+            let _ = send_error(server, addr, "Username or password is incorrect", true).await;
+            return;
+        }
+        Err(e) => {
+            error!(
+                "Verifying password for {} failed due to {}",
+                packet.username, e
+            );
+            send_error(server, addr, "Server error", true).await;
+            return;
+        }
+    }
+
+    let token = match auth::create_session(&server.pool, user.id).await {
+        Ok(token) => token,
+        Err(e) => {
+            error!(
+                "Creating session for {} failed due to {}",
+                packet.username, e
+            );
+            send_error(server, addr, "Server error", true).await;
+            return;
+        }
+    };
+
+    let packet = net::client::Packet::SessionToken(net::client::SessionToken { token });
+    if let Err(e) = server.send(&addr, &packet).await {
+        warn!("Failed to send session token to {} due to {}", addr, e);
+    }
+
+    join(server, user.id, addr).await;
+}
+
+async fn handle_resume(server: &mut Server, packet: &net::server::Resume, addr: SocketAddr) {
+    let user_id = match auth::resume_session(&server.pool, &packet.token).await {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => {
+            send_error(server, addr, "Session expired, please log in again", true).await;
+            return;
+        }
+        Err(e) => {
+            error!("Resuming session for {} failed due to {}", addr, e);
+            send_error(server, addr, "Server error", true).await;
+            return;
+        }
+    };
+
+    join(server, user_id, addr).await;
+}
+
+async fn join(server: &mut Server, user_id: i64, addr: SocketAddr) {
+    let Ok(user) = sqlx::query!(
+        "SELECT id, username, permission FROM users WHERE id = ?",
+        user_id
+    )
+    .fetch_one(&server.pool)
+    .await
+    else {
+        error!("Fetching user {} failed", user_id);
+        return;
+    };
+
+    let Ok(character) = sqlx::query!(
+        "SELECT id, name, position_x, position_y, position_z FROM characters WHERE owner = ?",
+        user.id
+    )
+    .fetch_one(&server.pool)
+    .await
+    else {
+        error!("Fetching character for user {} failed", user.username);
+        return;
+    };
+    let position = Vec3::new(
+        character.position_x as f32,
+        character.position_y as f32,
+        character.position_z as f32,
+    );
+
+    server.online.insert(Connection {
+        last_heartbeat: Instant::now(),
+        busy_until: Instant::now(),
+        addr,
+        user_id: user.id,
+        character_id: character.id,
+        username: user.username.clone(),
+        permission: Permission::from_level(user.permission),
+        state: MovementState::new(position),
+        inputs: InputQueue::new(),
+        unsaved: false,
+        visible: HashSet::new(),
+        depleted_nodes: HashSet::new(),
+    });
+    server.players.insert(addr, position);
+
+    let items = match inventory::load(&server.pool, character.id).await {
+        Ok(items) => items,
+        Err(e) => {
+            error!(
+                "Fetching items for character {} user {} failed due to {}",
+                character.name, user.username, e
+            );
+            return;
+        }
+    };
+
+    // Set clients inventory
+    for stack in items {
+        let inventory_packet = net::client::Packet::ModifyInventory(net::client::ModifyInventory {
+            stack: stack.clone(),
+        });
+
+        if let Err(e) = server.send(&addr, &inventory_packet).await {
+            warn!(
+                "Failed to update player {}'s inventory stack {:?} due to {}",
+                user.username, stack, e
+            );
+            continue;
+        }
+
+        info!("Updating player {}'s stack {:?}", user.username, stack);
+    }
+
+    let seed_packet = net::client::Packet::WorldSeed(net::client::WorldSeed {
+        seed: server.world.seed(),
+    });
+    if let Err(e) = server.send(&addr, &seed_packet).await {
+        warn!("Failed to send world seed to {} due to {}", addr, e);
+    }
+
+    update_interest(server, addr).await;
+    sync_nodes(server, addr).await;
+
+    info!("Added {} to connection list", user.username);
+}
+
+fn handle_player_input(server: &mut Server, packet: &net::server::PlayerInput, addr: SocketAddr) {
+    let Some(connection) = server.online.get_mut(&addr) else {
+        warn!("Cannot find client for addr {}", addr);
+        return;
+    };
+
+    if !connection.inputs.push(packet.sequence, packet.input) {
+        let violations = connection.inputs.violations;
+        if violations == movement::REPORT_THRESHOLD {
+            warn!(
+                "{} keeps sending inputs faster than it should, {} recent violations",
+                connection.username, violations
+            );
+        }
+    }
+}
+
+fn handle_heartbeat(server: &mut Server, addr: SocketAddr) {
+    let Some(connection) = server.online.get_mut(&addr) else {
+        warn!("Cannot find client for addr {}", addr);
+        return;
+    };
+
+    connection.last_heartbeat = Instant::now();
+    info!("{} heartbeat", connection.user_id);
+}
+
+async fn handle_packet(
+    server: &mut Server,
+    packet: &net::server::Packet,
+    addr: SocketAddr,
+) -> Result<()> {
+    if !matches!(packet, net::server::Packet::Hello(_)) && !server.greeted(&addr) {
+        warn!("Dropping packet from {} which hasn't said hello", addr);
+        return Ok(());
+    }
+
+    match packet {
+        net::server::Packet::Hello(packet) => handle_hello(server, packet, addr).await,
+        net::server::Packet::Login(packet) => handle_login(server, packet, addr).await,
+        net::server::Packet::PlayerInput(packet) => handle_player_input(server, packet, addr),
+        net::server::Packet::Heartbeat => handle_heartbeat(server, addr),
+        net::server::Packet::Disconnect => disconnect(server, addr, None).await?,
+        net::server::Packet::Signup(packet) => handle_signup(server, packet, addr).await,
+        net::server::Packet::Resume(packet) => handle_resume(server, packet, addr).await,
+        net::server::Packet::Craft(packet) => handle_craft(server, packet, addr).await,
+        net::server::Packet::Gather(packet) => handle_gather(server, packet, addr).await,
+        net::server::Packet::OpenFurnace => handle_open_furnace(server, addr).await,
+        net::server::Packet::LoadFurnace(packet) => {
+            handle_load_furnace(server, packet, addr).await;
+        }
+        net::server::Packet::TakeFurnaceOutput => handle_take_furnace_output(server, addr).await,
+        net::server::Packet::ChatMessage(packet) => handle_chat(server, packet, addr).await,
+    };
+
+    Ok(())
+}
+
+async fn disconnect(server: &mut Server, addr: SocketAddr, reason: Option<String>) -> Result<()> {
+    let Some(connection) = server.online.get(&addr) else {
+        warn!("Cannot find client for addr {}", addr);
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Client not found").into());
+    };
+    info!("{} is disconnecting", connection.user_id);
+
+    for peer in connection.visible.clone() {
+        despawn_player_for(server, peer, addr).await;
+    }
+
+    let Some(connection) = server.online.get(&addr) else {
+        return Ok(());
+    };
+
+    let position = connection.state.position;
+    if let Err(e) =
+        character::save_positions(&server.pool, &[(connection.character_id, position)]).await
+    {
+        error!(
+            "Saving position for character {} failed due to {}",
+            connection.character_id, e
+        );
+    }
+
+    if let Some(reason) = reason {
+        let packet =
+            net::client::Packet::NotifyDisconnection(net::client::NotifyDisconnection { reason });
+        // They're removed either way, otherwise a failed send would leave them online forever
+        if let Err(e) = server.send(connection, &packet).await {
+            warn!("Failed to notify {} of disconnection due to {}", addr, e);
+        }
+    }
+
+    server.online.remove(&addr);
+    server.players.remove(&addr);
+    // Kept until the notice is acked so it's retransmitted if lost, see `Server::reap_closed`
+    if let Some(peer) = server.sessions().get_mut(&addr) {
+        peer.closing = Some(Instant::now() + CLOSE_GRACE);
+    }
 
     Ok(())
 }
@@ -477,53 +1109,531 @@ async fn send_error(server: &Server, addr: SocketAddr, message: &str, fatal: boo
     let _ = server.send(&addr, &packet).await;
 }
 
-async fn handle_modify_inventory(
+async fn send_stacks(server: &Server, addr: SocketAddr, stacks: Vec<ItemStack>) {
+    for stack in stacks {
+        let packet = net::client::Packet::ModifyInventory(net::client::ModifyInventory {
+            stack: stack.clone(),
+        });
+        if let Err(e) = server.send(&addr, &packet).await {
+            warn!(
+                "Failed to update stack {:?} for {} due to {}",
+                stack, addr, e
+            );
+        }
+    }
+}
+
+/// Whether a player at `position` may craft `recipe`
+fn check_craft(world: &World, recipe: &Recipe, position: Vec3) -> Result<(), &'static str> {
+    // Smelting takes fuel and time, it only happens by loading the furnace
+    if recipe.station == Station::Furnace {
+        return Err("That recipe has to be smelted in the furnace");
+    }
+
+    if !world.can_use(recipe.station, position) {
+        return Err("Too far away from the station for that recipe");
+    }
+
+    Ok(())
+}
+
+async fn handle_craft(server: &mut Server, packet: &net::server::Craft, addr: SocketAddr) {
+    let Some(connection) = server.online.get(&addr) else {
+        warn!("Cannot find client for addr {}", addr);
+        return;
+    };
+
+    let Some(recipe) = recipe::get_recipe_book().get(&packet.recipe_id) else {
+        warn!(
+            "{} tried to craft unknown recipe {}",
+            addr, packet.recipe_id
+        );
+        return;
+    };
+
+    if let Err(message) = check_craft(&server.world, recipe, connection.state.position) {
+        warn!("{} tried to craft {}: {}", addr, recipe.id, message);
+        send_error(server, addr, message, false).await;
+        return;
+    }
+
+    if connection.busy_until > Instant::now() {
+        send_error(server, addr, "Already crafting something", false).await;
+        return;
+    }
+
+    let changes = recipe
+        .ingredients
+        .iter()
+        .map(|stack| (stack.item.clone(), -i64::from(stack.amount)))
+        .chain(
+            recipe
+                .outputs
+                .iter()
+                .map(|stack| (stack.item.clone(), i64::from(stack.amount))),
+        )
+        .collect::<Vec<(Item, i64)>>();
+
+    match inventory::apply(&server.pool, connection.character_id, &changes).await {
+        Ok(Some(stacks)) => {
+            let craft_time = Duration::from_secs_f32(recipe.craft_time);
+            if let Some(connection) = server.online.get_mut(&addr) {
+                connection.busy_until = Instant::now() + craft_time;
+            }
+            send_stacks(server, addr, stacks).await;
+        }
+        Ok(None) => send_error(server, addr, "Missing ingredients for that recipe", false).await,
+        Err(e) => {
+            error!(
+                "Crafting recipe {} for character {} failed due to {}",
+                recipe.id, connection.character_id, e
+            );
+            send_error(server, addr, "Server error", false).await;
+        }
+    }
+}
+
+async fn handle_gather(server: &mut Server, packet: &net::server::Gather, addr: SocketAddr) {
+    let Some(connection) = server.online.get(&addr) else {
+        warn!("Cannot find client for addr {}", addr);
+        return;
+    };
+
+    let Some(node) = server.world.get(packet.node_id) else {
+        warn!("{} tried to gather unknown node {}", addr, packet.node_id);
+        return;
+    };
+
+    // Someone else probably got there first and the update hasn't reached this client yet
+    if node.is_depleted() {
+        return;
+    }
+
+    let distance = (node.position - connection.state.position).length();
+    if distance > node.kind.reach() {
+        warn!(
+            "{} tried to gather node {} from {} away",
+            addr, packet.node_id, distance
+        );
+        return;
+    }
+
+    let yields = node.kind.yields();
+    let changes = [(yields.item, i64::from(yields.amount))];
+    let stacks = match inventory::apply(&server.pool, connection.character_id, &changes).await {
+        Ok(Some(stacks)) => stacks,
+        Ok(None) => return,
+        Err(e) => {
+            error!(
+                "Gathering node {} for character {} failed due to {}",
+                packet.node_id, connection.character_id, e
+            );
+            send_error(server, addr, "Server error", false).await;
+            return;
+        }
+    };
+
+    if let Err(e) = server.world.deplete(&server.pool, packet.node_id).await {
+        error!("Depleting node {} failed due to {}", packet.node_id, e);
+    }
+
+    send_stacks(server, addr, stacks).await;
+    broadcast_node(server, packet.node_id).await;
+}
+
+/// Sends a message from the server to one player, used for command output
+async fn reply(server: &Server, addr: SocketAddr, message: impl Into<String>) {
+    let packet = net::client::Packet::ChatMessage(net::client::ChatMessage {
+        sender: None,
+        message: message.into(),
+    });
+    if let Err(e) = server.send(&addr, &packet).await {
+        warn!("Failed to send chat message to {} due to {}", addr, e);
+    }
+}
+
+fn find_player(server: &Server, username: &str) -> Option<SocketAddr> {
+    server
+        .online
+        .values()
+        .find(|connection| connection.username.eq_ignore_ascii_case(username))
+        .map(|connection| connection.addr)
+}
+
+async fn handle_chat(server: &mut Server, packet: &net::server::ChatMessage, addr: SocketAddr) {
+    let Some(connection) = server.online.get(&addr) else {
+        warn!("Cannot find client for addr {}", addr);
+        return;
+    };
+
+    let message = packet.message.trim();
+    if message.is_empty() {
+        return;
+    }
+    if message.chars().count() > net::MAX_CHAT_LENGTH || message.chars().any(char::is_control) {
+        warn!("{} sent an invalid chat message", connection.username);
+        return;
+    }
+
+    if message.starts_with('/') {
+        run_command(server, addr, message).await;
+        return;
+    }
+
+    info!("{}: {}", connection.username, message);
+    let sender = Some(connection.username.clone());
+    broadcast_chat(server, sender, message.to_owned()).await;
+}
+
+async fn broadcast_chat(server: &Server, sender: Option<String>, message: String) {
+    let packet = net::client::Packet::ChatMessage(net::client::ChatMessage { sender, message });
+    for connection in server.online.values() {
+        if let Err(e) = server.send(connection, &packet).await {
+            warn!(
+                "Failed to send chat message to {} due to {}",
+                connection.addr, e
+            );
+        }
+    }
+}
+
+async fn run_command(server: &mut Server, addr: SocketAddr, line: &str) {
+    let command = match line.parse::<Command>() {
+        Ok(command) => command,
+        Err(e) => {
+            reply(server, addr, e.to_string()).await;
+            return;
+        }
+    };
+
+    let Some(connection) = server.online.get(&addr) else {
+        return;
+    };
+    if connection.permission < command.permission() {
+        warn!(
+            "{} tried to run {:?} without permission",
+            connection.username, command
+        );
+        reply(server, addr, "You dont have permission to do that").await;
+        return;
+    }
+    info!("{} ran {:?}", connection.username, command);
+    let permission = connection.permission;
+
+    match command {
+        Command::Help => {
+            for usage in command::available(permission) {
+                reply(server, addr, usage).await;
+            }
+        }
+        Command::Who => {
+            let mut usernames = server
+                .online
+                .values()
+                .map(|connection| connection.username.clone())
+                .collect::<Vec<String>>();
+            usernames.sort();
+            let message = format!("{} online {}", usernames.len(), usernames.join(" "));
+            reply(server, addr, message).await;
+        }
+        Command::Teleport {
+            player,
+            destination,
+        } => {
+            let target = player.map_or(Some(addr), |player| find_player(server, &player));
+            let position = match destination {
+                Destination::Position(position) => Some(position),
+                Destination::Player(player) => find_player(server, &player)
+                    .and_then(|other| server.online.get(&other))
+                    .map(|other| other.state.position),
+            };
+            let (Some(target), Some(position)) = (target, position) else {
+                reply(server, addr, "No such player").await;
+                return;
+            };
+
+            teleport(server, target, position).await;
+        }
+        Command::Kick { player, reason } => {
+            if let Err(e) = kick(server, &player, reason, Some(permission)).await {
+                reply(server, addr, e).await;
+            }
+        }
+        Command::Give {
+            player,
+            item,
+            amount,
+        } => {
+            if let Err(e) = give(server, &player, item, amount).await {
+                reply(server, addr, e).await;
+            }
+        }
+    }
+}
+
+/// Whether someone with `permission` may kick a player with `target`
+fn check_kick(permission: Permission, target: Permission) -> Result<(), &'static str> {
+    if target >= permission {
+        return Err("You can only kick players below your permission level");
+    }
+
+    Ok(())
+}
+
+/// `by` is the permission of whoever asked, `None` for the admin console which can kick anyone
+async fn kick(
     server: &mut Server,
-    packet: &net::server::ModifyInventory,
-    addr: SocketAddr,
-) {
+    player: &str,
+    reason: Option<String>,
+    by: Option<Permission>,
+) -> Result<(), &'static str> {
+    let target = find_player(server, player).ok_or("No such player")?;
+    if let Some(by) = by {
+        let permission = server
+            .online
+            .get(&target)
+            .map(|connection| connection.permission)
+            .ok_or("No such player")?;
+        check_kick(by, permission)?;
+    }
+
+    let reason = reason.unwrap_or_else(|| "Kicked by a moderator".to_owned());
+    if let Err(e) = disconnect(server, target, Some(reason)).await {
+        warn!("Failed to kick {} due to {}", player, e);
+    }
+
+    Ok(())
+}
+
+/// Errors are meant for whoever asked for the items
+async fn give(server: &Server, player: &str, item: Item, amount: u32) -> Result<(), &'static str> {
+    let target = find_player(server, player).ok_or("No such player")?;
+    if item.def().is_none() {
+        return Err("No such item");
+    }
+    let character_id = server
+        .online
+        .get(&target)
+        .map(|connection| connection.character_id)
+        .ok_or("No such player")?;
+
+    let changes = [(item, i64::from(amount))];
+    match inventory::apply(&server.pool, character_id, &changes).await {
+        Ok(Some(stacks)) => {
+            send_stacks(server, target, stacks).await;
+            Ok(())
+        }
+        Ok(None) => Err("That would go over the stack limit"),
+        Err(e) => {
+            error!(
+                "Giving items to character {} failed due to {}",
+                character_id, e
+            );
+            Err("Server error")
+        }
+    }
+}
+
+/// Puts a player somewhere new, their client picks it up from the next snapshot
+async fn teleport(server: &mut Server, addr: SocketAddr, position: Vec3) {
     let Some(connection) = server.online.get_mut(&addr) else {
-        warn!("Cannot find client for addr {}", addr);
         return;
     };
 
-    let item = packet.stack.item.to_i64();
+    connection.state = MovementState::new(position);
+    connection.unsaved = true;
+    server.players.insert(addr, position);
 
-    let Ok(existing) = sqlx::query!(
-        "SELECT id FROM items WHERE owner = ? AND item = ?",
-        connection.character_id,
-        item
+    update_interest(server, addr).await;
+    sync_nodes(server, addr).await;
+}
+
+async fn handle_admin_requests(server: &mut Server, admin: &Receiver<admin::Message>) {
+    while let Ok(message) = admin.try_recv() {
+        let lines = handle_admin(server, message.request).await;
+        for line in lines {
+            // The admin may have disconnected, which doesn't matter
+            let _ = message.output.send(line).await;
+        }
+    }
+}
+
+/// Returns the lines to show whoever made the request
+async fn handle_admin(server: &mut Server, request: admin::Request) -> Vec<String> {
+    match request {
+        admin::Request::Help => Vec::new(),
+        admin::Request::List => {
+            let mut lines = server
+                .online
+                .values()
+                .map(|connection| {
+                    let position = connection.state.position;
+                    format!(
+                        "{} {} ({:.1}, {:.1}, {:.1}) {:?}",
+                        connection.username,
+                        connection.addr,
+                        position.x,
+                        position.y,
+                        position.z,
+                        connection.permission
+                    )
+                })
+                .collect::<Vec<String>>();
+            lines.sort();
+            lines.insert(0, format!("{} online", lines.len()));
+            lines
+        }
+        admin::Request::Kick { player, reason } => {
+            match kick(server, &player, reason, None).await {
+                Ok(()) => vec![format!("Kicked {player}")],
+                Err(e) => vec![e.to_owned()],
+            }
+        }
+        admin::Request::Give {
+            player,
+            item,
+            amount,
+        } => match give(server, &player, item.clone(), amount).await {
+            Ok(()) => vec![format!("Gave {player} {}", ItemStack { item, amount })],
+            Err(e) => vec![e.to_owned()],
+        },
+        admin::Request::Say(message) => {
+            info!("Server: {}", message);
+            broadcast_chat(server, None, message).await;
+            Vec::new()
+        }
+        admin::Request::Inventory(player) => inventory_of(server, &player).await,
+        admin::Request::Permission { player, permission } => {
+            set_permission(server, &player, permission).await
+        }
+        admin::Request::Shutdown => {
+            server.stopping = true;
+            vec!["Shutting down".to_owned()]
+        }
+    }
+}
+
+/// Works for offline players too, so it goes by username rather than connection
+async fn inventory_of(server: &Server, player: &str) -> Vec<String> {
+    let character = sqlx::query!(
+        "SELECT characters.id FROM characters JOIN users ON users.id = characters.owner WHERE users.username = ?",
+        player
     )
     .fetch_optional(&server.pool)
-    .await
-    else {
-        error!(
-            "Failed to fetch existing item stack character {} item {}",
-            connection.character_id, packet.stack.item
-        );
+    .await;
+
+    let character_id = match character {
+        Ok(Some(character)) => character.id,
+        Ok(None) => return vec!["No such player".to_owned()],
+        Err(e) => return vec![format!("Finding {player} failed due to {e}")],
+    };
+
+    match inventory::load(&server.pool, character_id).await {
+        Ok(stacks) if stacks.is_empty() => vec![format!("{player} has nothing")],
+        Ok(stacks) => stacks.iter().map(ToString::to_string).collect(),
+        Err(e) => vec![format!("Loading inventory failed due to {e}")],
+    }
+}
+
+async fn set_permission(server: &mut Server, player: &str, permission: Permission) -> Vec<String> {
+    let level = permission.level();
+    let result = sqlx::query!(
+        "UPDATE users SET permission = ? WHERE username = ?",
+        level,
+        player
+    )
+    .execute(&server.pool)
+    .await;
+
+    match result {
+        Ok(result) if result.rows_affected() == 0 => return vec!["No such player".to_owned()],
+        Ok(_) => {}
+        Err(e) => return vec![format!("Updating permission failed due to {e}")],
+    }
+
+    // Online players get it straight away rather than on their next login
+    if let Some(connection) =
+        find_player(server, player).and_then(|addr| server.online.get_mut(&addr))
+    {
+        connection.permission = permission;
+    }
+
+    vec![format!("{player} is now {permission:?}")]
+}
+
+async fn send_furnace(server: &Server, addr: SocketAddr, furnace: Furnace) {
+    let packet = net::client::Packet::FurnaceState(net::client::FurnaceState { furnace });
+    if let Err(e) = server.send(&addr, &packet).await {
+        warn!("Failed to send furnace state to {} due to {}", addr, e);
+    }
+}
+
+async fn handle_open_furnace(server: &Server, addr: SocketAddr) {
+    let Some(connection) = server.online.get(&addr) else {
+        warn!("Cannot find client for addr {}", addr);
         return;
     };
 
-    let result = if let Some(_) = existing {
-        sqlx::query!("UPDATE items SET quantity = ?", packet.stack.amount)
-            .execute(&server.pool)
-            .await
-    } else {
-        sqlx::query!(
-            "INSERT INTO items (item, quantity, owner) VALUES (?, ?, ?)",
-            item,
-            packet.stack.amount,
-            connection.character_id
-        )
-        .execute(&server.pool)
-        .await
+    match furnace::open(&server.pool, connection.character_id).await {
+        Ok(furnace) => send_furnace(server, addr, furnace).await,
+        Err(e) => {
+            error!(
+                "Opening furnace for character {} failed due to {}",
+                connection.character_id, e
+            );
+            send_error(server, addr, "Server error", false).await;
+        }
+    }
+}
+
+async fn handle_load_furnace(server: &Server, packet: &net::server::LoadFurnace, addr: SocketAddr) {
+    let Some(connection) = server.online.get(&addr) else {
+        warn!("Cannot find client for addr {}", addr);
+        return;
     };
 
-    if let Err(e) = result {
-        error!(
-            "Failed to set item stack {:?} for character {} due to {}",
-            packet.stack, connection.character_id, e
-        );
+    match furnace::load_into(
+        &server.pool,
+        connection.character_id,
+        packet.slot,
+        &packet.stack,
+    )
+    .await
+    {
+        Ok(Ok((furnace, stack))) => {
+            send_stacks(server, addr, vec![stack]).await;
+            send_furnace(server, addr, furnace).await;
+        }
+        Ok(Err(message)) => send_error(server, addr, message, false).await,
+        Err(e) => {
+            error!(
+                "Loading furnace for character {} failed due to {}",
+                connection.character_id, e
+            );
+            send_error(server, addr, "Server error", false).await;
+        }
+    }
+}
+
+async fn handle_take_furnace_output(server: &Server, addr: SocketAddr) {
+    let Some(connection) = server.online.get(&addr) else {
+        warn!("Cannot find client for addr {}", addr);
+        return;
+    };
+
+    match furnace::take_output(&server.pool, connection.character_id).await {
+        Ok(Ok((furnace, stack))) => {
+            send_stacks(server, addr, vec![stack]).await;
+            send_furnace(server, addr, furnace).await;
+        }
+        Ok(Err(message)) => send_error(server, addr, message, false).await,
+        Err(e) => {
+            error!(
+                "Taking furnace output for character {} failed due to {}",
+                connection.character_id, e
+            );
+            send_error(server, addr, "Server error", false).await;
+        }
     }
 }
 
@@ -542,10 +1652,19 @@ async fn handle_signup(server: &Server, packet: &net::server::Signup, addr: Sock
         return;
     }
 
+    let hash = match auth::hash_password(packet.password.clone()).await {
+        Ok(hash) => hash,
+        Err(e) => {
+            error!("Failed to hash password for new user due to {}", e);
+            send_error(server, addr, "Server error", true).await;
+            return;
+        }
+    };
+
     if let Err(e) = sqlx::query!(
-        "INSERT INTO users (username, password) VALUES (?, ?)",
+        "INSERT INTO users (username, password_hash) VALUES (?, ?)",
         packet.username,
-        packet.password
+        hash
     )
     .execute(&server.pool)
     .await
@@ -570,3 +1689,77 @@ async fn handle_signup(server: &Server, packet: &net::server::Signup, addr: Sock
         return;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use common::worldgen;
+
+    const SEED: u32 = 7;
+
+    fn station(station: Station) -> Vec3 {
+        worldgen::generate(SEED)
+            .stations
+            .into_iter()
+            .find(|placement| placement.station == station)
+            .map_or(Vec3::NAN, |placement| placement.position)
+    }
+
+    fn recipe(id: &str) -> Result<&'static Recipe> {
+        recipe::get_recipe_book()
+            .get(id)
+            .ok_or_else(|| anyhow::anyhow!("No recipe {id}"))
+    }
+
+    #[test]
+    fn crafts_need_the_station_in_reach() -> Result<()> {
+        testing::load_data()?;
+        let world = World::generate(SEED);
+        let lamp = recipe("lamp")?;
+        assert_eq!(lamp.station, Station::CraftingBench);
+        let bench = station(Station::CraftingBench);
+
+        assert_eq!(check_craft(&world, lamp, bench), Ok(()));
+        assert_eq!(
+            check_craft(&world, lamp, bench + Vec3::new(30.0, 0.0, -30.0)),
+            Ok(())
+        );
+        // Spawn is a short walk from the stations
+        assert!(check_craft(&world, lamp, Vec3::ZERO).is_err());
+        assert!(check_craft(
+            &world,
+            lamp,
+            bench + Vec3::X * (Station::CraftingBench.reach() + 1.0)
+        )
+        .is_err());
+        // Standing at the wrong station doesn't count
+        assert!(check_craft(&world, lamp, station(Station::Furnace)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn furnace_recipes_cant_be_crafted() -> Result<()> {
+        testing::load_data()?;
+        let world = World::generate(SEED);
+
+        for recipe in recipe::get_recipe_book().for_station(Station::Furnace) {
+            assert!(check_craft(&world, recipe, station(Station::Furnace)).is_err());
+        }
+        let ingot = recipe("copper_ingot")?;
+        assert_eq!(ingot.station, Station::Furnace);
+        assert!(check_craft(&world, ingot, station(Station::Furnace)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn kicks_need_a_higher_permission() {
+        assert!(check_kick(Permission::Moderator, Permission::Player).is_ok());
+        assert!(check_kick(Permission::Admin, Permission::Moderator).is_ok());
+        assert!(check_kick(Permission::Moderator, Permission::Moderator).is_err());
+        assert!(check_kick(Permission::Moderator, Permission::Admin).is_err());
+        assert!(check_kick(Permission::Admin, Permission::Admin).is_err());
+    }
+}
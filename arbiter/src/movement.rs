@@ -0,0 +1,81 @@
+use common::{
+    movement::{self, MovementInput, MovementState, INPUT_RATE},
+    net::TICK_RATE,
+};
+use std::collections::VecDeque;
+
+/// Inputs a client is allowed each tick, this is what stops it moving faster than everyone else
+const INPUTS_PER_TICK: u32 = INPUT_RATE / TICK_RATE;
+/// Lets a client catch up on inputs that arrived late without letting it bank up a speed boost
+const MAX_BUDGET: u32 = INPUTS_PER_TICK * 2;
+/// Inputs past this are dropped, half a second's worth
+const MAX_QUEUED: usize = INPUT_RATE as usize / 2;
+/// Violations decay by one a second, past this a player is reported as a likely cheater
+pub const REPORT_THRESHOLD: u32 = 10;
+
+/// Inputs waiting to be simulated, the client's only way of moving its player
+#[derive(Clone, PartialEq)]
+pub struct InputQueue {
+    inputs: VecDeque<(u32, MovementInput)>,
+    last_sequence: u32,
+    budget: u32,
+    pub violations: u32,
+}
+
+impl InputQueue {
+    pub const fn new() -> Self {
+        Self {
+            inputs: VecDeque::new(),
+            last_sequence: 0,
+            budget: MAX_BUDGET,
+            violations: 0,
+        }
+    }
+
+    /// Sequence of the last input that was simulated
+    pub const fn last_sequence(&self) -> u32 {
+        self.last_sequence
+    }
+
+    /// Queues an input for the next tick, returns false if it was dropped for arriving faster than
+    /// the client should be sending them
+    pub fn push(&mut self, sequence: u32, input: MovementInput) -> bool {
+        let newest = self
+            .inputs
+            .back()
+            .map_or(self.last_sequence, |(sequence, _)| *sequence);
+        // Unreliable, so old inputs can turn up after newer ones
+        if sequence <= newest {
+            return true;
+        }
+
+        if self.inputs.len() >= MAX_QUEUED {
+            self.violations += 1;
+            return false;
+        }
+
+        self.inputs.push_back((sequence, input));
+        true
+    }
+
+    /// Simulates as many queued inputs as the client has budget for this tick
+    pub fn apply(&mut self, state: MovementState) -> MovementState {
+        self.budget = (self.budget + INPUTS_PER_TICK).min(MAX_BUDGET);
+
+        let mut state = state;
+        while self.budget > 0 {
+            let Some((sequence, input)) = self.inputs.pop_front() else {
+                break;
+            };
+            state = movement::simulate(state, &input);
+            self.last_sequence = sequence;
+            self.budget -= 1;
+        }
+
+        state
+    }
+
+    pub const fn forgive(&mut self) {
+        self.violations = self.violations.saturating_sub(1);
+    }
+}
@@ -0,0 +1,62 @@
+use anyhow::Result;
+use common::{item, recipe};
+use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+use std::sync::Once;
+
+const ITEMS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/data/items.ron");
+const RECIPES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/data/recipes.ron");
+
+fn load() -> Result<()> {
+    item::load_registry(ITEMS)?;
+    recipe::load_recipe_book(RECIPES)?;
+    Ok(())
+}
+
+/// Loads the shipped item registry and recipe book, they're global so only the first call does
+/// anything
+pub fn load_data() -> Result<()> {
+    static LOAD: Once = Once::new();
+    let mut result = Ok(());
+    LOAD.call_once(|| result = load());
+    result
+}
+
+/// A fresh database with every migration applied, it only lives as long as the pool
+pub async fn pool() -> Result<SqlitePool> {
+    // Every connection to `:memory:` is its own database, so only ever open one
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await?;
+    sqlx::migrate!().run(&pool).await?;
+
+    Ok(pool)
+}
+
+/// Creates a user without a password, returning their ID
+pub async fn user(pool: &SqlitePool, username: &str) -> Result<i64> {
+    Ok(sqlx::query!(
+        "INSERT INTO users (username, password_hash) VALUES (?, '')",
+        username
+    )
+    .execute(pool)
+    .await?
+    .last_insert_rowid())
+}
+
+/// Creates a user with one character, returning the character's ID
+pub async fn character(pool: &SqlitePool, username: &str) -> Result<i64> {
+    let user = user(pool, username).await?;
+
+    Ok(sqlx::query!(
+        "INSERT INTO characters (name, position_x, position_y, position_z, owner)
+         VALUES (?, 0, 0, 0, ?)",
+        username,
+        user
+    )
+    .execute(pool)
+    .await?
+    .last_insert_rowid())
+}
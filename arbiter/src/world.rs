@@ -0,0 +1,164 @@
+use crate::{interest::Grid, time::unix_time};
+use common::{
+    node::NodeKind,
+    recipe::Station,
+    worldgen::{self, StationPlacement},
+};
+use glam::Vec3;
+use sqlx::SqlitePool;
+use std::{collections::HashMap, time::SystemTime};
+use tracing::info;
+
+/// Size of the cells nodes are bucketed into for nearby lookups
+const CELL_SIZE: f32 = 100.0;
+
+pub struct Node {
+    pub kind: NodeKind,
+    pub position: Vec3,
+    /// Unix time the node comes back, `None` while it can be gathered
+    pub respawn_at: Option<i64>,
+}
+
+impl Node {
+    pub const fn is_depleted(&self) -> bool {
+        self.respawn_at.is_some()
+    }
+}
+
+/// Resource nodes everyone shares, the layout comes from the world seed and only depletion is
+/// stored in the database
+pub struct World {
+    seed: u32,
+    nodes: HashMap<u32, Node>,
+    grid: Grid<u32>,
+    stations: Vec<StationPlacement>,
+}
+
+impl World {
+    /// Generates the world from the stored seed, picking a new seed if there isn't one yet
+    pub async fn load(pool: &SqlitePool) -> Result<Self, sqlx::Error> {
+        let row = sqlx::query!("SELECT seed FROM world WHERE id = 0")
+            .fetch_optional(pool)
+            .await?;
+        let seed = if let Some(row) = row {
+            u32::try_from(row.seed).unwrap_or_default()
+        } else {
+            let seed = rand::random::<u32>();
+            sqlx::query!("INSERT INTO world (id, seed) VALUES (0, ?)", seed)
+                .execute(pool)
+                .await?;
+            info!("Created world with seed {}", seed);
+            seed
+        };
+
+        let mut world = Self::generate(seed);
+
+        let depleted = sqlx::query!("SELECT id, respawn_at FROM depleted_nodes")
+            .fetch_all(pool)
+            .await?;
+        for row in depleted {
+            let node = u32::try_from(row.id)
+                .ok()
+                .and_then(|id| world.nodes.get_mut(&id));
+            if let Some(node) = node {
+                node.respawn_at = Some(row.respawn_at);
+            }
+        }
+
+        info!(
+            "Generated {} resource nodes from seed {}",
+            world.nodes.len(),
+            seed
+        );
+
+        Ok(world)
+    }
+
+    /// The world as the seed lays it out, with every node available
+    pub fn generate(seed: u32) -> Self {
+        let layout = worldgen::generate(seed);
+        let nodes = layout
+            .nodes()
+            .map(|(id, placement)| {
+                (
+                    id,
+                    Node {
+                        kind: placement.kind,
+                        position: placement.position,
+                        respawn_at: None,
+                    },
+                )
+            })
+            .collect::<HashMap<u32, Node>>();
+
+        let mut grid = Grid::new(CELL_SIZE);
+        for (id, node) in &nodes {
+            grid.insert(*id, node.position);
+        }
+
+        Self {
+            seed,
+            nodes,
+            grid,
+            stations: layout.stations,
+        }
+    }
+
+    pub const fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Node> {
+        self.nodes.get(&id)
+    }
+
+    pub fn nearby(&self, position: Vec3, radius: f32) -> Vec<u32> {
+        self.grid.nearby(position, radius)
+    }
+
+    /// Whether a station of this kind is within reach of `position`
+    pub fn can_use(&self, station: Station, position: Vec3) -> bool {
+        self.stations.iter().any(|placement| {
+            placement.station == station
+                && (placement.position - position).length() <= station.reach()
+        })
+    }
+
+    /// Marks the node as gathered until its respawn time has passed
+    pub async fn deplete(&mut self, pool: &SqlitePool, id: u32) -> Result<(), sqlx::Error> {
+        let Some(node) = self.nodes.get_mut(&id) else {
+            return Ok(());
+        };
+
+        let respawn_at = unix_time(SystemTime::now() + node.kind.respawn_time());
+        sqlx::query!(
+            "INSERT OR REPLACE INTO depleted_nodes (id, respawn_at) VALUES (?, ?)",
+            id,
+            respawn_at
+        )
+        .execute(pool)
+        .await?;
+        node.respawn_at = Some(respawn_at);
+
+        Ok(())
+    }
+
+    /// Brings back nodes whose respawn time has passed, returning their IDs
+    pub async fn respawn(&mut self, pool: &SqlitePool) -> Result<Vec<u32>, sqlx::Error> {
+        let now = unix_time(SystemTime::now());
+
+        sqlx::query!("DELETE FROM depleted_nodes WHERE respawn_at <= ?", now)
+            .execute(pool)
+            .await?;
+
+        Ok(self
+            .nodes
+            .iter_mut()
+            .filter(|(_, node)| node.respawn_at.is_some_and(|respawn_at| respawn_at <= now))
+            .map(|(id, node)| {
+                node.respawn_at = None;
+                *id
+            })
+            .collect())
+    }
+}
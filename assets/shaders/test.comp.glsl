@@ -12,6 +12,7 @@ layout(set = 0, binding = 1) uniform Time {
 struct Vertex {
 	vec3 position;
 	vec3 normal;
+	vec2 uv;
 };
 
 layout(set = 1, binding = 0) uniform writeonly image2D outColor;
@@ -37,8 +38,21 @@ layout(std140, set = 1, binding = 3) buffer Meshes {
 	Mesh meshes[];
 } meshes;
 
+// Region of the texel buffer holding an image, offset is -1 when there isn't one
+struct TextureRegion {
+	int offset;
+	int width;
+	int height;
+};
+
+struct Material {
+	vec4 albedo;
+	TextureRegion albedoTexture;
+	TextureRegion normalTexture;
+};
+
 layout(std140, set = 1, binding = 4) buffer Materials {
-	vec3 materials[];
+	Material materials[];
 } materials;
 
 
@@ -53,6 +67,11 @@ layout(std140, set = 1, binding = 5) buffer Lights {
 	Light lights[];
 } lights;
 
+// Every model's images packed together, RGBA8 per texel
+layout(std430, set = 1, binding = 6) buffer Texels {
+	uint texels[];
+} texels;
+
 layout (local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
 
 float INFINITY = 1.0/0.0;
@@ -105,6 +124,9 @@ struct HitPayload {
   int mesh;
 	int material;
 	vec3 position;
+	vec2 uv;
+	vec3 tangent;
+	vec3 bitangent;
 	float t;
 };
 
@@ -140,6 +162,7 @@ struct TriangleHit {
 	bool hit;
 	vec3 position;
 	vec3 normal;
+	vec2 uv;
 	float t;
 };
 
@@ -158,6 +181,7 @@ TriangleHit triangle_hit(Ray ray, Triangle triangle) {
 	hit.hit = all(greaterThanEqual(b, vec4(0.0))) && all(lessThanEqual(b.yzw, vec3(1.0)));
 	hit.position = ray.origin + b.x * ray.direction;
 	hit.normal = triangle.v0.normal * b.y + triangle.v1.normal * b.z + triangle.v2.normal * b.w;
+	hit.uv = triangle.v0.uv * b.y + triangle.v1.uv * b.z + triangle.v2.uv * b.w;
 	hit.t = b.x;
 	return hit;
 }
@@ -168,6 +192,31 @@ Vertex vertex_transform(Vertex v, mat4 transform) {
 	return v;
 }
 
+// Directions the triangle's U and V increase in, for normal mapping
+void triangle_tangents(Triangle triangle, out vec3 tangent, out vec3 bitangent) {
+	vec3 edge1 = triangle.v1.position - triangle.v0.position;
+	vec3 edge2 = triangle.v2.position - triangle.v0.position;
+	vec2 duv1 = triangle.v1.uv - triangle.v0.uv;
+	vec2 duv2 = triangle.v2.uv - triangle.v0.uv;
+
+	float det = duv1.x * duv2.y - duv2.x * duv1.y;
+	if (abs(det) < EPSILON) {
+		tangent = vec3(0.0);
+		bitangent = vec3(0.0);
+		return;
+	}
+
+	tangent = (edge1 * duv2.y - edge2 * duv1.y) / det;
+	bitangent = (edge2 * duv1.x - edge1 * duv2.x) / det;
+}
+
+// Nearest texel, UVs outside 0 to 1 repeat
+vec4 sample_texture(TextureRegion region, vec2 uv) {
+	ivec2 size = ivec2(region.width, region.height);
+	ivec2 texel = min(ivec2(fract(uv) * vec2(size)), size - 1);
+	return unpackUnorm4x8(texels.texels[region.offset + texel.y * size.x + texel.x]);
+}
+
 bool intersects_box(Ray ray, vec3 bmin, vec3 bmax) {
 	float tx1 = (bmin.x - ray.origin.x) / ray.direction.x, tx2 = (bmax.x - ray.origin.x) / ray.direction.x;
 	float tmin = min( tx1, tx2 ), tmax = max( tx1, tx2 );
@@ -185,6 +234,9 @@ HitPayload trace_ray(Ray ray, vec3 target) {
 	payload.material = -1;
 	payload.position = vec3(0.0);
 	payload.normal = vec3(0.0);
+	payload.uv = vec2(0.0);
+	payload.tangent = vec3(0.0);
+	payload.bitangent = vec3(0.0);
 
 	float minT = INFINITY;
 
@@ -213,6 +265,8 @@ HitPayload trace_ray(Ray ray, vec3 target) {
 				payload.material = mesh.material;
 				payload.position = hit.position;
 				payload.normal = normalize(hit.normal);
+				payload.uv = hit.uv;
+				triangle_tangents(triangle, payload.tangent, payload.bitangent);
 			}
 			
 			/*minT = minT * float(!overwrite) + hit.t * float(overwrite);
@@ -232,7 +286,20 @@ vec3 per_pixel(Ray incoming) {
 	if (!hit.hit) { return vec3(0.0, 0.0, 0.0); }
 
   Mesh mesh = meshes.meshes[hit.mesh];
-	vec3 material = materials.materials[hit.material];
+	Material material = materials.materials[hit.material];
+
+	vec3 albedo = material.albedo.rgb;
+	if (material.albedoTexture.offset >= 0) {
+		albedo *= sample_texture(material.albedoTexture, hit.uv).rgb;
+	}
+
+	bool hasTangents = length(hit.tangent) > EPSILON && length(hit.bitangent) > EPSILON;
+	if (material.normalTexture.offset >= 0 && hasTangents) {
+		vec3 tangent = normalize(hit.tangent - hit.normal * dot(hit.normal, hit.tangent));
+		vec3 bitangent = normalize(hit.bitangent - hit.normal * dot(hit.normal, hit.bitangent));
+		vec3 normal = sample_texture(material.normalTexture, hit.uv).xyz * 2.0 - 1.0;
+		hit.normal = normalize(mat3(tangent, bitangent, hit.normal) * normal);
+	}
 	
 	Ray outgoing;
 	outgoing.origin = hit.position + hit.normal;
@@ -260,7 +327,7 @@ vec3 per_pixel(Ray incoming) {
 	}
 
 	if (length(diffuse) < 0.05) { diffuse = AMBIENT; }
-	vec3 color = material * diffuse;
+	vec3 color = albedo * diffuse;
 
 	if (length(color) > 1.0) { color = normalize(color); }
 	return color;
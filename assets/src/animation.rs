@@ -0,0 +1,455 @@
+use glam::{Mat4, Quat, Vec3};
+use gltf::{GltfError, Interpolation, TargetPath};
+use std::ops::{Add, Mul};
+use tracing::warn;
+
+use crate::Transform;
+
+/// Every node in the document with its rest pose, animations only override parts of it
+pub struct Skeleton {
+    parents: Vec<Option<usize>>,
+    rest: Vec<Transform>,
+}
+
+impl Skeleton {
+    pub fn new(gltf: &gltf::Gltf) -> Self {
+        let mut parents = vec![None; gltf.nodes.len()];
+        for (parent, node) in gltf.nodes.iter().enumerate() {
+            for child in &node.children {
+                if let Some(slot) = parents.get_mut(*child) {
+                    *slot = Some(parent);
+                }
+            }
+        }
+
+        let rest = gltf
+            .nodes
+            .iter()
+            .map(|node| match node.matrix {
+                Some(matrix) => Transform::from_matrix(&Mat4::from_cols_array(&matrix)),
+                None => Transform {
+                    translation: node.translation.map_or(Vec3::ZERO, Vec3::from_array),
+                    rotation: node.rotation.map_or(Quat::IDENTITY, Quat::from_array),
+                    scale: node.scale.map_or(Vec3::ONE, Vec3::from_array),
+                },
+            })
+            .collect();
+
+        Self { parents, rest }
+    }
+
+    /// Model space matrix of every node given their local transforms
+    fn globals(&self, locals: &[Transform]) -> Vec<Mat4> {
+        let mut globals: Vec<Option<Mat4>> = vec![None; locals.len()];
+
+        fn resolve(
+            skeleton: &Skeleton,
+            locals: &[Transform],
+            globals: &mut [Option<Mat4>],
+            node: usize,
+            depth: usize,
+        ) -> Mat4 {
+            if let Some(global) = globals[node] {
+                return global;
+            }
+
+            let local = locals[node].get_matrix();
+            // A cycle would be a malformed file, cut it off rather than recursing forever
+            let global = match skeleton.parents[node] {
+                Some(parent) if depth < locals.len() => {
+                    resolve(skeleton, locals, globals, parent, depth + 1) * local
+                }
+                _ => local,
+            };
+            globals[node] = Some(global);
+            global
+        }
+
+        (0..locals.len())
+            .map(|node| resolve(self, locals, &mut globals, node, 0))
+            .collect()
+    }
+}
+
+pub struct Skin {
+    /// Nodes used as joints, in the order `Mesh.joints` refers to them
+    pub joints: Vec<usize>,
+    pub inverse_bind_matrices: Vec<Mat4>,
+}
+
+impl Skin {
+    pub fn new(document: &gltf::Document, skin: &gltf::Skin) -> Result<Self, GltfError> {
+        Ok(Self {
+            joints: skin.joints.clone(),
+            inverse_bind_matrices: skin.get_inverse_bind_matrices(document)?,
+        })
+    }
+}
+
+enum Keyframes {
+    Translation(Vec<Vec3>),
+    Rotation(Vec<Quat>),
+    Scale(Vec<Vec3>),
+}
+
+struct Channel {
+    node: usize,
+    interpolation: Interpolation,
+    times: Vec<f32>,
+    /// Cubic spline channels have an in-tangent, value and out-tangent for every time
+    keyframes: Keyframes,
+}
+
+/// Finds the keyframe before `t` and how far it is towards the next one, `t` is clamped to the
+/// channel's range
+fn locate(times: &[f32], t: f32) -> (usize, usize, f32) {
+    let last = times.len() - 1;
+    if t <= times[0] {
+        return (0, 0, 0.0);
+    }
+    if t >= times[last] {
+        return (last, last, 0.0);
+    }
+
+    let next = times.partition_point(|time| *time <= t);
+    let previous = next - 1;
+    let span = times[next] - times[previous];
+    let s = if span > 0.0 {
+        (t - times[previous]) / span
+    } else {
+        0.0
+    };
+    (previous, next, s)
+}
+
+fn interpolate<T>(
+    times: &[f32],
+    values: &[T],
+    interpolation: Interpolation,
+    t: f32,
+    lerp: impl Fn(T, T, f32) -> T,
+) -> T
+where
+    T: Copy + Add<Output = T> + Mul<f32, Output = T>,
+{
+    let (previous, next, s) = locate(times, t);
+
+    match interpolation {
+        Interpolation::Step => values[previous],
+        Interpolation::Linear => lerp(values[previous], values[next], s),
+        Interpolation::CubicSpline => {
+            let value = |keyframe: usize| values[keyframe * 3 + 1];
+            if previous == next {
+                return value(previous);
+            }
+
+            let delta = times[next] - times[previous];
+            let out_tangent = values[previous * 3 + 2] * delta;
+            let in_tangent = values[next * 3] * delta;
+
+            let s2 = s * s;
+            let s3 = s2 * s;
+            value(previous) * (2.0 * s3 - 3.0 * s2 + 1.0)
+                + out_tangent * (s3 - 2.0 * s2 + s)
+                + value(next) * (-2.0 * s3 + 3.0 * s2)
+                + in_tangent * (s3 - s2)
+        }
+    }
+}
+
+impl Channel {
+    fn apply(&self, transform: &mut Transform, t: f32) {
+        match &self.keyframes {
+            Keyframes::Translation(values) => {
+                transform.translation =
+                    interpolate(&self.times, values, self.interpolation, t, Vec3::lerp);
+            }
+            Keyframes::Rotation(values) => {
+                transform.rotation =
+                    interpolate(&self.times, values, self.interpolation, t, Quat::slerp)
+                        .normalize();
+            }
+            Keyframes::Scale(values) => {
+                transform.scale =
+                    interpolate(&self.times, values, self.interpolation, t, Vec3::lerp);
+            }
+        }
+    }
+}
+
+/// A named animation, sampled at a time in seconds to pose a skeleton
+pub struct Clip {
+    pub name: String,
+    /// Time of the last keyframe in any channel
+    pub duration: f32,
+    channels: Vec<Channel>,
+}
+
+impl Clip {
+    pub fn new(
+        document: &gltf::Document,
+        animation: &gltf::Animation,
+        index: usize,
+    ) -> Result<Self, GltfError> {
+        let mut channels = Vec::new();
+        for channel in &animation.channels {
+            let Some(node) = channel.target.node else {
+                continue;
+            };
+            let sampler = gltf::get(&animation.samplers, "animation sampler", channel.sampler)?;
+            let times = gltf::get(&document.gltf.accessors, "accessor", sampler.input)?
+                .get_scalars(document)?;
+            let values = gltf::get(&document.gltf.accessors, "accessor", sampler.output)?;
+
+            let expected = match sampler.interpolation {
+                Interpolation::CubicSpline => times.len() * 3,
+                _ => times.len(),
+            };
+            let keyframes = match channel.target.path {
+                TargetPath::Translation => Keyframes::Translation(values.get_vec3s(document)?),
+                TargetPath::Rotation => Keyframes::Rotation(
+                    values
+                        .get_vec4s(document)?
+                        .into_iter()
+                        .map(Quat::from_vec4)
+                        .collect(),
+                ),
+                TargetPath::Scale => Keyframes::Scale(values.get_vec3s(document)?),
+                // Morph targets aren't supported
+                TargetPath::Weights => continue,
+            };
+            let count = match &keyframes {
+                Keyframes::Translation(values) | Keyframes::Scale(values) => values.len(),
+                Keyframes::Rotation(values) => values.len(),
+            };
+            if times.is_empty() || count != expected {
+                warn!(
+                    "Skipping animation channel with {} times and {} values",
+                    times.len(),
+                    count
+                );
+                continue;
+            }
+
+            channels.push(Channel {
+                node,
+                interpolation: sampler.interpolation,
+                times,
+                keyframes,
+            });
+        }
+
+        let duration = channels
+            .iter()
+            .filter_map(|channel| channel.times.last())
+            .fold(0.0, |duration: f32, time| duration.max(*time));
+
+        Ok(Self {
+            name: animation
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("Animation {index}")),
+            duration,
+            channels,
+        })
+    }
+
+    /// Local transform of every node at `t` seconds, nodes without a channel keep their rest pose
+    pub fn sample(&self, skeleton: &Skeleton, t: f32) -> Vec<Transform> {
+        let mut locals = skeleton.rest.clone();
+        for channel in &self.channels {
+            if let Some(transform) = locals.get_mut(channel.node) {
+                channel.apply(transform, t);
+            }
+        }
+        locals
+    }
+}
+
+/// Matrices that move a vertex from bind pose into the posed model, one per joint in the skin
+pub fn joint_matrices(skeleton: &Skeleton, skin: &Skin, locals: &[Transform]) -> Vec<Mat4> {
+    let globals = skeleton.globals(locals);
+    skin.joints
+        .iter()
+        .enumerate()
+        .map(|(i, joint)| {
+            let inverse_bind = skin
+                .inverse_bind_matrices
+                .get(i)
+                .copied()
+                .unwrap_or(Mat4::IDENTITY);
+            globals.get(*joint).copied().unwrap_or(Mat4::IDENTITY) * inverse_bind
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RIGGED_ARM: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/meshes/rigged_arm.glb");
+
+    fn lerp(from: f32, to: f32, s: f32) -> f32 {
+        from + (to - from) * s
+    }
+
+    fn translated(translation: Vec3) -> Transform {
+        Transform {
+            translation,
+            ..Transform::IDENTITY
+        }
+    }
+
+    /// A root with one child a unit above it
+    fn skeleton() -> Skeleton {
+        Skeleton {
+            parents: vec![None, Some(0)],
+            rest: vec![Transform::IDENTITY, translated(Vec3::Y)],
+        }
+    }
+
+    #[test]
+    fn locate_clamps_and_finds_the_span() {
+        let times = [0.0, 1.0, 3.0];
+
+        assert_eq!(locate(&times, -1.0), (0, 0, 0.0));
+        assert_eq!(locate(&times, 0.5), (0, 1, 0.5));
+        assert_eq!(locate(&times, 1.0), (1, 2, 0.0));
+        assert_eq!(locate(&times, 2.5), (1, 2, 0.75));
+        assert_eq!(locate(&times, 5.0), (2, 2, 0.0));
+        assert_eq!(locate(&[2.0], 1.0), (0, 0, 0.0));
+    }
+
+    #[test]
+    fn step_holds_the_previous_keyframe() {
+        let times = [0.0, 1.0];
+        let values = [1.0, 5.0];
+
+        assert_eq!(
+            interpolate(&times, &values, Interpolation::Step, 0.99, lerp),
+            1.0
+        );
+        assert_eq!(
+            interpolate(&times, &values, Interpolation::Step, 1.0, lerp),
+            5.0
+        );
+    }
+
+    #[test]
+    fn linear_blends_between_keyframes() {
+        let times = [0.0, 1.0, 3.0];
+        let values = [1.0, 5.0, 7.0];
+
+        assert_eq!(
+            interpolate(&times, &values, Interpolation::Linear, 0.25, lerp),
+            2.0
+        );
+        assert_eq!(
+            interpolate(&times, &values, Interpolation::Linear, 2.0, lerp),
+            6.0
+        );
+        assert_eq!(
+            interpolate(&times, &values, Interpolation::Linear, 10.0, lerp),
+            7.0
+        );
+    }
+
+    #[test]
+    fn cubic_spline_uses_scaled_tangents() {
+        let times = [0.0, 2.0];
+        // In-tangent, value and out-tangent for each keyframe
+        let flat = [0.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let sloped = [0.0, 0.0, 1.0, 0.0, 1.0, 0.0];
+        let spline =
+            |values: &[f32], t| interpolate(&times, values, Interpolation::CubicSpline, t, lerp);
+
+        assert_eq!(spline(&flat, 1.0), 0.5);
+        assert_eq!(spline(&flat, 0.5), 0.15625);
+        // The out-tangent is per second, so it's doubled across the two second span
+        assert_eq!(spline(&sloped, 1.0), 0.75);
+        assert_eq!(spline(&sloped, -1.0), 0.0);
+        assert_eq!(spline(&sloped, 3.0), 1.0);
+    }
+
+    #[test]
+    fn sample_only_overrides_animated_nodes() {
+        let clip = Clip {
+            name: "Slide".to_owned(),
+            duration: 1.0,
+            channels: vec![
+                Channel {
+                    node: 0,
+                    interpolation: Interpolation::Linear,
+                    times: vec![0.0, 1.0],
+                    keyframes: Keyframes::Translation(vec![Vec3::ZERO, Vec3::new(2.0, 0.0, 0.0)]),
+                },
+                // Points past the end of the skeleton, it's skipped rather than panicking
+                Channel {
+                    node: 5,
+                    interpolation: Interpolation::Step,
+                    times: vec![0.0],
+                    keyframes: Keyframes::Scale(vec![Vec3::ZERO]),
+                },
+            ],
+        };
+
+        let locals = clip.sample(&skeleton(), 0.5);
+        assert_eq!(locals.len(), 2);
+        assert_eq!(locals[0].translation, Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(locals[1].translation, Vec3::Y);
+    }
+
+    #[test]
+    fn joint_matrices_undo_the_bind_pose() {
+        let skeleton = skeleton();
+        let skin = Skin {
+            joints: vec![1, 0],
+            inverse_bind_matrices: vec![Mat4::from_translation(-Vec3::Y), Mat4::IDENTITY],
+        };
+
+        let rest = joint_matrices(&skeleton, &skin, &skeleton.rest);
+        assert!(rest
+            .iter()
+            .all(|matrix| matrix.abs_diff_eq(Mat4::IDENTITY, 1e-6)));
+
+        let moved = [translated(Vec3::X), translated(Vec3::Y)];
+        for matrix in joint_matrices(&skeleton, &skin, &moved) {
+            assert!(matrix.abs_diff_eq(Mat4::from_translation(Vec3::X), 1e-6));
+        }
+    }
+
+    #[test]
+    fn poses_the_rigged_sample() -> Result<(), GltfError> {
+        let document = gltf::Document::load(RIGGED_ARM)?;
+        let skeleton = Skeleton::new(&document.gltf);
+        let skin = Skin::new(&document, &document.gltf.skins[0])?;
+        let clip = Clip::new(&document, &document.gltf.animations[0], 0)?;
+
+        assert_eq!(clip.name, "Bend");
+        assert_eq!(clip.duration, 1.0);
+        assert_eq!(clip.channels.len(), 3);
+
+        let bind = joint_matrices(&skeleton, &skin, &clip.sample(&skeleton, 0.0));
+        assert!(bind
+            .iter()
+            .all(|matrix| matrix.abs_diff_eq(Mat4::IDENTITY, 1e-6)));
+
+        // Halfway the root has eased half way along Z, the elbow is rotated 45 degrees and has
+        // stepped up to double scale
+        let [root, elbow] = joint_matrices(&skeleton, &skin, &clip.sample(&skeleton, 0.5))[..]
+        else {
+            panic!("Expected two joints");
+        };
+        assert!(root.abs_diff_eq(Mat4::from_translation(Vec3::Z), 1e-5));
+        let tip = elbow.transform_point3(Vec3::new(0.0, 2.0, 0.0));
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert!(tip.abs_diff_eq(Vec3::new(-2.0 * half, 1.0 + 2.0 * half, 1.0), 1e-5));
+
+        // At the end the upper half of the arm points along -X
+        let joints = joint_matrices(&skeleton, &skin, &clip.sample(&skeleton, 1.0));
+        let tip = joints[1].transform_point3(Vec3::new(0.5, 2.0, 0.0));
+        assert!(tip.abs_diff_eq(Vec3::new(-2.0, 2.0, 2.0), 1e-5));
+
+        Ok(())
+    }
+}
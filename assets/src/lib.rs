@@ -1,11 +1,17 @@
+mod animation;
+
+pub use animation::{Clip, Skeleton, Skin};
+
 use ash::vk;
 use bytemuck::{cast_slice, Pod, Zeroable};
 use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+use gltf::{get, GltfError};
 use std::{
     collections::HashMap,
     path::Path,
     sync::{Arc, Weak},
 };
+use tracing::warn;
 use uuid::Uuid;
 use vulkan::{buffer::Buffer, context::Context, device::Device, graphics::Shader, Texture};
 
@@ -66,18 +72,115 @@ pub struct Vertex {
     pub _padding: f32,
     pub normal: Vec3,
     pub _padding2: f32,
+    pub uv: Vec2,
+    pub _padding3: [f32; 2],
+}
+
+/// Meshes are scaled up from the units they're exported in
+const MODEL_SCALE: f32 = 100.0;
+
+/// A decoded image from a model, as RGBA8 pixels row by row
+pub struct Image {
+    pub id: Uuid,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub base_color: Vec4,
+    /// Index into `Model.images`, sampled with the vertices' UVs and multiplied by `base_color`
+    pub base_color_texture: Option<usize>,
+    /// Index into `Model.images`, tangent space normals
+    pub normal_texture: Option<usize>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            base_color: Vec4::ONE,
+            base_color_texture: None,
+            normal_texture: None,
+        }
+    }
 }
 
 pub struct Model {
     pub meshes: Vec<Mesh>,
+    /// Every image a material refers to
+    pub images: Vec<Image>,
+    pub skeleton: Skeleton,
+    pub skins: Vec<Skin>,
+    pub clips: Vec<Clip>,
+}
+
+impl Model {
+    pub fn get_clip(&self, name: &str) -> Option<&Clip> {
+        self.clips.iter().find(|clip| clip.name == name)
+    }
+
+    /// Poses `skin` with `clip` at `t` seconds, see `Mesh::skin_vertices`
+    pub fn joint_matrices(&self, skin: usize, clip: &Clip, t: f32) -> Vec<Mat4> {
+        let Some(skin) = self.skins.get(skin) else {
+            return Vec::new();
+        };
+
+        let locals = clip.sample(&self.skeleton, t);
+        animation::joint_matrices(&self.skeleton, skin, &locals)
+    }
 }
 
 pub struct Mesh {
     pub id: Uuid,
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
-    pub color: Vec4,
+    pub material: Material,
     pub transform: Transform,
+    /// Index into `Model.skins`, skinned meshes ignore their node's transform
+    pub skin: Option<usize>,
+    /// Up to four joints per vertex, empty unless the mesh is skinned
+    pub joints: Vec<[u16; 4]>,
+    pub weights: Vec<Vec4>,
+}
+
+impl Mesh {
+    /// Vertices moved by the joint matrices from `Model::joint_matrices`, meshes without a skin
+    /// are returned as they are
+    pub fn skin_vertices(&self, joint_matrices: &[Mat4]) -> Vec<Vertex> {
+        if self.joints.is_empty() {
+            return self.vertices.clone();
+        }
+
+        // Joint matrices work in exported units
+        let scale = Mat4::from_scale(Vec3::splat(MODEL_SCALE));
+        let unscale = Mat4::from_scale(Vec3::splat(1.0 / MODEL_SCALE));
+
+        self.vertices
+            .iter()
+            .zip(self.joints.iter().zip(&self.weights))
+            .map(|(vertex, (joints, weights))| {
+                let skin = joints
+                    .iter()
+                    .zip(weights.to_array())
+                    .map(|(joint, weight)| {
+                        joint_matrices
+                            .get(*joint as usize)
+                            .copied()
+                            .unwrap_or(Mat4::IDENTITY)
+                            * weight
+                    })
+                    .fold(Mat4::ZERO, |skin, matrix| skin + matrix);
+                let skin = scale * skin * unscale;
+
+                Vertex {
+                    pos: skin.transform_point3(vertex.pos),
+                    normal: skin.transform_vector3(vertex.normal).normalize_or_zero(),
+                    ..*vertex
+                }
+            })
+            .collect()
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -169,64 +272,203 @@ impl ModelRegistry {
             }
         }
 
-        fn get_meshes(glb: &gltf::Glb, node: &gltf::Node, parent_transform: Mat4) -> Vec<Mesh> {
+        /// Images decoded so far, each is only decoded once however many materials use it
+        #[derive(Default)]
+        struct Images {
+            images: Vec<Image>,
+            /// Index in the document to index in `images`, `None` if it couldn't be decoded
+            loaded: HashMap<usize, Option<usize>>,
+        }
+
+        fn get_image(
+            document: &gltf::Document,
+            images: &mut Images,
+            texture: usize,
+            tex_coord: usize,
+        ) -> Result<Option<usize>, GltfError> {
+            // Only TEXCOORD_0 is read
+            if tex_coord != 0 {
+                warn!("Skipping texture using TEXCOORD_{}", tex_coord);
+                return Ok(None);
+            }
+
+            let source = get(&document.gltf.textures, "texture", texture)?.source;
+            if let Some(loaded) = images.loaded.get(&source) {
+                return Ok(*loaded);
+            }
+
+            let data = document.get_image_data(source)?;
+            let loaded = match image::load_from_memory(&data) {
+                Ok(decoded) => {
+                    let decoded = decoded.into_rgba8();
+                    images.images.push(Image {
+                        id: Uuid::new_v4(),
+                        width: decoded.width(),
+                        height: decoded.height(),
+                        pixels: decoded.into_raw(),
+                    });
+                    Some(images.images.len() - 1)
+                }
+                Err(e) => {
+                    warn!("Skipping image {} due to {}", source, e);
+                    None
+                }
+            };
+            images.loaded.insert(source, loaded);
+            Ok(loaded)
+        }
+
+        fn get_material(
+            document: &gltf::Document,
+            images: &mut Images,
+            material: usize,
+        ) -> Result<Material, GltfError> {
+            let material = get(&document.gltf.materials, "material", material)?;
+            let base_color_texture = match &material.pbr.base_color_texture {
+                Some(texture) => get_image(document, images, texture.index, texture.tex_coord)?,
+                None => None,
+            };
+            let normal_texture = match &material.normal_texture {
+                Some(texture) => get_image(document, images, texture.index, texture.tex_coord)?,
+                None => None,
+            };
+
+            Ok(Material {
+                base_color: material
+                    .pbr
+                    .base_color_factor
+                    .map_or(Vec4::ONE, Vec4::from_array),
+                base_color_texture,
+                normal_texture,
+            })
+        }
+
+        fn get_meshes(
+            document: &gltf::Document,
+            images: &mut Images,
+            node: &gltf::Node,
+            parent_transform: Mat4,
+        ) -> Result<Vec<Mesh>, GltfError> {
             let transform = get_transform(node) * parent_transform;
 
             let mut meshes = match node.mesh {
                 None => Vec::new(),
                 Some(mesh) => {
-                    let mesh = &glb.gltf.meshes[mesh];
+                    let mesh = get(&document.gltf.meshes, "mesh", mesh)?;
                     mesh.primitives
                         .iter()
                         .map(|primitive| {
-                            let color = primitive
-                                .material
-                                .map(|material| &glb.gltf.materials[material])
-                                .and_then(|material| material.pbr.base_color_factor)
-                                .map(|arr| Vec4::from_array(arr))
-                                .unwrap_or(Vec4::ONE);
-
-                            let indices = primitive.get_indices_data(glb).expect("No indicies");
+                            let material = match primitive.material {
+                                Some(material) => get_material(document, images, material)?,
+                                None => Material::default(),
+                            };
+
+                            let indices = primitive.get_indices_data(document)?;
                             let positions = primitive
-                                .get_attribute_data(glb, "POSITION")
-                                .expect("No positions");
-                            let positions = bytemuck::cast_slice::<u8, Vec3>(&positions)
-                                .iter()
-                                .map(|position| *position * 100.0)
-                                .collect::<Vec<Vec3>>();
+                                .get_attribute(document, "POSITION")?
+                                .get_vec3s(document)?
+                                .into_iter()
+                                .map(|position| position * MODEL_SCALE);
                             let normals = primitive
-                                .get_attribute_data(glb, "NORMAL")
-                                .expect("No normals");
-                            let normals = bytemuck::cast_slice::<u8, Vec3>(&normals);
-                            let vertices: Vec<Vertex> = std::iter::zip(positions, normals)
+                                .get_attribute(document, "NORMAL")?
+                                .get_vec3s(document)?;
+                            let mut vertices: Vec<Vertex> = std::iter::zip(positions, normals)
                                 .map(|(pos, normal)| Vertex {
                                     pos,
-                                    normal: *normal,
+                                    normal,
                                     ..Default::default()
                                 })
                                 .collect();
-                            Mesh {
+
+                            // Untextured meshes often have no UVs, leave them at zero
+                            if primitive.attributes.contains_key("TEXCOORD_0") {
+                                let uvs = primitive
+                                    .get_attribute(document, "TEXCOORD_0")?
+                                    .get_vec2s(document)?;
+                                if uvs.len() == vertices.len() {
+                                    for (vertex, uv) in vertices.iter_mut().zip(uvs) {
+                                        vertex.uv = uv;
+                                    }
+                                }
+                            }
+
+                            let has_skin = primitive.attributes.contains_key("JOINTS_0")
+                                && primitive.attributes.contains_key("WEIGHTS_0");
+                            let (joints, weights) = if has_skin {
+                                (
+                                    primitive.get_joints_data(document)?,
+                                    primitive.get_weights_data(document)?,
+                                )
+                            } else {
+                                (Vec::new(), Vec::new())
+                            };
+                            let skinned = node.skin.is_some()
+                                && joints.len() == vertices.len()
+                                && weights.len() == vertices.len();
+                            let transform = if skinned {
+                                Transform::IDENTITY
+                            } else {
+                                Transform::from_matrix(&transform)
+                            };
+
+                            Ok(Mesh {
                                 id: Uuid::new_v4(),
                                 indices,
                                 vertices,
-                                color,
-                                transform: Transform::from_matrix(&transform),
-                            }
+                                material,
+                                transform,
+                                skin: node.skin.filter(|_| skinned),
+                                joints: if skinned { joints } else { Vec::new() },
+                                weights: if skinned { weights } else { Vec::new() },
+                            })
                         })
-                        .collect()
+                        .collect::<Result<Vec<Mesh>, GltfError>>()?
                 }
             };
 
-            meshes.append(
-                &mut node
-                    .children
-                    .iter()
-                    .map(|child| &glb.gltf.nodes[*child])
-                    .flat_map(|child| get_meshes(glb, child, transform))
-                    .collect(),
-            );
+            for child in &node.children {
+                let child = get(&document.gltf.nodes, "node", *child)?;
+                meshes.append(&mut get_meshes(document, images, child, transform)?);
+            }
 
-            meshes
+            Ok(meshes)
+        }
+
+        fn get_model(document: &gltf::Document) -> Result<Model, GltfError> {
+            let scene = get(&document.gltf.scenes, "scene", document.gltf.scene)?;
+            let mut images = Images::default();
+            let mut meshes = Vec::new();
+            for node in &scene.nodes {
+                let node = get(&document.gltf.nodes, "node", *node)?;
+                meshes.append(&mut get_meshes(
+                    document,
+                    &mut images,
+                    node,
+                    Mat4::IDENTITY,
+                )?);
+            }
+
+            let skins = document
+                .gltf
+                .skins
+                .iter()
+                .map(|skin| Skin::new(document, skin))
+                .collect::<Result<Vec<Skin>, GltfError>>()?;
+            let clips = document
+                .gltf
+                .animations
+                .iter()
+                .enumerate()
+                .map(|(i, animation)| Clip::new(document, animation, i))
+                .collect::<Result<Vec<Clip>, GltfError>>()?;
+
+            Ok(Model {
+                meshes,
+                images: images.images,
+                skeleton: Skeleton::new(&document.gltf),
+                skins,
+                clips,
+            })
         }
 
         let registry_value = self
@@ -238,18 +480,13 @@ impl ModelRegistry {
         match registry_value {
             Some(value) => value,
             None => {
-                let glb_path = Path::new("assets/meshes").join(path);
-                println!("Loading: {}", glb_path.display());
+                let model_path = Path::new("assets/meshes").join(path);
+                println!("Loading: {}", model_path.display());
 
-                let glb = gltf::Glb::load(&std::fs::read(glb_path).unwrap()).unwrap();
-                let scene = &glb.gltf.scenes[glb.gltf.scene];
-                let meshes = scene
-                    .nodes
-                    .iter()
-                    .map(|node| &glb.gltf.nodes[*node])
-                    .flat_map(|node| get_meshes(&glb, node, Mat4::IDENTITY))
-                    .collect();
-                let model = Model { meshes };
+                // Works for both .glb and .gltf
+                let model = gltf::Document::load(model_path)
+                    .and_then(|document| get_model(&document))
+                    .unwrap_or_else(|e| panic!("Failed to load {}: {}", path, e));
 
                 let model = Arc::new(model);
                 self.registry
@@ -0,0 +1,149 @@
+use crate::{
+    item::{Item, ItemStack},
+    recipe::{get_recipe_book, Recipe, Station},
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FurnaceSlot {
+    Input,
+    Fuel,
+}
+
+/// Smelts its input using furnace recipes for as long as it has fuel to burn. `advance` only
+/// depends on the elapsed time, so the client can predict progress between updates from the
+/// arbiter and the arbiter can catch up on time the player spent away.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Furnace {
+    pub input: Option<ItemStack>,
+    pub fuel: Option<ItemStack>,
+    pub output: Option<ItemStack>,
+    /// Seconds left burning the last piece of fuel that was taken
+    pub burn_remaining: f32,
+    /// Seconds spent smelting the current item
+    pub progress: f32,
+}
+
+impl Furnace {
+    pub fn accepts(slot: FurnaceSlot, item: &Item) -> bool {
+        match slot {
+            FurnaceSlot::Input => get_recipe_book()
+                .for_station(Station::Furnace)
+                .any(|recipe| recipe.ingredients.iter().any(|stack| &stack.item == item)),
+            FurnaceSlot::Fuel => burn_time(item).is_some(),
+        }
+    }
+
+    pub const fn slot(&self, slot: FurnaceSlot) -> Option<&ItemStack> {
+        match slot {
+            FurnaceSlot::Input => self.input.as_ref(),
+            FurnaceSlot::Fuel => self.fuel.as_ref(),
+        }
+    }
+
+    pub fn slot_mut(&mut self, slot: FurnaceSlot) -> &mut Option<ItemStack> {
+        match slot {
+            FurnaceSlot::Input => &mut self.input,
+            FurnaceSlot::Fuel => &mut self.fuel,
+        }
+    }
+
+    /// The recipe being smelted, if there's enough input and room in the output for it
+    pub fn recipe(&self) -> Option<&'static Recipe> {
+        let input = self.input.as_ref()?;
+
+        get_recipe_book()
+            .for_station(Station::Furnace)
+            .find(|recipe| {
+                let ([ingredient], [output]) =
+                    (recipe.ingredients.as_slice(), recipe.outputs.as_slice())
+                else {
+                    return false;
+                };
+
+                let fits = self.output.as_ref().map_or(
+                    output.amount <= output.item.max_stack(),
+                    |existing| {
+                        existing.item == output.item
+                            && existing.amount + output.amount <= existing.item.max_stack()
+                    },
+                );
+
+                ingredient.item == input.item && ingredient.amount <= input.amount && fits
+            })
+    }
+
+    /// How far through the current smelt the furnace is, from 0 to 1
+    pub fn progress_fraction(&self) -> f32 {
+        self.recipe()
+            .map_or(0.0, |recipe| (self.progress / recipe.craft_time).min(1.0))
+    }
+
+    pub fn advance(&mut self, mut elapsed: f32) {
+        while elapsed > 0.0 {
+            let Some(recipe) = self.recipe() else {
+                self.progress = 0.0;
+                return;
+            };
+
+            if self.burn_remaining <= 0.0 && !self.take_fuel() {
+                return;
+            }
+
+            let step = elapsed
+                .min(self.burn_remaining)
+                .min(recipe.craft_time - self.progress);
+            self.progress += step;
+            self.burn_remaining -= step;
+            elapsed -= step;
+
+            if self.progress >= recipe.craft_time {
+                self.finish(recipe);
+            }
+        }
+    }
+
+    fn take_fuel(&mut self) -> bool {
+        let Some(fuel) = &mut self.fuel else {
+            return false;
+        };
+        let Some(burn_time) = burn_time(&fuel.item) else {
+            return false;
+        };
+
+        fuel.amount -= 1;
+        if fuel.amount == 0 {
+            self.fuel = None;
+        }
+        self.burn_remaining += burn_time;
+
+        true
+    }
+
+    fn finish(&mut self, recipe: &Recipe) {
+        self.progress = 0.0;
+
+        for ingredient in &recipe.ingredients {
+            if let Some(input) = &mut self.input {
+                input.amount = input.amount.saturating_sub(ingredient.amount);
+                if input.amount == 0 {
+                    self.input = None;
+                }
+            }
+        }
+
+        for output in &recipe.outputs {
+            match &mut self.output {
+                Some(existing) => existing.amount += output.amount,
+                None => self.output = Some(output.clone()),
+            }
+        }
+    }
+}
+
+/// Seconds of burning one of the item gives, `None` if it isn't fuel
+fn burn_time(item: &Item) -> Option<f32> {
+    item.def()
+        .and_then(|def| def.burn_time)
+        .filter(|burn_time| *burn_time > 0.0)
+}
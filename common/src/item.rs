@@ -1,35 +1,139 @@
-use num_derive::{FromPrimitive, ToPrimitive};
 use serde::{Deserialize, Serialize};
-use std::fmt::Display;
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, fmt::Display, fs, path::Path, sync::OnceLock};
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
-pub enum Item {
-    Wood,
-    Fireglow,
-    Lamp,
-    CopperOre,
-    CopperIngot,
-    CopperSword,
+pub const REGISTRY_PATH: &str = "assets/data/items.ron";
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+#[derive(thiserror::Error, Debug)]
+pub enum RegistryError {
+    #[error("Failed to read item registry")]
+    IOError(#[from] std::io::Error),
+    #[error("Failed to parse item registry")]
+    RonError(#[from] ron::error::SpannedError),
+    #[error("Item {0} is defined more than once")]
+    Duplicate(String),
+    #[error("Item registry has already been loaded")]
+    AlreadyLoaded,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ItemDef {
+    pub id: String,
+    pub name: String,
+    pub max_stack: u32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Path to the model in `assets/meshes`
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Seconds one of these burns for in a furnace, `None` if it isn't fuel
+    #[serde(default)]
+    pub burn_time: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct RegistryFile {
+    items: Vec<ItemDef>,
+}
+
+pub struct Registry {
+    version: u64,
+    items: HashMap<String, ItemDef>,
+}
+
+impl Registry {
+    pub fn parse(source: &str) -> Result<Self, RegistryError> {
+        let file: RegistryFile = ron::from_str(source)?;
+
+        let mut items = HashMap::new();
+        for def in file.items {
+            if items.contains_key(&def.id) {
+                return Err(RegistryError::Duplicate(def.id));
+            }
+            items.insert(def.id.clone(), def);
+        }
+
+        // Hashing the whole file means any edit changes the version, no need to remember to bump it
+        let digest = Sha256::digest(source.as_bytes());
+        let mut version = [0_u8; 8];
+        version.copy_from_slice(&digest[..8]);
+
+        Ok(Self {
+            version: u64::from_le_bytes(version),
+            items,
+        })
+    }
+
+    /// Clients with a different version are turned away as they'd disagree about what items exist
+    pub const fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn get(&self, item: &Item) -> Option<&ItemDef> {
+        self.items.get(&item.0)
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &ItemDef> {
+        self.items.values()
+    }
+}
+
+/// Loads the registry into the global, call once at startup before any item is used
+pub fn load_registry(path: impl AsRef<Path>) -> Result<&'static Registry, RegistryError> {
+    let registry = Registry::parse(&fs::read_to_string(path)?)?;
+    REGISTRY
+        .set(registry)
+        .map_err(|_| RegistryError::AlreadyLoaded)?;
+
+    Ok(get_registry())
+}
+
+pub fn get_registry() -> &'static Registry {
+    REGISTRY
+        .get()
+        .expect("Item registry used before it was loaded")
+}
+
+/// An item's stable string ID, look it up in the registry for everything else
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct Item(String);
+
+impl Item {
+    pub fn new(id: &str) -> Self {
+        Self(id.to_owned())
+    }
+
+    pub fn id(&self) -> &str {
+        &self.0
+    }
+
+    pub fn def(&self) -> Option<&'static ItemDef> {
+        get_registry().get(self)
+    }
+
+    pub fn max_stack(&self) -> u32 {
+        self.def().map_or(0, |def| def.max_stack)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.def()
+            .is_some_and(|def| def.tags.iter().any(|t| t == tag))
+    }
 }
 
 impl Display for Item {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Self::Wood => "Wood",
-                Self::Fireglow => "Fireglow",
-                Self::Lamp => "Lamp",
-                Self::CopperOre => "Copper Ore",
-                Self::CopperIngot => "Copper Ingot",
-                Self::CopperSword => "Copper Sword",
-            }
-        )
+        match self.def() {
+            Some(def) => write!(f, "{}", def.name),
+            None => write!(f, "{}", self.0),
+        }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct ItemStack {
     pub item: Item,
     pub amount: u32,
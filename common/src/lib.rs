@@ -1,8 +1,18 @@
+pub mod furnace;
 pub mod item;
+pub mod movement;
 pub mod net;
+pub mod node;
+pub mod recipe;
+pub mod worldgen;
 
 use std::ops::Deref;
 
+/// Combined version of every data file, the arbiter turns away clients that disagree with it
+pub fn data_version() -> u64 {
+    item::get_registry().version() ^ recipe::get_recipe_book().version().rotate_left(1)
+}
+
 pub trait Observer<T> {
     fn notify(&self, old: &T, new: &T);
 }
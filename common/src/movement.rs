@@ -0,0 +1,93 @@
+use glam::{Quat, Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+pub const PLAYER_SPEED: f32 = 100.0;
+pub const JUMP_HEIGHT: f32 = 100.0;
+pub const JUMP_SPEED: f32 = 4.0;
+pub const DASH_DISTANCE: f32 = 100.0;
+/// Seconds before the player can dash again
+pub const DASH_COOLDOWN: f32 = 1.0;
+/// Dashes are only possible early on in a jump, while `jump_t` is above this
+pub const DASH_WINDOW: f32 = PI / 4.0;
+/// Movement is simulated in fixed steps, the client sends one input per step
+pub const INPUT_RATE: u32 = 60;
+pub const STEP: f32 = 1.0 / INPUT_RATE as f32;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MovementKeys {
+    pub forward: bool,
+    pub back: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+/// What the player did during a single step
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct MovementInput {
+    pub keys: MovementKeys,
+    pub jump: bool,
+    pub dash: bool,
+    /// Rotation of the camera around the Y axis, movement keys are relative to it
+    pub camera_theta: f32,
+    /// Screen space direction from the player to the cursor, dashes go this way
+    pub mouse_direction: Vec2,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct MovementState {
+    pub position: Vec3,
+    /// Progress through the current jump, counts down from PI to zero
+    pub jump_t: f32,
+    /// Seconds until the player can dash again
+    pub dash_cooldown: f32,
+}
+
+impl MovementState {
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            position,
+            ..Default::default()
+        }
+    }
+}
+
+/// Advances the player by one step, the client and arbiter both run this so the client can
+/// predict where the server will put it
+pub fn simulate(state: MovementState, input: &MovementInput) -> MovementState {
+    let mut state = state;
+
+    // Inputs come from the network, don't let a bad angle turn the position into NaN
+    let theta = if input.camera_theta.is_finite() {
+        input.camera_theta
+    } else {
+        0.0
+    };
+    let rotation = Quat::from_axis_angle(Vec3::Y, theta);
+
+    // Dash
+    if input.dash && state.jump_t >= DASH_WINDOW && state.dash_cooldown <= 0.0 {
+        let direction = input.mouse_direction.normalize_or_zero();
+        state.position += rotation * Vec3::new(direction.x, 0.0, direction.y) * DASH_DISTANCE;
+        state.dash_cooldown = DASH_COOLDOWN;
+    }
+    state.dash_cooldown = (state.dash_cooldown - STEP).max(0.0);
+
+    // Jump
+    if input.jump && state.jump_t == 0.0 {
+        state.jump_t = PI - 0.0001;
+    }
+
+    state.position.y = state.jump_t.sin().powf(0.6) * JUMP_HEIGHT;
+    state.jump_t = (state.jump_t - STEP * JUMP_SPEED).max(0.0);
+
+    // Movement
+    let z = i8::from(input.keys.forward) - i8::from(input.keys.back);
+    let x = i8::from(input.keys.right) - i8::from(input.keys.left);
+    if x != 0 || z != 0 {
+        let delta = Vec3::new(f32::from(x), 0.0, f32::from(z)).normalize() * PLAYER_SPEED * STEP;
+        state.position += rotation * delta;
+    }
+
+    state
+}
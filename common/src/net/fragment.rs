@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Most bytes of a message carried by one fragment, small enough that a sealed fragment fits in
+/// `MAX_DATAGRAM_SIZE`
+pub const FRAGMENT_SIZE: usize = 1024;
+/// Largest message either end will send or reassemble
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+const MAX_FRAGMENTS: usize = MAX_MESSAGE_SIZE / FRAGMENT_SIZE;
+/// Partially received messages are given up on after this, the sender will retransmit them if
+/// they were reliable
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Messages being reassembled at once, stops a peer from making us buffer an unbounded number of
+/// fragments
+const MAX_ASSEMBLIES: usize = 16;
+
+#[derive(thiserror::Error, Debug)]
+pub enum FragmentError {
+    #[error("Message of {0} bytes is over the {MAX_MESSAGE_SIZE} byte limit")]
+    TooLarge(usize),
+    #[error("Received a malformed fragment")]
+    Malformed,
+}
+
+/// Part of a message that was too large to send in one datagram
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Fragment {
+    pub id: u32,
+    pub index: u16,
+    pub count: u16,
+    pub bytes: Vec<u8>,
+}
+
+/// Splits a message into fragments that all share `id`
+pub fn split(id: u32, message: &[u8]) -> Result<Vec<Fragment>, FragmentError> {
+    if message.len() > MAX_MESSAGE_SIZE {
+        return Err(FragmentError::TooLarge(message.len()));
+    }
+
+    let count = message.len().div_ceil(FRAGMENT_SIZE) as u16;
+    Ok(message
+        .chunks(FRAGMENT_SIZE)
+        .enumerate()
+        .map(|(index, bytes)| Fragment {
+            id,
+            index: index as u16,
+            count,
+            bytes: bytes.to_vec(),
+        })
+        .collect())
+}
+
+struct Assembly {
+    fragments: Vec<Option<Vec<u8>>>,
+    received: usize,
+    started: Instant,
+}
+
+/// Collects fragments until every part of a message has arrived, in any order
+#[derive(Default)]
+pub struct Reassembler {
+    assemblies: HashMap<u32, Assembly>,
+}
+
+impl Reassembler {
+    /// Returns the whole message once its last fragment arrives
+    pub fn insert(&mut self, fragment: Fragment) -> Result<Option<Vec<u8>>, FragmentError> {
+        self.assemblies
+            .retain(|_, assembly| assembly.started.elapsed() < REASSEMBLY_TIMEOUT);
+
+        let id = fragment.id;
+        let count = usize::from(fragment.count);
+        let index = usize::from(fragment.index);
+        if !(2..=MAX_FRAGMENTS).contains(&count)
+            || index >= count
+            || fragment.bytes.is_empty()
+            || fragment.bytes.len() > FRAGMENT_SIZE
+        {
+            return Err(FragmentError::Malformed);
+        }
+
+        if !self.assemblies.contains_key(&id) && self.assemblies.len() >= MAX_ASSEMBLIES {
+            let oldest = self
+                .assemblies
+                .iter()
+                .min_by_key(|(_, assembly)| assembly.started)
+                .map(|(id, _)| *id);
+            if let Some(oldest) = oldest {
+                self.assemblies.remove(&oldest);
+            }
+        }
+
+        let assembly = self.assemblies.entry(id).or_insert_with(|| Assembly {
+            fragments: vec![None; count],
+            received: 0,
+            started: Instant::now(),
+        });
+        if assembly.fragments.len() != count {
+            return Err(FragmentError::Malformed);
+        }
+
+        let slot = &mut assembly.fragments[index];
+        if slot.is_none() {
+            *slot = Some(fragment.bytes);
+            assembly.received += 1;
+        }
+
+        if assembly.received < count {
+            return Ok(None);
+        }
+
+        Ok(self
+            .assemblies
+            .remove(&id)
+            .map(|assembly| assembly.fragments.into_iter().flatten().flatten().collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    fn fragment(id: u32, index: u16, count: u16) -> Fragment {
+        Fragment {
+            id,
+            index,
+            count,
+            bytes: vec![0; 8],
+        }
+    }
+
+    /// Pretends the assembly for `id` started `by` earlier than it did
+    fn age(reassembler: &mut Reassembler, id: u32, by: Duration) {
+        let assembly = reassembler.assemblies.get_mut(&id).unwrap();
+        assembly.started = assembly.started.checked_sub(by).unwrap();
+    }
+
+    #[test]
+    fn reassembles_out_of_order_fragments() {
+        let message = message(FRAGMENT_SIZE * 2 + 100);
+        let fragments = split(7, &message).unwrap();
+        assert_eq!(fragments.len(), 3);
+
+        let mut reassembler = Reassembler::default();
+        let mut fragments = fragments.into_iter().rev();
+        assert!(reassembler
+            .insert(fragments.next().unwrap())
+            .unwrap()
+            .is_none());
+        assert!(reassembler
+            .insert(fragments.next().unwrap())
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            reassembler.insert(fragments.next().unwrap()).unwrap(),
+            Some(message)
+        );
+        assert!(reassembler.assemblies.is_empty());
+    }
+
+    #[test]
+    fn duplicate_fragments_are_ignored() {
+        let message = message(FRAGMENT_SIZE + 1);
+        let fragments = split(1, &message).unwrap();
+
+        let mut reassembler = Reassembler::default();
+        assert!(reassembler.insert(fragments[0].clone()).unwrap().is_none());
+        assert!(reassembler.insert(fragments[0].clone()).unwrap().is_none());
+        assert_eq!(
+            reassembler.insert(fragments[1].clone()).unwrap(),
+            Some(message)
+        );
+    }
+
+    #[test]
+    fn malformed_fragments_are_rejected() {
+        let mut reassembler = Reassembler::default();
+
+        assert!(matches!(
+            reassembler.insert(fragment(1, 0, 0)),
+            Err(FragmentError::Malformed)
+        ));
+        assert!(matches!(
+            reassembler.insert(fragment(1, 3, 3)),
+            Err(FragmentError::Malformed)
+        ));
+
+        assert!(reassembler.insert(fragment(1, 0, 3)).unwrap().is_none());
+        assert!(matches!(
+            reassembler.insert(fragment(1, 1, 4)),
+            Err(FragmentError::Malformed)
+        ));
+    }
+
+    #[test]
+    fn oversized_messages_are_rejected() {
+        assert_eq!(
+            split(1, &message(MAX_MESSAGE_SIZE)).unwrap().len(),
+            MAX_FRAGMENTS
+        );
+        assert!(matches!(
+            split(1, &message(MAX_MESSAGE_SIZE + 1)),
+            Err(FragmentError::TooLarge(size)) if size == MAX_MESSAGE_SIZE + 1
+        ));
+    }
+
+    #[test]
+    fn stale_assemblies_time_out() {
+        let message = message(FRAGMENT_SIZE + 1);
+        let fragments = split(1, &message).unwrap();
+
+        let mut reassembler = Reassembler::default();
+        reassembler.insert(fragments[0].clone()).unwrap();
+        age(&mut reassembler, 1, REASSEMBLY_TIMEOUT);
+
+        // The first half was thrown away, so this starts over
+        assert!(reassembler.insert(fragments[1].clone()).unwrap().is_none());
+        assert_eq!(
+            reassembler.insert(fragments[0].clone()).unwrap(),
+            Some(message)
+        );
+    }
+
+    #[test]
+    fn assemblies_are_capped() {
+        let mut reassembler = Reassembler::default();
+        for id in 0..MAX_ASSEMBLIES as u32 {
+            reassembler.insert(fragment(id, 0, 2)).unwrap();
+        }
+        age(&mut reassembler, 3, Duration::from_secs(1));
+
+        reassembler.insert(fragment(100, 0, 2)).unwrap();
+        assert_eq!(reassembler.assemblies.len(), MAX_ASSEMBLIES);
+        assert!(!reassembler.assemblies.contains_key(&3));
+        assert!(reassembler.assemblies.contains_key(&100));
+    }
+}
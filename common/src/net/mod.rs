@@ -0,0 +1,493 @@
+pub mod fragment;
+pub mod reliable;
+pub mod secure;
+
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+/// Bump whenever a packet changes shape, postcard encodes enum variants by index so any change
+/// leaves older peers misreading everything after it
+pub const PROTOCOL_VERSION: u32 = 3;
+/// Receive buffer size for both ends, every datagram sent fits in this
+pub const MAX_DATAGRAM_SIZE: usize = 1200;
+/// Longest chat message in characters, commands included
+pub const MAX_CHAT_LENGTH: usize = 128;
+/// How many times a second the arbiter simulates and sends snapshots
+pub const TICK_RATE: u32 = 20;
+/// Snapshot positions are sent in sixteenths of a unit
+const QUANTIZE_SCALE: f32 = 16.0;
+
+/// Position rounded to a fixed grid, postcard encodes the integers as varints so this is a few
+/// bytes per axis instead of four
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuantizedVec3 {
+    x: i32,
+    y: i32,
+    z: i32,
+}
+
+impl From<Vec3> for QuantizedVec3 {
+    fn from(value: Vec3) -> Self {
+        let value = (value * QUANTIZE_SCALE).round();
+        Self {
+            x: value.x as i32,
+            y: value.y as i32,
+            z: value.z as i32,
+        }
+    }
+}
+
+impl From<QuantizedVec3> for Vec3 {
+    fn from(value: QuantizedVec3) -> Self {
+        Self::new(value.x as f32, value.y as f32, value.z as f32) / QUANTIZE_SCALE
+    }
+}
+
+pub mod server {
+    use super::reliable::{Channel, Delivery};
+    use crate::{furnace::FurnaceSlot, item::ItemStack, movement::MovementInput};
+    use serde::{Deserialize, Serialize};
+
+    /// First packet of every connection, nothing else is handled until the server accepts it
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Hello {
+        pub protocol_version: u32,
+        /// Version of the client build, only used for logging
+        pub build_id: String,
+        /// See `common::data_version`
+        pub data_version: u64,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Login {
+        pub username: String,
+        pub password: String,
+    }
+
+    /// Sent once per movement step, the server simulates these in order
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct PlayerInput {
+        pub sequence: u32,
+        pub input: MovementInput,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Signup {
+        pub username: String,
+        pub password: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Resume {
+        pub token: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Craft {
+        pub recipe_id: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Gather {
+        pub node_id: u32,
+    }
+
+    /// Said by the player, messages starting with `/` are run as commands
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct ChatMessage {
+        pub message: String,
+    }
+
+    /// Moves the stack from the player's inventory into the furnace
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct LoadFurnace {
+        pub slot: FurnaceSlot,
+        pub stack: ItemStack,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub enum Packet {
+        /// Must stay the first variant and never change, it's how mismatched versions are caught
+        Hello(Hello),
+        Login(Login),
+        PlayerInput(PlayerInput),
+        Heartbeat,
+        Disconnect,
+        Signup(Signup),
+        Resume(Resume),
+        Craft(Craft),
+        Gather(Gather),
+        OpenFurnace,
+        LoadFurnace(LoadFurnace),
+        TakeFurnaceOutput,
+        ChatMessage(ChatMessage),
+    }
+
+    impl Delivery for Packet {
+        fn channel(&self) -> Channel {
+            match self {
+                Self::PlayerInput(_) | Self::Heartbeat => Channel::Unreliable,
+                Self::Craft(_)
+                | Self::Gather(_)
+                | Self::OpenFurnace
+                | Self::LoadFurnace(_)
+                | Self::TakeFurnaceOutput => Channel::Inventory,
+                Self::ChatMessage(_) => Channel::Chat,
+                Self::Hello(_)
+                | Self::Login(_)
+                | Self::Signup(_)
+                | Self::Resume(_)
+                | Self::Disconnect => Channel::System,
+            }
+        }
+    }
+}
+
+pub mod client {
+    use super::{
+        reliable::{Channel, Delivery},
+        QuantizedVec3,
+    };
+    use crate::{furnace::Furnace, item::ItemStack, movement::MovementState};
+    use serde::{Deserialize, Serialize};
+
+    /// Reply to a `Hello` the server won't accept, nothing else the client sends is handled
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Rejected {
+        pub reason: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct SpawnPlayer {
+        pub username: String,
+        pub position: glam::Vec3,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct DespawnPlayer {
+        pub username: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct PlayerState {
+        pub username: String,
+        pub position: QuantizedVec3,
+    }
+
+    /// Sent once a tick with every player the client can see
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Snapshot {
+        pub tick: u32,
+        /// Last input the server simulated for this client, and where it left them
+        pub sequence: u32,
+        pub state: MovementState,
+        pub players: Vec<PlayerState>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct NotifyDisconnection {
+        pub reason: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct DisplayError {
+        pub message: String,
+        pub fatal: bool,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct SessionToken {
+        pub token: String,
+    }
+
+    /// Sets the amount of an item the player has, zero removes the stack
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct ModifyInventory {
+        pub stack: ItemStack,
+    }
+
+    /// Sent on login, the client generates the world layout from the seed itself
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct WorldSeed {
+        pub seed: u32,
+    }
+
+    /// Sent when a node is gathered or respawns, and for every depleted node on login
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct UpdateNode {
+        pub id: u32,
+        pub depleted: bool,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct FurnaceState {
+        pub furnace: Furnace,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct ChatMessage {
+        /// `None` for messages from the server, like command output
+        pub sender: Option<String>,
+        pub message: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub enum Packet {
+        /// Must stay the first variant and never change, older clients need to be able to read it
+        Rejected(Rejected),
+        SpawnPlayer(SpawnPlayer),
+        DespawnPlayer(DespawnPlayer),
+        Snapshot(Snapshot),
+        NotifyDisconnection(NotifyDisconnection),
+        ModifyInventory(ModifyInventory),
+        DisplayError(DisplayError),
+        SessionToken(SessionToken),
+        WorldSeed(WorldSeed),
+        UpdateNode(UpdateNode),
+        FurnaceState(FurnaceState),
+        ChatMessage(ChatMessage),
+    }
+
+    impl Delivery for Packet {
+        fn channel(&self) -> Channel {
+            match self {
+                Self::Snapshot(_) => Channel::Unreliable,
+                Self::SpawnPlayer(_) | Self::DespawnPlayer(_) => Channel::Players,
+                Self::WorldSeed(_) | Self::UpdateNode(_) => Channel::World,
+                Self::ModifyInventory(_) | Self::FurnaceState(_) => Channel::Inventory,
+                Self::ChatMessage(_) => Channel::Chat,
+                Self::Rejected(_)
+                | Self::NotifyDisconnection(_)
+                | Self::DisplayError(_)
+                | Self::SessionToken(_) => Channel::System,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        furnace::{Furnace, FurnaceSlot},
+        item::{Item, ItemStack},
+        movement::{MovementInput, MovementKeys, MovementState},
+    };
+    use glam::Vec2;
+    use std::{fmt::Write, fs, path::PathBuf};
+
+    /// Set this to rewrite the fixtures after a deliberate wire change, and bump
+    /// `PROTOCOL_VERSION` alongside it
+    const UPDATE: &str = "UPDATE_FIXTURES";
+
+    fn fixtures(side: &str) -> PathBuf {
+        [env!("CARGO_MANIFEST_DIR"), "fixtures", "packets", side]
+            .iter()
+            .collect()
+    }
+
+    fn stack(id: &str, amount: u32) -> ItemStack {
+        ItemStack {
+            item: Item::new(id),
+            amount,
+        }
+    }
+
+    /// Checks every packet against its fixture, they have to be listed in declaration order so a
+    /// new variant can't be left out
+    fn check(side: &str, packets: &[(&str, Vec<u8>)]) {
+        let mut mismatched = Vec::new();
+        for (index, (name, bytes)) in packets.iter().enumerate() {
+            // Postcard writes the variant index first, as a single byte while it's under 128
+            assert_eq!(
+                usize::from(bytes[0]),
+                index,
+                "{side} {name} is out of order"
+            );
+
+            let encoded = bytes.iter().fold(String::new(), |mut hex, byte| {
+                let _ = write!(hex, "{byte:02x}");
+                hex
+            });
+            let path = fixtures(side).join(name).with_extension("hex");
+            if std::env::var_os(UPDATE).is_some() {
+                fs::create_dir_all(fixtures(side)).unwrap();
+                fs::write(&path, format!("{encoded}\n")).unwrap();
+                continue;
+            }
+
+            let expected = fs::read_to_string(&path)
+                .unwrap_or_else(|_| panic!("{} is missing", path.display()));
+            if expected.trim() != encoded {
+                mismatched.push(*name);
+            }
+        }
+
+        let count = fs::read_dir(fixtures(side)).unwrap().count();
+        assert_eq!(count, packets.len(), "stale {side} fixtures");
+        assert!(
+            mismatched.is_empty(),
+            "{side} packets changed on the wire: {mismatched:?}"
+        );
+    }
+
+    #[test]
+    fn server_packets_match_fixtures() {
+        use server::*;
+
+        // Exhaustive so adding a variant won't build until it's given a fixture
+        let name = |packet: &Packet| match packet {
+            Packet::Hello(_) => "hello",
+            Packet::Login(_) => "login",
+            Packet::PlayerInput(_) => "player_input",
+            Packet::Heartbeat => "heartbeat",
+            Packet::Disconnect => "disconnect",
+            Packet::Signup(_) => "signup",
+            Packet::Resume(_) => "resume",
+            Packet::Craft(_) => "craft",
+            Packet::Gather(_) => "gather",
+            Packet::OpenFurnace => "open_furnace",
+            Packet::LoadFurnace(_) => "load_furnace",
+            Packet::TakeFurnaceOutput => "take_furnace_output",
+            Packet::ChatMessage(_) => "chat_message",
+        };
+
+        let packets = [
+            Packet::Hello(Hello {
+                protocol_version: PROTOCOL_VERSION,
+                build_id: "0.1.0".to_owned(),
+                data_version: 0x0123_4567_89ab_cdef,
+            }),
+            Packet::Login(Login {
+                username: "jw".to_owned(),
+                password: "hunter2".to_owned(),
+            }),
+            Packet::PlayerInput(PlayerInput {
+                sequence: 300,
+                input: MovementInput {
+                    keys: MovementKeys {
+                        forward: true,
+                        right: true,
+                        ..Default::default()
+                    },
+                    jump: true,
+                    dash: false,
+                    camera_theta: 1.5,
+                    mouse_direction: Vec2::new(-3.0, 4.0),
+                },
+            }),
+            Packet::Heartbeat,
+            Packet::Disconnect,
+            Packet::Signup(Signup {
+                username: "jw".to_owned(),
+                password: "hunter2".to_owned(),
+            }),
+            Packet::Resume(Resume {
+                token: "c0ffee".to_owned(),
+            }),
+            Packet::Craft(Craft {
+                recipe_id: "lamp".to_owned(),
+            }),
+            Packet::Gather(Gather { node_id: 17 }),
+            Packet::OpenFurnace,
+            Packet::LoadFurnace(LoadFurnace {
+                slot: FurnaceSlot::Fuel,
+                stack: stack("wood", 3),
+            }),
+            Packet::TakeFurnaceOutput,
+            Packet::ChatMessage(ChatMessage {
+                message: "/help".to_owned(),
+            }),
+        ];
+
+        let packets: Vec<_> = packets
+            .iter()
+            .map(|packet| (name(packet), postcard::to_stdvec(packet).unwrap()))
+            .collect();
+        check("server", &packets);
+    }
+
+    #[test]
+    fn client_packets_match_fixtures() {
+        use client::*;
+
+        // Exhaustive so adding a variant won't build until it's given a fixture
+        let name = |packet: &Packet| match packet {
+            Packet::Rejected(_) => "rejected",
+            Packet::SpawnPlayer(_) => "spawn_player",
+            Packet::DespawnPlayer(_) => "despawn_player",
+            Packet::Snapshot(_) => "snapshot",
+            Packet::NotifyDisconnection(_) => "notify_disconnection",
+            Packet::ModifyInventory(_) => "modify_inventory",
+            Packet::DisplayError(_) => "display_error",
+            Packet::SessionToken(_) => "session_token",
+            Packet::WorldSeed(_) => "world_seed",
+            Packet::UpdateNode(_) => "update_node",
+            Packet::FurnaceState(_) => "furnace_state",
+            Packet::ChatMessage(_) => "chat_message",
+        };
+
+        let packets = [
+            Packet::Rejected(Rejected {
+                reason: "Outdated client".to_owned(),
+            }),
+            Packet::SpawnPlayer(SpawnPlayer {
+                username: "jw".to_owned(),
+                position: Vec3::new(1.0, 2.0, -3.5),
+            }),
+            Packet::DespawnPlayer(DespawnPlayer {
+                username: "jw".to_owned(),
+            }),
+            Packet::Snapshot(Snapshot {
+                tick: 1000,
+                sequence: 42,
+                state: MovementState {
+                    position: Vec3::new(10.0, 0.5, -2.0),
+                    jump_t: 0.25,
+                    dash_cooldown: 1.0,
+                },
+                players: vec![PlayerState {
+                    username: "jw".to_owned(),
+                    position: Vec3::new(10.0, 0.5, -2.0).into(),
+                }],
+            }),
+            Packet::NotifyDisconnection(NotifyDisconnection {
+                reason: "Timed out".to_owned(),
+            }),
+            Packet::ModifyInventory(ModifyInventory {
+                stack: stack("wood", 12),
+            }),
+            Packet::DisplayError(DisplayError {
+                message: "Not enough wood".to_owned(),
+                fatal: false,
+            }),
+            Packet::SessionToken(SessionToken {
+                token: "c0ffee".to_owned(),
+            }),
+            Packet::WorldSeed(WorldSeed { seed: 7 }),
+            Packet::UpdateNode(UpdateNode {
+                id: 17,
+                depleted: true,
+            }),
+            Packet::FurnaceState(FurnaceState {
+                furnace: Furnace {
+                    input: Some(stack("copper_ore", 2)),
+                    fuel: None,
+                    output: Some(stack("copper_ingot", 1)),
+                    burn_remaining: 4.0,
+                    progress: 0.5,
+                },
+            }),
+            Packet::ChatMessage(ChatMessage {
+                sender: Some("jw".to_owned()),
+                message: "hello".to_owned(),
+            }),
+        ];
+
+        let packets: Vec<_> = packets
+            .iter()
+            .map(|packet| (name(packet), postcard::to_stdvec(packet).unwrap()))
+            .collect();
+        check("client", &packets);
+    }
+}
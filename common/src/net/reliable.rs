@@ -0,0 +1,316 @@
+use super::fragment::{self, Fragment, FragmentError, Reassembler, FRAGMENT_SIZE};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+const RETRANSMIT_AFTER: Duration = Duration::from_millis(250);
+// How far ahead of the next expected sequence we'll buffer before dropping, stops a peer from
+// making us hold onto an unbounded number of out of order messages
+const MAX_BUFFERED: u32 = 256;
+
+/// Reliable channels are each delivered in order, independently of one another, so a lost
+/// inventory update doesn't hold up player spawns
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Channel {
+    Unreliable,
+    Players,
+    World,
+    Inventory,
+    System,
+    Chat,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum EndpointError {
+    #[error("Error encoding or decoding message")]
+    Postcard(#[from] postcard::Error),
+    #[error("Error fragmenting or reassembling message")]
+    Fragment(#[from] FragmentError),
+}
+
+/// Implemented by packet enums to say how each variant should be delivered
+pub trait Delivery {
+    fn channel(&self) -> Channel;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Message {
+    Unreliable(Vec<u8>),
+    Reliable {
+        channel: Channel,
+        sequence: u32,
+        payload: Vec<u8>,
+    },
+    Ack {
+        channel: Channel,
+        sequence: u32,
+    },
+    /// Part of any other message that was too large for one datagram
+    Fragment(Fragment),
+}
+
+struct Pending {
+    /// Datagrams making up the message, resent as they are so a partly received message can
+    /// still be completed
+    datagrams: Vec<Vec<u8>>,
+    last_sent: Instant,
+}
+
+#[derive(Default)]
+pub struct Received {
+    /// Payloads ready for the application, in order for each channel
+    pub payloads: Vec<Vec<u8>>,
+    /// Message to send back to the peer, if any
+    pub reply: Option<Vec<u8>>,
+}
+
+/// One end of a connection, tracks sequence numbers, acks and retransmission
+#[derive(Default)]
+pub struct Endpoint {
+    next_sequence: HashMap<Channel, u32>,
+    pending: HashMap<(Channel, u32), Pending>,
+    expected: HashMap<Channel, u32>,
+    buffered: HashMap<Channel, BTreeMap<u32, Vec<u8>>>,
+    next_fragment_id: u32,
+    reassembler: Reassembler,
+}
+
+impl Endpoint {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps a payload for sending, returning the datagrams to send in order. Reliable messages
+    /// are remembered until acked.
+    pub fn send(
+        &mut self,
+        channel: Channel,
+        payload: Vec<u8>,
+    ) -> Result<Vec<Vec<u8>>, EndpointError> {
+        if channel == Channel::Unreliable {
+            let message = postcard::to_stdvec(&Message::Unreliable(payload))?;
+            return self.fragment(message);
+        }
+
+        let sequence = self.next_sequence.get(&channel).copied().unwrap_or(0);
+        let message = postcard::to_stdvec(&Message::Reliable {
+            channel,
+            sequence,
+            payload,
+        })?;
+        let datagrams = self.fragment(message)?;
+
+        self.pending.insert(
+            (channel, sequence),
+            Pending {
+                datagrams: datagrams.clone(),
+                last_sent: Instant::now(),
+            },
+        );
+        self.next_sequence.insert(channel, sequence.wrapping_add(1));
+
+        Ok(datagrams)
+    }
+
+    /// Splits messages that won't fit in a datagram
+    fn fragment(&mut self, message: Vec<u8>) -> Result<Vec<Vec<u8>>, EndpointError> {
+        if message.len() <= FRAGMENT_SIZE {
+            return Ok(vec![message]);
+        }
+
+        let id = self.next_fragment_id;
+        self.next_fragment_id = id.wrapping_add(1);
+
+        fragment::split(id, &message)?
+            .into_iter()
+            .map(|fragment| Ok(postcard::to_stdvec(&Message::Fragment(fragment))?))
+            .collect()
+    }
+
+    pub fn receive(&mut self, bytes: &[u8]) -> Result<Received, EndpointError> {
+        match postcard::from_bytes(bytes)? {
+            Message::Fragment(fragment) => match self.reassembler.insert(fragment)? {
+                Some(bytes) => self.deliver(postcard::from_bytes(&bytes)?),
+                None => Ok(Received::default()),
+            },
+            message => self.deliver(message),
+        }
+    }
+
+    fn deliver(&mut self, message: Message) -> Result<Received, EndpointError> {
+        match message {
+            Message::Unreliable(payload) => Ok(Received {
+                payloads: vec![payload],
+                reply: None,
+            }),
+            Message::Ack { channel, sequence } => {
+                self.pending.remove(&(channel, sequence));
+                Ok(Received::default())
+            }
+            Message::Reliable {
+                channel,
+                sequence,
+                payload,
+            } => {
+                let ack = postcard::to_stdvec(&Message::Ack { channel, sequence })?;
+
+                let expected = self.expected.entry(channel).or_insert(0);
+                let ahead = sequence.wrapping_sub(*expected);
+                if ahead >= MAX_BUFFERED {
+                    // Sequences we've already delivered wrap around to huge distances, ack them
+                    // again in case the first ack was lost. Anything else is too far ahead to
+                    // buffer, leave it unacked so it gets retransmitted later.
+                    let duplicate = ahead > u32::MAX / 2;
+                    return Ok(Received {
+                        payloads: Vec::new(),
+                        reply: duplicate.then_some(ack),
+                    });
+                }
+
+                let buffered = self.buffered.entry(channel).or_default();
+                buffered.insert(sequence, payload);
+
+                let mut payloads = Vec::new();
+                while let Some(payload) = buffered.remove(expected) {
+                    payloads.push(payload);
+                    *expected = expected.wrapping_add(1);
+                }
+
+                Ok(Received {
+                    payloads,
+                    reply: Some(ack),
+                })
+            }
+            // Fragments are never fragmented again, a peer nesting them is misbehaving
+            Message::Fragment(_) => Err(FragmentError::Malformed.into()),
+        }
+    }
+
+    /// Whether every reliable message sent so far has been acked
+    pub fn all_acked(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Messages that haven't been acked in time and should be sent again
+    pub fn retransmit(&mut self) -> Vec<Vec<u8>> {
+        self.pending
+            .values_mut()
+            .filter(|pending| pending.last_sent.elapsed() > RETRANSMIT_AFTER)
+            .flat_map(|pending| {
+                pending.last_sent = Instant::now();
+                pending.datagrams.clone()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends each payload on `channel`, returning one datagram per payload
+    fn send(endpoint: &mut Endpoint, channel: Channel, payloads: &[u8]) -> Vec<Vec<u8>> {
+        payloads
+            .iter()
+            .flat_map(|payload| endpoint.send(channel, vec![*payload]).unwrap())
+            .collect()
+    }
+
+    fn payloads(received: &Received) -> Vec<u8> {
+        received.payloads.iter().flatten().copied().collect()
+    }
+
+    #[test]
+    fn out_of_order_messages_are_delivered_in_order() {
+        let mut sender = Endpoint::new();
+        let mut receiver = Endpoint::new();
+        let datagrams = send(&mut sender, Channel::Inventory, &[0, 1, 2]);
+
+        let received = receiver.receive(&datagrams[2]).unwrap();
+        assert!(payloads(&received).is_empty());
+        // Buffered messages are still acked so they aren't resent
+        assert!(received.reply.is_some());
+
+        assert_eq!(payloads(&receiver.receive(&datagrams[0]).unwrap()), [0]);
+        assert_eq!(payloads(&receiver.receive(&datagrams[1]).unwrap()), [1, 2]);
+    }
+
+    #[test]
+    fn delivered_messages_are_acked_again() {
+        let mut sender = Endpoint::new();
+        let mut receiver = Endpoint::new();
+        let datagrams = send(&mut sender, Channel::System, &[0]);
+
+        let first = receiver.receive(&datagrams[0]).unwrap();
+        let second = receiver.receive(&datagrams[0]).unwrap();
+        assert_eq!(payloads(&first), [0]);
+        assert!(payloads(&second).is_empty());
+        assert_eq!(second.reply, first.reply);
+    }
+
+    #[test]
+    fn messages_too_far_ahead_are_dropped_unacked() {
+        let mut receiver = Endpoint::new();
+        let message = postcard::to_stdvec(&Message::Reliable {
+            channel: Channel::World,
+            sequence: MAX_BUFFERED,
+            payload: vec![0],
+        })
+        .unwrap();
+
+        let received = receiver.receive(&message).unwrap();
+        assert!(received.payloads.is_empty());
+        assert!(received.reply.is_none());
+        assert!(receiver.buffered.values().all(BTreeMap::is_empty));
+    }
+
+    #[test]
+    fn acks_clear_pending_messages() {
+        let mut sender = Endpoint::new();
+        let mut receiver = Endpoint::new();
+        let datagrams = send(&mut sender, Channel::Players, &[0, 1]);
+        assert_eq!(sender.pending.len(), 2);
+
+        let ack = receiver.receive(&datagrams[1]).unwrap().reply.unwrap();
+        sender.receive(&ack).unwrap();
+        assert_eq!(
+            sender.pending.keys().collect::<Vec<_>>(),
+            [&(Channel::Players, 0)]
+        );
+        assert!(!sender.all_acked());
+
+        let ack = receiver.receive(&datagrams[0]).unwrap().reply.unwrap();
+        sender.receive(&ack).unwrap();
+        assert!(sender.all_acked());
+    }
+
+    #[test]
+    fn unacked_messages_are_retransmitted() {
+        let mut sender = Endpoint::new();
+        let datagrams = send(&mut sender, Channel::Chat, &[0]);
+        send(&mut sender, Channel::Unreliable, &[1]);
+        assert!(sender.retransmit().is_empty());
+
+        for pending in sender.pending.values_mut() {
+            pending.last_sent -= RETRANSMIT_AFTER * 2;
+        }
+        assert_eq!(sender.retransmit(), datagrams);
+        // Resending restarts the timer
+        assert!(sender.retransmit().is_empty());
+    }
+
+    #[test]
+    fn channels_are_independent() {
+        let mut sender = Endpoint::new();
+        let mut receiver = Endpoint::new();
+        let inventory = send(&mut sender, Channel::Inventory, &[0, 1]);
+        let chat = send(&mut sender, Channel::Chat, &[2]);
+
+        // The lost inventory message doesn't hold up chat
+        assert!(payloads(&receiver.receive(&inventory[1]).unwrap()).is_empty());
+        assert_eq!(payloads(&receiver.receive(&chat[0]).unwrap()), [2]);
+        assert_eq!(payloads(&receiver.receive(&inventory[0]).unwrap()), [0, 1]);
+    }
+}
@@ -0,0 +1,378 @@
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use hkdf::Hkdf;
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+const CLIENT_KEY_INFO: &[u8] = b"aetheria client to server";
+const SERVER_KEY_INFO: &[u8] = b"aetheria server to client";
+const REPLAY_WINDOW: u64 = 64;
+
+/// Everything sent over the wire is one of these, only `Sealed` frames carry packets
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Frame {
+    ClientHello {
+        public_key: [u8; 32],
+    },
+    /// `confirmation` is an empty message sealed with counter 0, only a server holding the pinned
+    /// key could have derived the key to seal it
+    ServerHello {
+        public_key: [u8; 32],
+        confirmation: Vec<u8>,
+    },
+    Sealed {
+        counter: u64,
+        ciphertext: Vec<u8>,
+    },
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SecureError {
+    #[error("Error encoding or decoding frame")]
+    Postcard(#[from] postcard::Error),
+    #[error("Key exchange produced a non-contributory shared secret")]
+    WeakKey,
+    #[error("Datagram failed authentication")]
+    Aead,
+    #[error("Datagram was replayed or is too old")]
+    Replay,
+    #[error("Received an unexpected frame")]
+    UnexpectedFrame,
+    #[error("Server couldn't prove it holds the pinned key")]
+    Unauthenticated,
+}
+
+/// The arbiter's long-term key, clients pin the public half so nobody else can stand in for it
+pub struct ServerKey {
+    secret: StaticSecret,
+    public_key: PublicKey,
+}
+
+impl ServerKey {
+    pub fn generate() -> Self {
+        Self::from_bytes(StaticSecret::random_from_rng(OsRng).to_bytes())
+    }
+
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        let secret = StaticSecret::from(bytes);
+        let public_key = PublicKey::from(&secret);
+
+        Self { secret, public_key }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.secret.to_bytes()
+    }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        self.public_key.to_bytes()
+    }
+}
+
+/// Hex, the form public keys are handed to players in
+pub fn encode_public_key(public_key: &[u8; 32]) -> String {
+    public_key
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+pub fn parse_public_key(hex: &str) -> Option<[u8; 32]> {
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut public_key = [0_u8; 32];
+    for (byte, pair) in public_key.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(public_key)
+}
+
+/// Client side of the key exchange, becomes a `Session` once the server replies and proves it
+/// holds the secret half of `server_key`
+pub struct Handshake {
+    secret: StaticSecret,
+    public_key: PublicKey,
+    server_key: PublicKey,
+}
+
+impl Handshake {
+    pub fn new(server_key: [u8; 32]) -> Self {
+        // Used twice so it can't be an `EphemeralSecret`, it's still thrown away afterwards
+        let secret = StaticSecret::random_from_rng(OsRng);
+        let public_key = PublicKey::from(&secret);
+
+        Self {
+            secret,
+            public_key,
+            server_key: PublicKey::from(server_key),
+        }
+    }
+
+    pub fn hello(&self) -> Result<Vec<u8>, SecureError> {
+        let frame = Frame::ClientHello {
+            public_key: self.public_key.to_bytes(),
+        };
+
+        Ok(postcard::to_stdvec(&frame)?)
+    }
+
+    pub fn complete(self, bytes: &[u8]) -> Result<Session, SecureError> {
+        let Frame::ServerHello {
+            public_key,
+            confirmation,
+        } = postcard::from_bytes(bytes)?
+        else {
+            return Err(SecureError::UnexpectedFrame);
+        };
+
+        let server_public_key = PublicKey::from(public_key);
+        let ephemeral = self.secret.diffie_hellman(&server_public_key);
+        let pinned = self.secret.diffie_hellman(&self.server_key);
+        if !ephemeral.was_contributory() || !pinned.was_contributory() {
+            return Err(SecureError::WeakKey);
+        }
+
+        let (client_key, server_key) = derive_keys(
+            &[ephemeral.as_bytes().as_slice(), pinned.as_bytes()].concat(),
+            &self.public_key,
+            &server_public_key,
+            &self.server_key,
+        );
+
+        let session = Session::new(&client_key, &server_key);
+        session
+            .receive
+            .decrypt(&Session::nonce(0), confirmation.as_slice())
+            .map_err(|_| SecureError::Unauthenticated)?;
+
+        Ok(session)
+    }
+}
+
+fn derive_keys(
+    shared: &[u8],
+    client_public_key: &PublicKey,
+    server_public_key: &PublicKey,
+    server_static_key: &PublicKey,
+) -> ([u8; 32], [u8; 32]) {
+    let salt = [
+        client_public_key.as_bytes().as_slice(),
+        server_public_key.as_bytes(),
+        server_static_key.as_bytes(),
+    ]
+    .concat();
+    let hkdf = Hkdf::<Sha256>::new(Some(&salt), shared);
+
+    let mut client_key = [0_u8; 32];
+    let mut server_key = [0_u8; 32];
+    hkdf.expand(CLIENT_KEY_INFO, &mut client_key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    hkdf.expand(SERVER_KEY_INFO, &mut server_key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+
+    (client_key, server_key)
+}
+
+/// Sliding window over the last `REPLAY_WINDOW` counters seen from the peer
+#[derive(Default)]
+struct ReplayWindow {
+    highest: u64,
+    seen: u64,
+}
+
+impl ReplayWindow {
+    fn is_fresh(&self, counter: u64) -> bool {
+        if counter == 0 {
+            return false;
+        }
+
+        if counter > self.highest {
+            return true;
+        }
+
+        let age = self.highest - counter;
+        age < REPLAY_WINDOW && self.seen & (1 << age) == 0
+    }
+
+    fn mark(&mut self, counter: u64) {
+        if counter > self.highest {
+            let shift = counter - self.highest;
+            self.seen = if shift >= REPLAY_WINDOW {
+                0
+            } else {
+                self.seen << shift
+            };
+            self.seen |= 1;
+            self.highest = counter;
+        } else {
+            self.seen |= 1 << (self.highest - counter);
+        }
+    }
+}
+
+/// An established, encrypted channel to a single peer
+pub struct Session {
+    send: ChaCha20Poly1305,
+    receive: ChaCha20Poly1305,
+    send_counter: u64,
+    window: ReplayWindow,
+}
+
+impl Session {
+    fn new(send_key: &[u8; 32], receive_key: &[u8; 32]) -> Self {
+        Self {
+            send: ChaCha20Poly1305::new(Key::from_slice(send_key)),
+            receive: ChaCha20Poly1305::new(Key::from_slice(receive_key)),
+            send_counter: 0,
+            window: ReplayWindow::default(),
+        }
+    }
+
+    /// Server side of the key exchange, returns the session and the `ServerHello` to reply with
+    pub fn accept(
+        key: &ServerKey,
+        client_public_key: [u8; 32],
+    ) -> Result<(Self, Vec<u8>), SecureError> {
+        let secret = EphemeralSecret::random_from_rng(OsRng);
+        let server_public_key = PublicKey::from(&secret);
+        let client_public_key = PublicKey::from(client_public_key);
+
+        let ephemeral = secret.diffie_hellman(&client_public_key);
+        let pinned = key.secret.diffie_hellman(&client_public_key);
+        if !ephemeral.was_contributory() || !pinned.was_contributory() {
+            return Err(SecureError::WeakKey);
+        }
+
+        let (client_key, server_key) = derive_keys(
+            &[ephemeral.as_bytes().as_slice(), pinned.as_bytes()].concat(),
+            &client_public_key,
+            &server_public_key,
+            &key.public_key,
+        );
+        let session = Self::new(&server_key, &client_key);
+
+        // Counter 0 is never accepted for sealed frames, so this can't be replayed as one
+        let confirmation = session
+            .send
+            .encrypt(&Self::nonce(0), [].as_slice())
+            .map_err(|_| SecureError::Aead)?;
+        let reply = postcard::to_stdvec(&Frame::ServerHello {
+            public_key: server_public_key.to_bytes(),
+            confirmation,
+        })?;
+
+        Ok((session, reply))
+    }
+
+    fn nonce(counter: u64) -> Nonce {
+        let mut nonce = [0_u8; 12];
+        nonce[4..].copy_from_slice(&counter.to_le_bytes());
+        *Nonce::from_slice(&nonce)
+    }
+
+    pub fn seal(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, SecureError> {
+        self.send_counter += 1;
+        let ciphertext = self
+            .send
+            .encrypt(&Self::nonce(self.send_counter), plaintext)
+            .map_err(|_| SecureError::Aead)?;
+
+        Ok(postcard::to_stdvec(&Frame::Sealed {
+            counter: self.send_counter,
+            ciphertext,
+        })?)
+    }
+
+    pub fn open(&mut self, counter: u64, ciphertext: &[u8]) -> Result<Vec<u8>, SecureError> {
+        if !self.window.is_fresh(counter) {
+            return Err(SecureError::Replay);
+        }
+
+        let plaintext = self
+            .receive
+            .decrypt(&Self::nonce(counter), ciphertext)
+            .map_err(|_| SecureError::Aead)?;
+
+        // Only remember counters that authenticated, otherwise forged frames could burn them
+        self.window.mark(counter);
+
+        Ok(plaintext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handshake(pinned: [u8; 32], key: &ServerKey) -> Result<(Session, Session), SecureError> {
+        let handshake = Handshake::new(pinned);
+        let Frame::ClientHello { public_key } = postcard::from_bytes(&handshake.hello()?)? else {
+            return Err(SecureError::UnexpectedFrame);
+        };
+        let (server, reply) = Session::accept(key, public_key)?;
+        Ok((handshake.complete(&reply)?, server))
+    }
+
+    fn open(session: &mut Session, sealed: &[u8]) -> Result<Vec<u8>, SecureError> {
+        let Frame::Sealed {
+            counter,
+            ciphertext,
+        } = postcard::from_bytes(sealed)?
+        else {
+            return Err(SecureError::UnexpectedFrame);
+        };
+        session.open(counter, &ciphertext)
+    }
+
+    #[test]
+    fn handshake_with_pinned_key() {
+        let key = ServerKey::generate();
+        let (mut client, mut server) = handshake(key.public_key(), &key).unwrap();
+
+        let sealed = client.seal(b"login").unwrap();
+        assert_eq!(open(&mut server, &sealed).unwrap(), b"login");
+        let sealed = server.seal(b"welcome").unwrap();
+        assert_eq!(open(&mut client, &sealed).unwrap(), b"welcome");
+    }
+
+    #[test]
+    fn impostor_is_rejected() {
+        let key = ServerKey::generate();
+        let impostor = ServerKey::generate();
+
+        assert!(matches!(
+            handshake(key.public_key(), &impostor),
+            Err(SecureError::Unauthenticated)
+        ));
+    }
+
+    #[test]
+    fn replayed_frame_is_rejected() {
+        let key = ServerKey::generate();
+        let (mut client, mut server) = handshake(key.public_key(), &key).unwrap();
+
+        let sealed = client.seal(b"move").unwrap();
+        open(&mut server, &sealed).unwrap();
+        assert!(matches!(
+            open(&mut server, &sealed),
+            Err(SecureError::Replay)
+        ));
+    }
+
+    #[test]
+    fn public_key_round_trips_through_hex() {
+        let key = ServerKey::generate();
+        let hex = encode_public_key(&key.public_key());
+
+        assert_eq!(parse_public_key(&hex), Some(key.public_key()));
+        assert_eq!(parse_public_key(&hex[1..]), None);
+        assert_eq!(parse_public_key(&"zz".repeat(32)), None);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::item::{Item, ItemStack};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Gatherable things placed in the world by the arbiter
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeKind {
+    Tree,
+    CopperOre,
+    Firefly,
+}
+
+impl NodeKind {
+    /// Stable name used to store the kind in the database
+    pub const fn id(self) -> &'static str {
+        match self {
+            Self::Tree => "tree",
+            Self::CopperOre => "copper_ore",
+            Self::Firefly => "firefly",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        [Self::Tree, Self::CopperOre, Self::Firefly]
+            .into_iter()
+            .find(|kind| kind.id() == id)
+    }
+
+    pub fn yields(self) -> ItemStack {
+        let item = match self {
+            Self::Tree => "wood",
+            Self::CopperOre => "copper_ore",
+            Self::Firefly => "fireglow",
+        };
+
+        ItemStack {
+            item: Item::new(item),
+            amount: 1,
+        }
+    }
+
+    /// How far from the node's position a player can be and still gather it, fireflies wander
+    /// away from where they spawned so get a lot more leeway
+    pub const fn reach(self) -> f32 {
+        match self {
+            Self::Tree | Self::CopperOre => 60.0,
+            Self::Firefly => 250.0,
+        }
+    }
+
+    /// How long the node stays depleted after being gathered
+    pub const fn respawn_time(self) -> Duration {
+        match self {
+            Self::Tree => Duration::from_secs(120),
+            Self::CopperOre => Duration::from_secs(300),
+            Self::Firefly => Duration::from_secs(60),
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use crate::item::ItemStack;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{fs, path::Path, sync::OnceLock};
+
+pub const RECIPE_BOOK_PATH: &str = "assets/data/recipes.ron";
+
+static RECIPE_BOOK: OnceLock<RecipeBook> = OnceLock::new();
+
+#[derive(thiserror::Error, Debug)]
+pub enum RecipeBookError {
+    #[error("Failed to read recipe book")]
+    IOError(#[from] std::io::Error),
+    #[error("Failed to parse recipe book")]
+    RonError(#[from] ron::error::SpannedError),
+    #[error("Recipe {0} is defined more than once")]
+    Duplicate(String),
+    #[error("Recipe {0} uses unknown item {1}")]
+    UnknownItem(String, String),
+    #[error("Recipe {0} has an invalid craft time of {1}")]
+    InvalidCraftTime(String, f32),
+    #[error("Recipe book has already been loaded")]
+    AlreadyLoaded,
+}
+
+/// What a player needs to be standing at to craft a recipe
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Station {
+    CraftingBench,
+    Furnace,
+}
+
+impl Station {
+    /// How far from the station a player can be and still use it, a little more than the client
+    /// lets them so positions that are slightly behind aren't refused
+    pub const fn reach(self) -> f32 {
+        match self {
+            Self::CraftingBench | Self::Furnace => 60.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Recipe {
+    pub id: String,
+    pub station: Station,
+    pub ingredients: Vec<ItemStack>,
+    pub outputs: Vec<ItemStack>,
+    /// Seconds
+    pub craft_time: f32,
+}
+
+impl Recipe {
+    pub fn has_ingredients(&self, inventory: &[ItemStack]) -> bool {
+        self.ingredients.iter().all(|ingredient| {
+            ingredient.amount
+                <= inventory
+                    .iter()
+                    .find(|stack| stack.item == ingredient.item)
+                    .map_or(0, |stack| stack.amount)
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct RecipeBookFile {
+    recipes: Vec<Recipe>,
+}
+
+pub struct RecipeBook {
+    version: u64,
+    recipes: Vec<Recipe>,
+}
+
+impl RecipeBook {
+    /// Parses the recipe book, the item registry must already be loaded so items can be checked
+    pub fn parse(source: &str) -> Result<Self, RecipeBookError> {
+        let file: RecipeBookFile = ron::from_str(source)?;
+
+        for (i, recipe) in file.recipes.iter().enumerate() {
+            if file.recipes[..i].iter().any(|other| other.id == recipe.id) {
+                return Err(RecipeBookError::Duplicate(recipe.id.clone()));
+            }
+
+            // Turned into a `Duration` when crafting, which panics on these
+            if !recipe.craft_time.is_finite() || recipe.craft_time < 0.0 {
+                return Err(RecipeBookError::InvalidCraftTime(
+                    recipe.id.clone(),
+                    recipe.craft_time,
+                ));
+            }
+
+            let unknown = recipe
+                .ingredients
+                .iter()
+                .chain(recipe.outputs.iter())
+                .find(|stack| stack.item.def().is_none());
+            if let Some(stack) = unknown {
+                return Err(RecipeBookError::UnknownItem(
+                    recipe.id.clone(),
+                    stack.item.id().to_owned(),
+                ));
+            }
+        }
+
+        let digest = Sha256::digest(source.as_bytes());
+        let mut version = [0_u8; 8];
+        version.copy_from_slice(&digest[..8]);
+
+        Ok(Self {
+            version: u64::from_le_bytes(version),
+            recipes: file.recipes,
+        })
+    }
+
+    pub const fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Recipe> {
+        self.recipes.iter().find(|recipe| recipe.id == id)
+    }
+
+    pub fn for_station(&self, station: Station) -> impl Iterator<Item = &Recipe> {
+        self.recipes
+            .iter()
+            .filter(move |recipe| recipe.station == station)
+    }
+}
+
+/// Loads the recipe book into the global, call once at startup after the item registry
+pub fn load_recipe_book(path: impl AsRef<Path>) -> Result<&'static RecipeBook, RecipeBookError> {
+    let book = RecipeBook::parse(&fs::read_to_string(path)?)?;
+    RECIPE_BOOK
+        .set(book)
+        .map_err(|_| RecipeBookError::AlreadyLoaded)?;
+
+    Ok(get_recipe_book())
+}
+
+pub fn get_recipe_book() -> &'static RecipeBook {
+    RECIPE_BOOK
+        .get()
+        .expect("Recipe book used before it was loaded")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A book with one recipe that has no items, so the item registry isn't needed
+    fn book(craft_time: &str) -> Result<RecipeBook, RecipeBookError> {
+        RecipeBook::parse(&format!(
+            "(recipes: [(id: \"wait\", station: CraftingBench, ingredients: [], outputs: [], \
+             craft_time: {craft_time})])"
+        ))
+    }
+
+    #[test]
+    fn rejects_invalid_craft_times() {
+        assert_eq!(book("2.5").unwrap().get("wait").unwrap().craft_time, 2.5);
+        assert!(book("0.0").is_ok());
+
+        for craft_time in ["-1.0", "NaN", "inf", "-inf"] {
+            assert!(
+                matches!(
+                    book(craft_time),
+                    Err(RecipeBookError::InvalidCraftTime(id, _)) if id == "wait"
+                ),
+                "{craft_time} was accepted"
+            );
+        }
+    }
+}
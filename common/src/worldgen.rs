@@ -0,0 +1,375 @@
+use crate::{node::NodeKind, recipe::Station};
+use glam::{Vec2, Vec3, Vec3Swizzles};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+// Generation only uses arithmetic and sqrt, which IEEE 754 requires to be correctly rounded, so
+// the client and arbiter come up with exactly the same layout. Trig functions aren't, so avoid
+// them here or layouts could drift apart between platforms.
+
+/// Nodes are placed within this distance of the origin on both axes
+pub const WORLD_RADIUS: f32 = 400.0;
+/// Minimum distance between spawn points, the Poisson disk radius
+const SPACING: f32 = 60.0;
+/// How many times Bridson's algorithm tries to place a point around each active point
+const ATTEMPTS: u32 = 30;
+/// Nothing spawns this close to the player spawn or a station
+const CLEARING: f32 = 50.0;
+const STATION_DISTANCE: f32 = 100.0;
+const STATION_GAP: f32 = 70.0;
+/// Size of a cell in the biome noise lattice
+const BIOME_SCALE: f32 = 250.0;
+const SWARM_SIZE: (u32, u32) = (2, 4);
+const SWARM_SPREAD: f32 = 30.0;
+const FIREFLY_HEIGHT: f32 = 50.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Biome {
+    Forest,
+    Meadow,
+    Rocky,
+}
+
+/// Chance that a spawn point in a biome holds each kind of feature, the rest stay empty
+struct Density {
+    tree: f32,
+    ore: f32,
+    swarm: f32,
+}
+
+impl Biome {
+    pub fn at(seed: u32, position: Vec2) -> Self {
+        let noise = value_noise(seed, position / BIOME_SCALE);
+        if noise < 0.35 {
+            Self::Rocky
+        } else if noise < 0.6 {
+            Self::Meadow
+        } else {
+            Self::Forest
+        }
+    }
+
+    const fn density(self) -> Density {
+        match self {
+            Self::Forest => Density {
+                tree: 0.6,
+                ore: 0.05,
+                swarm: 0.1,
+            },
+            Self::Meadow => Density {
+                tree: 0.15,
+                ore: 0.05,
+                swarm: 0.2,
+            },
+            Self::Rocky => Density {
+                tree: 0.05,
+                ore: 0.4,
+                swarm: 0.02,
+            },
+        }
+    }
+}
+
+/// A resource node, its ID is its index in `Layout::nodes`
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodePlacement {
+    pub kind: NodeKind,
+    pub position: Vec3,
+    /// Rotation around the Y axis in radians
+    pub yaw: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StationPlacement {
+    pub station: Station,
+    pub position: Vec3,
+}
+
+/// Static layout of the world, generated from the seed the arbiter sends on login
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layout {
+    pub seed: u32,
+    pub nodes: Vec<NodePlacement>,
+    pub stations: Vec<StationPlacement>,
+}
+
+impl Layout {
+    pub fn node(&self, id: u32) -> Option<&NodePlacement> {
+        self.nodes.get(usize::try_from(id).ok()?)
+    }
+
+    /// Nodes paired with their IDs
+    pub fn nodes(&self) -> impl Iterator<Item = (u32, &NodePlacement)> {
+        (0..).zip(self.nodes.iter())
+    }
+}
+
+pub fn generate(seed: u32) -> Layout {
+    let mut rng = ChaCha8Rng::seed_from_u64(u64::from(seed));
+
+    let stations = place_stations(&mut rng);
+    let mut nodes = Vec::new();
+
+    for point in poisson_disk(&mut rng) {
+        let cleared = point.length() < CLEARING
+            || stations
+                .iter()
+                .any(|station| (station.position.xz() - point).length() < CLEARING);
+        if cleared {
+            continue;
+        }
+
+        let density = Biome::at(seed, point).density();
+        let roll = rng.gen::<f32>();
+        let yaw = rng.gen_range(-std::f32::consts::PI..std::f32::consts::PI);
+
+        if roll < density.tree {
+            nodes.push(NodePlacement {
+                kind: NodeKind::Tree,
+                position: Vec3::new(point.x, 0.0, point.y),
+                yaw,
+            });
+        } else if roll < density.tree + density.ore {
+            nodes.push(NodePlacement {
+                kind: NodeKind::CopperOre,
+                position: Vec3::new(point.x, 0.0, point.y),
+                yaw,
+            });
+        } else if roll < density.tree + density.ore + density.swarm {
+            for _ in 0..rng.gen_range(SWARM_SIZE.0..=SWARM_SIZE.1) {
+                let offset = random_direction(&mut rng) * rng.gen_range(0.0..SWARM_SPREAD);
+                nodes.push(NodePlacement {
+                    kind: NodeKind::Firefly,
+                    position: Vec3::new(point.x + offset.x, FIREFLY_HEIGHT, point.y + offset.y),
+                    yaw,
+                });
+            }
+        }
+    }
+
+    Layout {
+        seed,
+        nodes,
+        stations,
+    }
+}
+
+/// Stations sit next to each other a short walk from the player spawn
+fn place_stations(rng: &mut ChaCha8Rng) -> Vec<StationPlacement> {
+    let direction = random_direction(rng);
+    let bench = direction * STATION_DISTANCE;
+    let furnace = bench + direction.perp() * STATION_GAP;
+
+    vec![
+        StationPlacement {
+            station: Station::CraftingBench,
+            position: Vec3::new(bench.x, 0.0, bench.y),
+        },
+        StationPlacement {
+            station: Station::Furnace,
+            position: Vec3::new(furnace.x, 0.0, furnace.y),
+        },
+    ]
+}
+
+/// Rejection sampled so it doesn't need trig
+fn random_direction(rng: &mut ChaCha8Rng) -> Vec2 {
+    loop {
+        let candidate = Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+        let length_squared = candidate.length_squared();
+        if length_squared > 0.01 && length_squared <= 1.0 {
+            return candidate / length_squared.sqrt();
+        }
+    }
+}
+
+/// Bridson's algorithm over the square `WORLD_RADIUS` either side of the origin
+fn poisson_disk(rng: &mut ChaCha8Rng) -> Vec<Vec2> {
+    let cell_size = SPACING / std::f32::consts::SQRT_2;
+    let cells = (WORLD_RADIUS * 2.0 / cell_size).ceil() as usize;
+    let cell = |point: Vec2| {
+        let x = ((point.x + WORLD_RADIUS) / cell_size) as usize;
+        let y = ((point.y + WORLD_RADIUS) / cell_size) as usize;
+        (x.min(cells - 1), y.min(cells - 1))
+    };
+
+    let mut grid: Vec<Option<usize>> = vec![None; cells * cells];
+    let mut points = Vec::new();
+    let mut active = Vec::new();
+
+    let first = Vec2::new(
+        rng.gen_range(-WORLD_RADIUS..WORLD_RADIUS),
+        rng.gen_range(-WORLD_RADIUS..WORLD_RADIUS),
+    );
+    let (x, y) = cell(first);
+    grid[y * cells + x] = Some(0);
+    points.push(first);
+    active.push(0);
+
+    while !active.is_empty() {
+        // Sampled as a u32 since usize ranges consume the RNG differently on 32 bit platforms
+        let index = rng.gen_range(0..active.len() as u32) as usize;
+        let origin = points[active[index]];
+
+        let mut placed = false;
+        for _ in 0..ATTEMPTS {
+            // Uniform in the square around the origin, kept if it lands in the annulus between
+            // one and two spacings away
+            let offset = Vec2::new(
+                rng.gen_range(-2.0 * SPACING..2.0 * SPACING),
+                rng.gen_range(-2.0 * SPACING..2.0 * SPACING),
+            );
+            let distance_squared = offset.length_squared();
+            if !(SPACING * SPACING..=4.0 * SPACING * SPACING).contains(&distance_squared) {
+                continue;
+            }
+
+            let candidate = origin + offset;
+            if candidate.x.abs() >= WORLD_RADIUS || candidate.y.abs() >= WORLD_RADIUS {
+                continue;
+            }
+
+            let (x, y) = cell(candidate);
+            let crowded = (y.saturating_sub(2)..=(y + 2).min(cells - 1)).any(|y| {
+                (x.saturating_sub(2)..=(x + 2).min(cells - 1)).any(|x| {
+                    grid[y * cells + x].is_some_and(|other| {
+                        (points[other] - candidate).length_squared() < SPACING * SPACING
+                    })
+                })
+            });
+            if crowded {
+                continue;
+            }
+
+            grid[y * cells + x] = Some(points.len());
+            active.push(points.len());
+            points.push(candidate);
+            placed = true;
+            break;
+        }
+
+        if !placed {
+            active.swap_remove(index);
+        }
+    }
+
+    points
+}
+
+fn hash(seed: u32, x: i32, y: i32) -> f32 {
+    let mut h = u64::from(seed)
+        ^ u64::from(x as u32).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ u64::from(y as u32).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xC4CE_B9FE_1A85_EC53);
+    h ^= h >> 33;
+
+    // Top 24 bits fit exactly in an f32 mantissa
+    (h >> 40) as f32 / (1 << 24) as f32
+}
+
+/// Smoothly interpolated random values on an integer lattice, between 0 and 1
+fn value_noise(seed: u32, position: Vec2) -> f32 {
+    let floor = position.floor();
+    let fraction = position - floor;
+    let smooth = fraction * fraction * (Vec2::splat(3.0) - 2.0 * fraction);
+    let (x, y) = (floor.x as i32, floor.y as i32);
+
+    let top = hash(seed, x, y) + (hash(seed, x + 1, y) - hash(seed, x, y)) * smooth.x;
+    let bottom =
+        hash(seed, x, y + 1) + (hash(seed, x + 1, y + 1) - hash(seed, x, y + 1)) * smooth.x;
+    top + (bottom - top) * smooth.y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEEDS: [u32; 4] = [0, 1, 42, u32::MAX];
+
+    #[test]
+    fn same_seed_same_layout() {
+        for seed in SEEDS {
+            assert_eq!(generate(seed), generate(seed));
+        }
+    }
+
+    #[test]
+    fn different_seeds_different_layouts() {
+        for (i, a) in SEEDS.iter().enumerate() {
+            for b in &SEEDS[i + 1..] {
+                let (a, b) = (generate(*a), generate(*b));
+                assert_ne!(a.nodes, b.nodes);
+                assert_ne!(a.stations, b.stations);
+            }
+        }
+    }
+
+    #[test]
+    fn poisson_points_are_spaced_and_in_bounds() {
+        for seed in SEEDS {
+            let points = poisson_disk(&mut ChaCha8Rng::seed_from_u64(u64::from(seed)));
+            assert!(points.len() > 10);
+
+            for (i, a) in points.iter().enumerate() {
+                assert!(a.x.abs() <= WORLD_RADIUS && a.y.abs() <= WORLD_RADIUS);
+                for b in &points[i + 1..] {
+                    assert!((*a - *b).length() >= SPACING);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nodes_are_in_bounds_and_clear_of_stations() {
+        for seed in SEEDS {
+            let layout = generate(seed);
+            let clearings = layout
+                .stations
+                .iter()
+                .map(|station| station.position)
+                .chain([Vec3::ZERO])
+                .collect::<Vec<Vec3>>();
+
+            for node in &layout.nodes {
+                let bound = match node.kind {
+                    NodeKind::Firefly => WORLD_RADIUS + SWARM_SPREAD,
+                    NodeKind::Tree | NodeKind::CopperOre => WORLD_RADIUS,
+                };
+                assert!(node.position.x.abs() <= bound && node.position.z.abs() <= bound);
+
+                if node.kind != NodeKind::Firefly {
+                    for clearing in &clearings {
+                        assert!((node.position - *clearing).length() >= CLEARING);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stations_are_placed_near_spawn() {
+        for seed in SEEDS {
+            let layout = generate(seed);
+            let station = |station: Station| {
+                layout
+                    .stations
+                    .iter()
+                    .filter(|placement| placement.station == station)
+                    .map(|placement| placement.position)
+                    .collect::<Vec<Vec3>>()
+            };
+            let (benches, furnaces) = (station(Station::CraftingBench), station(Station::Furnace));
+            assert_eq!((benches.len(), furnaces.len()), (1, 1));
+
+            assert!((benches[0].length() - STATION_DISTANCE).abs() < 0.01);
+            assert!(((furnaces[0] - benches[0]).length() - STATION_GAP).abs() < 0.01);
+            for position in benches.iter().chain(&furnaces) {
+                assert_eq!(position.y, 0.0);
+                assert!(position.x.abs() < WORLD_RADIUS && position.z.abs() < WORLD_RADIUS);
+            }
+        }
+    }
+}
@@ -0,0 +1,34 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Run with `cargo +nightly fuzz run load` from the gltf directory, the meshes in assets/meshes
+// make a good starting corpus. There's no base path, so only embedded and data URI buffers load.
+fuzz_target!(|bytes: &[u8]| {
+    let Ok(document) = gltf::Document::from_slice(bytes, None) else {
+        return;
+    };
+
+    // Loading only parses the JSON, accessors are where the buffer actually gets read
+    for mesh in &document.gltf.meshes {
+        for primitive in &mesh.primitives {
+            let _ = primitive.get_indices_data(&document);
+            if let Ok(positions) = primitive.get_attribute(&document, "POSITION") {
+                let _ = positions.get_vec3s(&document);
+            }
+            if let Ok(tex_coords) = primitive.get_attribute(&document, "TEXCOORD_0") {
+                let _ = tex_coords.get_vec2s(&document);
+            }
+            let _ = primitive.get_joints_data(&document);
+            let _ = primitive.get_weights_data(&document);
+        }
+    }
+    for accessor in &document.gltf.accessors {
+        let _ = accessor.get_scalars(&document);
+        let _ = accessor.get_vec4s(&document);
+        let _ = accessor.get_indices(&document);
+    }
+    for skin in &document.gltf.skins {
+        let _ = skin.get_inverse_bind_matrices(&document);
+    }
+});
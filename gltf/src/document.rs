@@ -0,0 +1,375 @@
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+
+use crate::{get, Gltf, GltfError};
+
+const GLB_MAGIC: u32 = 0x46546C67;
+const CHUNK_JSON: u32 = 0x4E4F534A;
+const CHUNK_BIN: u32 = 0x004E4942;
+
+/// A parsed glTF with every buffer loaded, from either a `.glb` or a `.gltf` and its files
+pub struct Document {
+    pub gltf: Gltf,
+    pub buffers: Vec<Vec<u8>>,
+    /// Directory relative URIs are resolved against, `None` when loaded from memory
+    base: Option<PathBuf>,
+}
+
+fn get_u32(bytes: &[u8], offset: usize) -> Result<u32, GltfError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .ok_or(GltfError::Truncated(offset))
+}
+
+/// Returns the chunk's type and contents along with where the next chunk starts
+fn get_chunk(bytes: &[u8], offset: usize) -> Result<(u32, &[u8], usize), GltfError> {
+    let length = get_u32(bytes, offset)? as usize;
+    let kind = get_u32(bytes, offset + 4)?;
+    let start = offset + 8;
+    let data = bytes
+        .get(start..start.saturating_add(length))
+        .ok_or(GltfError::Truncated(start))?;
+
+    Ok((kind, data, start + length))
+}
+
+/// Contents of a `data:` URI or the file a relative URI points to
+fn resolve(uri: &str, base: Option<&Path>) -> Result<Vec<u8>, GltfError> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, encoded) = data
+            .split_once(";base64,")
+            .ok_or_else(|| GltfError::InvalidDataUri(uri.chars().take(32).collect()))?;
+        return base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|_| GltfError::InvalidDataUri(uri.chars().take(32).collect()));
+    }
+
+    let base = base.ok_or_else(|| GltfError::ExternalUri(uri.to_owned()))?;
+    let path = percent_encoding::percent_decode_str(uri)
+        .decode_utf8()
+        .map_err(|_| GltfError::ExternalUri(uri.to_owned()))?;
+    Ok(std::fs::read(base.join(path.as_ref()))?)
+}
+
+impl Document {
+    /// Loads a `.glb` or `.gltf`, whichever the file turns out to be, along with any files it
+    /// refers to
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, GltfError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        Self::from_slice(&bytes, path.parent())
+    }
+
+    /// External URIs are only allowed when there's a `base` to find them in
+    pub fn from_slice(bytes: &[u8], base: Option<&Path>) -> Result<Self, GltfError> {
+        if get_u32(bytes, 0).is_ok_and(|magic| magic == GLB_MAGIC) {
+            Self::from_glb(bytes, base)
+        } else {
+            Self::from_gltf(bytes, base)
+        }
+    }
+
+    pub fn from_glb(bytes: &[u8], base: Option<&Path>) -> Result<Self, GltfError> {
+        let magic = get_u32(bytes, 0)?;
+        if magic != GLB_MAGIC {
+            return Err(GltfError::BadMagic(magic));
+        }
+
+        let version = get_u32(bytes, 4)?;
+        if version != 2 {
+            return Err(GltfError::UnsupportedVersion(version.to_string()));
+        }
+
+        let length = get_u32(bytes, 8)? as usize;
+        if length != bytes.len() {
+            return Err(GltfError::LengthMismatch {
+                declared: length,
+                actual: bytes.len(),
+            });
+        }
+
+        let (kind, gltf_bytes, mut offset) = get_chunk(bytes, 12)?;
+        if kind != CHUNK_JSON {
+            return Err(GltfError::BadChunkType(kind));
+        }
+        let gltf = Gltf::load(gltf_bytes)?;
+
+        // Chunks of unknown types are skipped as the spec asks
+        let mut bin = None;
+        while offset < bytes.len() {
+            let (kind, data, next) = get_chunk(bytes, offset)?;
+            if kind == CHUNK_BIN && bin.is_none() {
+                bin = Some(data);
+            }
+            offset = next;
+        }
+
+        Self::new(gltf, bin, base)
+    }
+
+    pub fn from_gltf(bytes: &[u8], base: Option<&Path>) -> Result<Self, GltfError> {
+        Self::new(Gltf::load(bytes)?, None, base)
+    }
+
+    /// Only the first buffer can be the GLB's binary chunk, it's the one without a URI
+    fn new(gltf: Gltf, bin: Option<&[u8]>, base: Option<&Path>) -> Result<Self, GltfError> {
+        let buffers = gltf
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(index, buffer)| {
+                let data = match (&buffer.uri, bin) {
+                    (Some(uri), _) => resolve(uri, base)?,
+                    (None, Some(bin)) if index == 0 => bin.to_vec(),
+                    (None, _) => return Err(GltfError::MissingBufferData(index)),
+                };
+
+                if data.len() < buffer.byte_length {
+                    return Err(GltfError::BufferTooShort {
+                        buffer: index,
+                        expected: buffer.byte_length,
+                        found: data.len(),
+                    });
+                }
+                Ok(data)
+            })
+            .collect::<Result<Vec<Vec<u8>>, GltfError>>()?;
+
+        Ok(Self {
+            gltf,
+            buffers,
+            base: base.map(Path::to_path_buf),
+        })
+    }
+
+    /// The bytes a buffer view covers
+    pub fn get_buffer_view(&self, index: usize) -> Result<&[u8], GltfError> {
+        let view = get(&self.gltf.buffer_views, "buffer view", index)?;
+        let buffer = get(&self.buffers, "buffer", view.buffer)?;
+
+        view.byte_offset
+            .checked_add(view.byte_length)
+            .and_then(|end| buffer.get(view.byte_offset..end))
+            .ok_or(GltfError::OutOfRange { buffer_view: index })
+    }
+
+    /// Encoded contents of an image, from a buffer view, a `data:` URI or a file
+    pub fn get_image_data(&self, index: usize) -> Result<Vec<u8>, GltfError> {
+        let image = get(&self.gltf.images, "image", index)?;
+        match (&image.buffer_view, &image.uri) {
+            (Some(view), _) => Ok(self.get_buffer_view(*view)?.to_vec()),
+            (None, Some(uri)) => resolve(uri, self.base.as_deref()),
+            (None, None) => Err(GltfError::MissingImageData(index)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"{
+        "asset": { "version": "2.0" },
+        "buffers": [{ "byteLength": 8 }],
+        "bufferViews": [
+            { "buffer": 0, "byteLength": 8 },
+            { "buffer": 0, "byteOffset": 4, "byteLength": 8 },
+            { "buffer": 3, "byteLength": 4 }
+        ],
+        "accessors": [{ "bufferView": 0, "componentType": 5126, "count": 2, "type": "SCALAR" }],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 7 }, "indices": 9 }] }],
+        "skins": [{ "inverseBindMatrices": 4, "joints": [0] }]
+    }"#;
+
+    fn chunk(kind: u32, data: &[u8]) -> Vec<u8> {
+        let mut chunk = Vec::new();
+        chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(&kind.to_le_bytes());
+        chunk.extend_from_slice(data);
+        chunk
+    }
+
+    /// Header followed by the chunks, the declared length is always right
+    fn glb(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body = chunks.concat();
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&GLB_MAGIC.to_le_bytes());
+        bytes.extend_from_slice(&2_u32.to_le_bytes());
+        bytes.extend_from_slice(&(12 + body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    fn valid() -> Vec<u8> {
+        glb(&[
+            chunk(CHUNK_JSON, JSON.as_bytes()),
+            chunk(CHUNK_BIN, &[0; 8]),
+        ])
+    }
+
+    #[test]
+    fn loads_a_valid_glb() {
+        let document = Document::from_slice(&valid(), None).unwrap();
+        assert_eq!(document.buffers, vec![vec![0; 8]]);
+        assert_eq!(document.get_buffer_view(0).unwrap(), &[0; 8]);
+    }
+
+    #[test]
+    fn truncated_headers_are_errors() {
+        let bytes = valid();
+        for length in 0..12 {
+            let error = Document::from_glb(&bytes[..length], None).err();
+            assert!(
+                matches!(error, Some(GltfError::Truncated(offset)) if offset == length / 4 * 4),
+                "{length} bytes gave {error:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn truncated_chunk_headers_are_errors() {
+        // The file length matches but the JSON chunk header is cut off half way
+        let mut bytes = glb(&[vec![0; 4]]);
+        assert!(matches!(
+            Document::from_glb(&bytes, None),
+            Err(GltfError::Truncated(16))
+        ));
+
+        // Same for the binary chunk's
+        bytes = glb(&[chunk(CHUNK_JSON, JSON.as_bytes()), vec![8, 0]]);
+        assert!(matches!(
+            Document::from_glb(&bytes, None),
+            Err(GltfError::Truncated(_))
+        ));
+    }
+
+    #[test]
+    fn bad_chunk_lengths_are_errors() {
+        for length in [JSON.len() as u32 + 1, u32::MAX] {
+            let mut json = chunk(CHUNK_JSON, JSON.as_bytes());
+            json[..4].copy_from_slice(&length.to_le_bytes());
+            assert!(matches!(
+                Document::from_glb(&glb(&[json]), None),
+                Err(GltfError::Truncated(20))
+            ));
+        }
+
+        let mut bin = chunk(CHUNK_BIN, &[0; 8]);
+        bin[..4].copy_from_slice(&9_u32.to_le_bytes());
+        assert!(matches!(
+            Document::from_glb(&glb(&[chunk(CHUNK_JSON, JSON.as_bytes()), bin]), None),
+            Err(GltfError::Truncated(_))
+        ));
+
+        // A binary chunk shorter than the buffer it backs
+        assert!(matches!(
+            Document::from_glb(
+                &glb(&[
+                    chunk(CHUNK_JSON, JSON.as_bytes()),
+                    chunk(CHUNK_BIN, &[0; 4])
+                ]),
+                None
+            ),
+            Err(GltfError::BufferTooShort {
+                buffer: 0,
+                expected: 8,
+                found: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn bad_headers_are_errors() {
+        let mut bytes = valid();
+        bytes.push(0);
+        assert!(matches!(
+            Document::from_glb(&bytes, None),
+            Err(GltfError::LengthMismatch { declared, actual }) if actual == declared + 1
+        ));
+
+        let mut bytes = valid();
+        bytes[0] = b'x';
+        assert!(matches!(
+            Document::from_glb(&bytes, None),
+            Err(GltfError::BadMagic(_))
+        ));
+
+        let mut bytes = valid();
+        bytes[4] = 1;
+        assert!(matches!(
+            Document::from_glb(&bytes, None),
+            Err(GltfError::UnsupportedVersion(version)) if version == "1"
+        ));
+
+        let bytes = glb(&[chunk(CHUNK_BIN, &[0; 8])]);
+        assert!(matches!(
+            Document::from_glb(&bytes, None),
+            Err(GltfError::BadChunkType(CHUNK_BIN))
+        ));
+
+        let bytes = glb(&[chunk(CHUNK_JSON, JSON.as_bytes())]);
+        assert!(matches!(
+            Document::from_glb(&bytes, None),
+            Err(GltfError::MissingBufferData(0))
+        ));
+    }
+
+    #[test]
+    fn out_of_range_indices_are_errors() {
+        let document = Document::from_slice(&valid(), None).unwrap();
+        let primitive = &document.gltf.meshes[0].primitives[0];
+
+        assert!(matches!(
+            document.get_buffer_view(5),
+            Err(GltfError::MissingIndex {
+                kind: "buffer view",
+                index: 5
+            })
+        ));
+        assert!(matches!(
+            document.get_buffer_view(1),
+            Err(GltfError::OutOfRange { buffer_view: 1 })
+        ));
+        assert!(matches!(
+            document.get_buffer_view(2),
+            Err(GltfError::MissingIndex {
+                kind: "buffer",
+                index: 3
+            })
+        ));
+        assert!(matches!(
+            document.get_image_data(0),
+            Err(GltfError::MissingIndex {
+                kind: "image",
+                index: 0
+            })
+        ));
+        assert!(matches!(
+            primitive.get_attribute(&document, "POSITION"),
+            Err(GltfError::MissingIndex {
+                kind: "accessor",
+                index: 7
+            })
+        ));
+        assert!(matches!(
+            primitive.get_attribute(&document, "NORMAL"),
+            Err(GltfError::MissingAttribute(attribute)) if attribute == "NORMAL"
+        ));
+        assert!(matches!(
+            primitive.get_indices_data(&document),
+            Err(GltfError::MissingIndex {
+                kind: "accessor",
+                index: 9
+            })
+        ));
+        assert!(matches!(
+            document.gltf.skins[0].get_inverse_bind_matrices(&document),
+            Err(GltfError::MissingIndex {
+                kind: "accessor",
+                index: 4
+            })
+        ));
+    }
+}
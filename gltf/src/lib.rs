@@ -1,15 +1,82 @@
-#![feature(exact_size_is_empty)]
+mod document;
 
-use std::{
-    collections::HashMap,
-    fmt::Debug,
-    io::{Cursor, Read},
-};
+pub use document::Document;
 
+use std::{collections::HashMap, fmt::Debug};
+
+use glam::{Mat4, Vec2, Vec3, Vec4};
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
-#[derive(Deserialize_repr, Serialize_repr, Debug)]
+/// Extensions that change how a file must be read, none are implemented yet
+const SUPPORTED_EXTENSIONS: [&str; 0] = [];
+/// Accessors without a buffer view aren't bounded by a buffer, so they're bounded by this
+const MAX_COMPONENTS: usize = 1 << 24;
+
+#[derive(thiserror::Error, Debug)]
+pub enum GltfError {
+    #[error("IO error")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid JSON")]
+    Json(#[from] serde_json::Error),
+    #[error("Not a GLB file, magic was {0:#010x}")]
+    BadMagic(u32),
+    #[error("Unsupported glTF version {0}, only 2 is supported")]
+    UnsupportedVersion(String),
+    #[error("Header says the file is {declared} bytes but it's {actual}")]
+    LengthMismatch { declared: usize, actual: usize },
+    #[error("Truncated chunk or header at byte {0}")]
+    Truncated(usize),
+    #[error("Expected a JSON chunk first, found chunk type {0:#010x}")]
+    BadChunkType(u32),
+    #[error("No {kind} with index {index}")]
+    MissingIndex { kind: &'static str, index: usize },
+    #[error("Primitive has no {0} attribute")]
+    MissingAttribute(String),
+    #[error("Accessor reads past the end of buffer view {buffer_view}")]
+    OutOfRange { buffer_view: usize },
+    #[error("Sparse index {index} is past the end of an accessor with {count} elements")]
+    SparseIndexOutOfRange { index: usize, count: usize },
+    #[error("Accessor has {0} elements and no buffer view")]
+    TooManyElements(usize),
+    #[error("Invalid element type {0}")]
+    InvalidElementType(String),
+    #[error("Expected {expected} elements, found {found}")]
+    UnexpectedElementType {
+        expected: &'static str,
+        found: String,
+    },
+    #[error("Component type {component_type:?} can't be used for {usage}")]
+    UnsupportedComponentType {
+        component_type: ComponentType,
+        usage: &'static str,
+    },
+    #[error("Required extension {0} isn't supported")]
+    MissingExtension(String),
+    #[error("Invalid data URI {0}")]
+    InvalidDataUri(String),
+    #[error("Can't resolve {0} without knowing where the file was loaded from")]
+    ExternalUri(String),
+    #[error("Buffer {0} has no URI and there's no GLB binary chunk for it")]
+    MissingBufferData(usize),
+    #[error("Image {0} has neither a URI nor a buffer view")]
+    MissingImageData(usize),
+    #[error("Buffer {buffer} should be {expected} bytes but only {found} were loaded")]
+    BufferTooShort {
+        buffer: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+/// Looks up one of the document's arrays by index, `kind` names the array in the error
+pub fn get<'a, T>(items: &'a [T], kind: &'static str, index: usize) -> Result<&'a T, GltfError> {
+    items
+        .get(index)
+        .ok_or(GltfError::MissingIndex { kind, index })
+}
+
+#[derive(Deserialize_repr, Serialize_repr, Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u16)]
 pub enum ComponentType {
     I8 = 5120,
@@ -28,10 +95,44 @@ impl ComponentType {
             Self::U32 | Self::F32 => 4,
         }
     }
+
+    fn is_unsigned_integer(self) -> bool {
+        matches!(self, Self::U8 | Self::U16 | Self::U32)
+    }
+
+    /// `bytes` is exactly one component, normalized integers are mapped to 0..1 or -1..1
+    fn read_f32(self, bytes: &[u8], normalized: bool) -> f32 {
+        let value = match self {
+            Self::I8 => bytes[0] as i8 as f32,
+            Self::U8 => bytes[0] as f32,
+            Self::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+            Self::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+            Self::U32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+            Self::F32 => return f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        };
+
+        match (self, normalized) {
+            (Self::I8, true) => (value / 127.0).max(-1.0),
+            (Self::U8, true) => value / 255.0,
+            (Self::I16, true) => (value / 32767.0).max(-1.0),
+            (Self::U16, true) => value / 65535.0,
+            _ => value,
+        }
+    }
+
+    /// `bytes` is exactly one component, only meaningful for unsigned integers
+    fn read_u32(self, bytes: &[u8]) -> u32 {
+        match self {
+            Self::U8 => bytes[0] as u32,
+            Self::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as u32,
+            Self::U32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            _ => 0,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Accessor {
+pub struct SparseIndices {
     #[serde(rename = "bufferView")]
     pub buffer_view: usize,
     #[serde(default)]
@@ -39,6 +140,36 @@ pub struct Accessor {
     pub byte_offset: usize,
     #[serde(rename = "componentType")]
     pub component_type: ComponentType,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SparseValues {
+    #[serde(rename = "bufferView")]
+    pub buffer_view: usize,
+    #[serde(default)]
+    #[serde(rename = "byteOffset")]
+    pub byte_offset: usize,
+}
+
+/// Elements that replace the ones in the buffer view, or zeros if there isn't one
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Sparse {
+    pub count: usize,
+    pub indices: SparseIndices,
+    pub values: SparseValues,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Accessor {
+    /// Every element is zero when this isn't set, sparse values can still replace some
+    #[serde(default)]
+    #[serde(rename = "bufferView")]
+    pub buffer_view: Option<usize>,
+    #[serde(default)]
+    #[serde(rename = "byteOffset")]
+    pub byte_offset: usize,
+    #[serde(rename = "componentType")]
+    pub component_type: ComponentType,
     #[serde(default)]
     pub normalized: bool,
     pub count: usize,
@@ -48,32 +179,294 @@ pub struct Accessor {
     pub max: Option<Vec<f64>>,
     #[serde(default)]
     pub min: Option<Vec<f64>>,
+    #[serde(default)]
+    pub sparse: Option<Sparse>,
 }
 
 impl Accessor {
-    pub fn get_data(&self, glb: &Glb) -> Vec<u8> {
-        let buffer_view = glb.gltf.buffer_views.get(self.buffer_view).unwrap();
-        let buffer = glb.gltf.buffers.get(buffer_view.buffer).unwrap();
-
-        let offset = self.byte_offset + buffer_view.byte_offset;
-
-        let element_size = match self.element_type.as_str() {
-            "SCALAR" => 1,
-            "VEC2" => 2,
-            "VEC3" => 3,
-            "VEC4" | "MAT2" => 4,
-            "MAT3" => 9,
-            "MAT4" => 16,
-            _ => panic!("Invalid element type"),
+    /// Columns and rows in each element, vectors are a single column
+    fn shape(&self) -> Result<(usize, usize), GltfError> {
+        match self.element_type.as_str() {
+            "SCALAR" => Ok((1, 1)),
+            "VEC2" => Ok((1, 2)),
+            "VEC3" => Ok((1, 3)),
+            "VEC4" => Ok((1, 4)),
+            "MAT2" => Ok((2, 2)),
+            "MAT3" => Ok((3, 3)),
+            "MAT4" => Ok((4, 4)),
+            _ => Err(GltfError::InvalidElementType(self.element_type.clone())),
+        }
+    }
+
+    /// Where each component starts within an element and the size of the whole element, matrix
+    /// columns are padded to start on 4 byte boundaries
+    fn layout(&self) -> Result<(Vec<usize>, usize), GltfError> {
+        let (columns, rows) = self.shape()?;
+        let size = self.component_type.size_of();
+        let column_size = if columns > 1 {
+            (rows * size).next_multiple_of(4)
+        } else {
+            rows * size
         };
-        let size = self.component_type.size_of() * element_size * self.count;
 
-        glb.buffer[offset..(offset + size)].to_vec()
+        let offsets = (0..columns)
+            .flat_map(|column| (0..rows).map(move |row| column * column_size + row * size))
+            .collect();
+        Ok((offsets, columns * column_size))
+    }
+
+    /// Reads `count` elements `stride` bytes apart, converting each component with `read`
+    fn read_elements<T>(
+        &self,
+        view: &[u8],
+        view_index: usize,
+        byte_offset: usize,
+        count: usize,
+        stride: usize,
+        read: &impl Fn(&[u8]) -> T,
+    ) -> Result<Vec<T>, GltfError> {
+        let (offsets, element_size) = self.layout()?;
+        let size = self.component_type.size_of();
+
+        if count > 0 {
+            let end = (count - 1)
+                .checked_mul(stride)
+                .and_then(|last| last.checked_add(byte_offset))
+                .and_then(|last| last.checked_add(element_size));
+            if end.is_none_or(|end| end > view.len()) {
+                return Err(GltfError::OutOfRange {
+                    buffer_view: view_index,
+                });
+            }
+        }
+
+        Ok((0..count)
+            .flat_map(|element| {
+                let start = byte_offset + element * stride;
+                offsets
+                    .iter()
+                    .map(move |offset| read(&view[start + offset..start + offset + size]))
+            })
+            .collect())
+    }
+
+    /// Every component of every element in order, honouring byte stride and sparse substitution
+    fn read<T: Copy + Default>(
+        &self,
+        document: &Document,
+        read: impl Fn(&[u8]) -> T,
+    ) -> Result<Vec<T>, GltfError> {
+        let (offsets, element_size) = self.layout()?;
+        let components = offsets.len();
+
+        let mut values = match self.buffer_view {
+            Some(index) => {
+                let view = document.get_buffer_view(index)?;
+                let stride = match document.gltf.buffer_views[index].byte_stride {
+                    0 => element_size,
+                    stride => stride,
+                };
+                self.read_elements(view, index, self.byte_offset, self.count, stride, &read)?
+            }
+            None => {
+                if self.count.saturating_mul(components) > MAX_COMPONENTS {
+                    return Err(GltfError::TooManyElements(self.count));
+                }
+                vec![T::default(); self.count * components]
+            }
+        };
+
+        let Some(sparse) = &self.sparse else {
+            return Ok(values);
+        };
+
+        let index_type = sparse.indices.component_type;
+        if !index_type.is_unsigned_integer() {
+            return Err(GltfError::UnsupportedComponentType {
+                component_type: index_type,
+                usage: "sparse indices",
+            });
+        }
+        let index_view = document.get_buffer_view(sparse.indices.buffer_view)?;
+        let index_size = index_type.size_of();
+        let indices = sparse
+            .count
+            .checked_mul(index_size)
+            .and_then(|length| length.checked_add(sparse.indices.byte_offset))
+            .and_then(|end| index_view.get(sparse.indices.byte_offset..end))
+            .ok_or(GltfError::OutOfRange {
+                buffer_view: sparse.indices.buffer_view,
+            })?;
+
+        let substitutes = self.read_elements(
+            document.get_buffer_view(sparse.values.buffer_view)?,
+            sparse.values.buffer_view,
+            sparse.values.byte_offset,
+            sparse.count,
+            element_size,
+            &read,
+        )?;
+
+        for (index, substitute) in indices
+            .chunks_exact(index_size)
+            .zip(substitutes.chunks_exact(components))
+        {
+            let index = index_type.read_u32(index) as usize;
+            let Some(element) = values.get_mut(index * components..(index + 1) * components) else {
+                return Err(GltfError::SparseIndexOutOfRange {
+                    index,
+                    count: self.count,
+                });
+            };
+            element.copy_from_slice(substitute);
+        }
+
+        Ok(values)
+    }
+
+    fn expect_element_type(&self, expected: &'static str) -> Result<(), GltfError> {
+        if self.element_type == expected {
+            Ok(())
+        } else {
+            Err(GltfError::UnexpectedElementType {
+                expected,
+                found: self.element_type.clone(),
+            })
+        }
     }
+
+    fn read_f32(&self, document: &Document, expected: &'static str) -> Result<Vec<f32>, GltfError> {
+        self.expect_element_type(expected)?;
+        let component_type = self.component_type;
+        let normalized = self.normalized;
+        self.read(document, |bytes| component_type.read_f32(bytes, normalized))
+    }
+
+    fn read_u32(
+        &self,
+        document: &Document,
+        expected: &'static str,
+        usage: &'static str,
+    ) -> Result<Vec<u32>, GltfError> {
+        self.expect_element_type(expected)?;
+        let component_type = self.component_type;
+        if !component_type.is_unsigned_integer() {
+            return Err(GltfError::UnsupportedComponentType {
+                component_type,
+                usage,
+            });
+        }
+        self.read(document, |bytes| component_type.read_u32(bytes))
+    }
+
+    pub fn get_scalars(&self, document: &Document) -> Result<Vec<f32>, GltfError> {
+        self.read_f32(document, "SCALAR")
+    }
+
+    pub fn get_vec2s(&self, document: &Document) -> Result<Vec<Vec2>, GltfError> {
+        Ok(self
+            .read_f32(document, "VEC2")?
+            .chunks_exact(2)
+            .map(Vec2::from_slice)
+            .collect())
+    }
+
+    pub fn get_vec3s(&self, document: &Document) -> Result<Vec<Vec3>, GltfError> {
+        Ok(self
+            .read_f32(document, "VEC3")?
+            .chunks_exact(3)
+            .map(Vec3::from_slice)
+            .collect())
+    }
+
+    pub fn get_vec4s(&self, document: &Document) -> Result<Vec<Vec4>, GltfError> {
+        Ok(self
+            .read_f32(document, "VEC4")?
+            .chunks_exact(4)
+            .map(Vec4::from_slice)
+            .collect())
+    }
+
+    pub fn get_mat4s(&self, document: &Document) -> Result<Vec<Mat4>, GltfError> {
+        Ok(self
+            .read_f32(document, "MAT4")?
+            .chunks_exact(16)
+            .map(Mat4::from_cols_slice)
+            .collect())
+    }
+
+    pub fn get_indices(&self, document: &Document) -> Result<Vec<u32>, GltfError> {
+        self.read_u32(document, "SCALAR", "indices")
+    }
+
+    /// Joint indices are at most 16 bits, wider component types aren't allowed
+    pub fn get_joints(&self, document: &Document) -> Result<Vec<[u16; 4]>, GltfError> {
+        if self.component_type == ComponentType::U32 {
+            return Err(GltfError::UnsupportedComponentType {
+                component_type: self.component_type,
+                usage: "joints",
+            });
+        }
+
+        Ok(self
+            .read_u32(document, "VEC4", "joints")?
+            .chunks_exact(4)
+            .map(|joint| [joint[0], joint[1], joint[2], joint[3]].map(|joint| joint as u16))
+            .collect())
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    #[default]
+    #[serde(rename = "LINEAR")]
+    Linear,
+    #[serde(rename = "STEP")]
+    Step,
+    /// Each keyframe has an in-tangent, value and out-tangent
+    #[serde(rename = "CUBICSPLINE")]
+    CubicSpline,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AnimationSampler {
+    /// Keyframe times in seconds
+    pub input: usize,
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    /// Keyframe values
+    pub output: usize,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetPath {
+    Translation,
+    Rotation,
+    Scale,
+    Weights,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AnimationTarget {
+    #[serde(default)]
+    pub node: Option<usize>,
+    pub path: TargetPath,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AnimationChannel {
+    pub sampler: usize,
+    pub target: AnimationTarget,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Animation {}
+pub struct Animation {
+    pub channels: Vec<AnimationChannel>,
+    pub samplers: Vec<AnimationSampler>,
+    #[serde(default)]
+    pub name: Option<String>,
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Asset {
@@ -89,8 +482,9 @@ pub struct Asset {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Buffer {
+    /// Either a `data:` URI or a path relative to the file, GLB binary chunks don't have one
     #[serde(default)]
-    pub uri: String,
+    pub uri: Option<String>,
     #[serde(rename = "byteLength")]
     pub byte_length: usize,
 }
@@ -222,26 +616,34 @@ pub struct MeshPrimitive {
 }
 
 impl MeshPrimitive {
-    pub fn get_attribute_data(&self, glb: &Glb, attribute: &str) -> Option<Vec<u8>> {
-        glb.gltf
-            .accessors
-            .get(*self.attributes.get(attribute)?)
-            .map(|accessor| accessor.get_data(glb))
-    }
-
-    pub fn get_indices_data(&self, glb: &Glb) -> Option<Vec<u32>> {
-        glb.gltf.accessors.get(self.indices?).map(|accessor| {
-            let data = accessor.get_data(glb);
-            match accessor.component_type {
-                ComponentType::U16 => bytemuck::cast_slice::<u8, u16>(&data)
-                    .iter()
-                    .copied()
-                    .map(|short| short as u32)
-                    .collect(),
-                ComponentType::U32 => bytemuck::cast_slice::<u8, u32>(&data).to_vec(),
-                _ => panic!("Invalid index component type"),
-            }
-        })
+    pub fn get_attribute<'a>(
+        &self,
+        document: &'a Document,
+        attribute: &str,
+    ) -> Result<&'a Accessor, GltfError> {
+        let index = self
+            .attributes
+            .get(attribute)
+            .copied()
+            .ok_or_else(|| GltfError::MissingAttribute(attribute.to_owned()))?;
+        get(&document.gltf.accessors, "accessor", index)
+    }
+
+    pub fn get_indices_data(&self, document: &Document) -> Result<Vec<u32>, GltfError> {
+        let index = self
+            .indices
+            .ok_or_else(|| GltfError::MissingAttribute("indices".to_owned()))?;
+        get(&document.gltf.accessors, "accessor", index)?.get_indices(document)
+    }
+
+    pub fn get_joints_data(&self, document: &Document) -> Result<Vec<[u16; 4]>, GltfError> {
+        self.get_attribute(document, "JOINTS_0")?
+            .get_joints(document)
+    }
+
+    pub fn get_weights_data(&self, document: &Document) -> Result<Vec<Vec4>, GltfError> {
+        self.get_attribute(document, "WEIGHTS_0")?
+            .get_vec4s(document)
     }
 }
 
@@ -272,6 +674,8 @@ pub struct Node {
     pub translation: Option<[f32; 3]>,
     #[serde(default)]
     pub weights: Option<Vec<f64>>,
+    #[serde(default)]
+    pub name: Option<String>,
 }
 
 #[derive(Deserialize_repr, Serialize_repr, Debug)]
@@ -328,7 +732,28 @@ pub struct Scene {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Skin {}
+pub struct Skin {
+    /// Identity matrices are used when this isn't set
+    #[serde(default)]
+    #[serde(rename = "inverseBindMatrices")]
+    pub inverse_bind_matrices: Option<usize>,
+    #[serde(default)]
+    pub skeleton: Option<usize>,
+    /// Nodes used as joints, `JOINTS_0` indexes into this
+    pub joints: Vec<usize>,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+impl Skin {
+    pub fn get_inverse_bind_matrices(&self, document: &Document) -> Result<Vec<Mat4>, GltfError> {
+        let Some(index) = self.inverse_bind_matrices else {
+            return Ok(vec![Mat4::IDENTITY; self.joints.len()]);
+        };
+
+        get(&document.gltf.accessors, "accessor", index)?.get_mat4s(document)
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Texture {
@@ -379,60 +804,231 @@ pub struct Gltf {
 }
 
 impl Gltf {
-    fn load(bytes: &[u8]) -> serde_json::Result<Self> {
-        serde_json::from_slice(bytes)
+    fn load(bytes: &[u8]) -> Result<Self, GltfError> {
+        let gltf: Self = serde_json::from_slice(bytes)?;
+        if !gltf.asset.version.starts_with("2.") {
+            return Err(GltfError::UnsupportedVersion(gltf.asset.version));
+        }
+        if let Some(extension) = gltf
+            .extensions_required
+            .iter()
+            .find(|extension| !SUPPORTED_EXTENSIONS.contains(&extension.as_str()))
+        {
+            return Err(GltfError::MissingExtension(extension.clone()));
+        }
+
+        Ok(gltf)
     }
 }
 
-pub struct Glb {
-    pub gltf: Gltf,
-    pub buffer: Vec<u8>,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
 
-impl Glb {
-    fn get_u32(bytes: &mut impl Iterator<Item = u8>) -> u32 {
-        *bytemuck::from_bytes::<u32>(&bytes.take(4).collect::<Vec<u8>>())
+    /// A document with one buffer holding `data`, `views` and `accessors` are JSON arrays
+    fn document(data: &[u8], views: &str, accessors: &str) -> Document {
+        let json = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "buffers": [{{ "byteLength": {}, "uri": "data:application/octet-stream;base64,{}" }}],
+                "bufferViews": {views},
+                "accessors": {accessors}
+            }}"#,
+            data.len(),
+            base64::engine::general_purpose::STANDARD.encode(data)
+        );
+        Document::from_gltf(json.as_bytes(), None).unwrap()
     }
 
-    fn get(bytes: &mut impl Iterator<Item = u8>, length: usize) -> Vec<u8> {
-        bytes.take(length).collect()
+    fn floats(values: &[f32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
     }
 
-    pub fn load(bytes: &[u8]) -> serde_json::Result<Self> {
-        let mut bytes = bytes.iter().copied();
-
-        let magic = Self::get_u32(&mut bytes);
-        if magic != 0x46546C67 {
-            panic!("Malformed GLB");
+    #[test]
+    fn reads_interleaved_attributes() {
+        // Position then a u16 pair padded to four bytes, for two vertices
+        let mut data = Vec::new();
+        for (position, uv) in [
+            ([1.0, 2.0, 3.0], [0_u16, 65535]),
+            ([4.0, 5.0, 6.0], [65535, 0]),
+        ] {
+            data.extend(floats(&position));
+            data.extend(uv.iter().flat_map(|value| value.to_le_bytes()));
         }
+        let document = document(
+            &data,
+            r#"[{ "buffer": 0, "byteLength": 32, "byteStride": 16 }]"#,
+            r#"[
+                { "bufferView": 0, "componentType": 5126, "count": 2, "type": "VEC3" },
+                { "bufferView": 0, "byteOffset": 12, "componentType": 5123, "normalized": true, "count": 2, "type": "VEC2" }
+            ]"#,
+        );
+        let accessors = &document.gltf.accessors;
 
-        let version = Self::get_u32(&mut bytes);
-        if version != 2 {
-            panic!("Aetheria only supports glTF 2.0");
-        }
+        assert_eq!(
+            accessors[0].get_vec3s(&document).unwrap(),
+            vec![Vec3::new(1.0, 2.0, 3.0), Vec3::new(4.0, 5.0, 6.0)]
+        );
+        assert_eq!(
+            accessors[1].get_vec2s(&document).unwrap(),
+            vec![Vec2::new(0.0, 1.0), Vec2::new(1.0, 0.0)]
+        );
+    }
 
-        let _length = Self::get_u32(&mut bytes);
+    #[test]
+    fn normalizes_integer_components() {
+        let document = document(
+            &[
+                0, 128, 255, 0, // u8
+                0, 0, 255, 255, // u16
+                0x81, 0x80, 0, 127, // i8
+                0x01, 0x80, 0xff, 0x7f, // i16
+            ],
+            r#"[
+                { "buffer": 0, "byteLength": 3 },
+                { "buffer": 0, "byteOffset": 4, "byteLength": 4 },
+                { "buffer": 0, "byteOffset": 8, "byteLength": 4 },
+                { "buffer": 0, "byteOffset": 12, "byteLength": 4 }
+            ]"#,
+            r#"[
+                { "bufferView": 0, "componentType": 5121, "normalized": true, "count": 3, "type": "SCALAR" },
+                { "bufferView": 1, "componentType": 5123, "normalized": true, "count": 2, "type": "SCALAR" },
+                { "bufferView": 2, "componentType": 5120, "normalized": true, "count": 4, "type": "SCALAR" },
+                { "bufferView": 3, "componentType": 5122, "normalized": true, "count": 2, "type": "SCALAR" },
+                { "bufferView": 0, "componentType": 5121, "count": 3, "type": "SCALAR" }
+            ]"#,
+        );
+        let scalars = |accessor: usize| {
+            document.gltf.accessors[accessor]
+                .get_scalars(&document)
+                .unwrap()
+        };
 
-        let gltf_length = Self::get_u32(&mut bytes);
-        let gltf_type = Self::get_u32(&mut bytes);
-        if gltf_type != 0x4E4F534A {
-            panic!("Malformed GLB");
-        }
+        assert_eq!(scalars(0), vec![0.0, 128.0 / 255.0, 1.0]);
+        assert_eq!(scalars(1), vec![0.0, 1.0]);
+        // Both -127 and -128 map to -1
+        assert_eq!(scalars(2), vec![-1.0, -1.0, 0.0, 1.0]);
+        assert_eq!(scalars(3), vec![-1.0, 1.0]);
+        // Without the flag integers are read as they are
+        assert_eq!(scalars(4), vec![0.0, 128.0, 255.0]);
+    }
 
-        let gltf_bytes: Vec<u8> = Self::get(&mut bytes, gltf_length as usize);
-        let gltf = Gltf::load(&gltf_bytes)?;
+    #[test]
+    fn sparse_values_override_the_buffer_view() {
+        let mut data = floats(&[1.0, 2.0, 3.0, 4.0]);
+        data.extend([3, 0, 1, 0]);
+        data.extend(floats(&[40.0, 20.0]));
+        let document = document(
+            &data,
+            r#"[
+                { "buffer": 0, "byteLength": 16 },
+                { "buffer": 0, "byteOffset": 16, "byteLength": 4 },
+                { "buffer": 0, "byteOffset": 20, "byteLength": 8 }
+            ]"#,
+            r#"[
+                {
+                    "bufferView": 0, "componentType": 5126, "count": 4, "type": "SCALAR",
+                    "sparse": {
+                        "count": 2,
+                        "indices": { "bufferView": 1, "componentType": 5123 },
+                        "values": { "bufferView": 2 }
+                    }
+                },
+                {
+                    "componentType": 5126, "count": 5, "type": "SCALAR",
+                    "sparse": {
+                        "count": 2,
+                        "indices": { "bufferView": 1, "componentType": 5123 },
+                        "values": { "bufferView": 2 }
+                    }
+                }
+            ]"#,
+        );
+        let accessors = &document.gltf.accessors;
 
-        let mut buffer = Vec::new();
-        if !bytes.is_empty() {
-            let buffer_length = Self::get_u32(&mut bytes);
-            let buffer_type = Self::get_u32(&mut bytes);
-            if buffer_type != 0x004E4942 {
-                panic!("Malformed GLB");
-            }
+        assert_eq!(
+            accessors[0].get_scalars(&document).unwrap(),
+            vec![1.0, 20.0, 3.0, 40.0]
+        );
+        // Elements without a buffer view start as zero
+        assert_eq!(
+            accessors[1].get_scalars(&document).unwrap(),
+            vec![0.0, 20.0, 0.0, 40.0, 0.0]
+        );
+    }
 
-            buffer = bytes.take(buffer_length as usize).collect();
-        }
+    #[test]
+    fn out_of_bounds_reads_are_errors() {
+        let mut data = floats(&[1.0, 2.0, 3.0, 4.0]);
+        data.extend([9, 0, 0, 0]);
+        let document = document(
+            &data,
+            r#"[
+                { "buffer": 0, "byteLength": 16 },
+                { "buffer": 0, "byteLength": 16, "byteStride": 12 },
+                { "buffer": 0, "byteOffset": 16, "byteLength": 4 }
+            ]"#,
+            r#"[
+                { "bufferView": 0, "componentType": 5126, "count": 5, "type": "SCALAR" },
+                { "bufferView": 0, "byteOffset": 8, "componentType": 5126, "count": 1, "type": "VEC3" },
+                { "bufferView": 1, "componentType": 5126, "count": 2, "type": "VEC2" },
+                {
+                    "bufferView": 0, "componentType": 5126, "count": 4, "type": "SCALAR",
+                    "sparse": {
+                        "count": 1,
+                        "indices": { "bufferView": 2, "componentType": 5125 },
+                        "values": { "bufferView": 0 }
+                    }
+                },
+                {
+                    "bufferView": 0, "componentType": 5126, "count": 4, "type": "SCALAR",
+                    "sparse": {
+                        "count": 2,
+                        "indices": { "bufferView": 2, "componentType": 5125 },
+                        "values": { "bufferView": 0 }
+                    }
+                },
+                { "componentType": 5126, "count": 16777217, "type": "SCALAR" }
+            ]"#,
+        );
+        let scalars = |accessor: usize| document.gltf.accessors[accessor].get_scalars(&document);
+        let accessors = &document.gltf.accessors;
 
-        Ok(Self { gltf, buffer })
+        assert!(matches!(
+            scalars(0),
+            Err(GltfError::OutOfRange { buffer_view: 0 })
+        ));
+        assert!(matches!(
+            accessors[1].get_vec3s(&document),
+            Err(GltfError::OutOfRange { buffer_view: 0 })
+        ));
+        // The second element starts inside the view but runs off the end of it
+        assert!(matches!(
+            accessors[2].get_vec2s(&document),
+            Err(GltfError::OutOfRange { buffer_view: 1 })
+        ));
+        assert!(matches!(
+            scalars(3),
+            Err(GltfError::SparseIndexOutOfRange { index: 9, count: 4 })
+        ));
+        assert!(matches!(
+            scalars(4),
+            Err(GltfError::OutOfRange { buffer_view: 2 })
+        ));
+        assert!(matches!(
+            scalars(5),
+            Err(GltfError::TooManyElements(16777217))
+        ));
+        assert!(matches!(
+            accessors[0].get_vec2s(&document),
+            Err(GltfError::UnexpectedElementType {
+                expected: "VEC2",
+                ..
+            })
+        ));
     }
 }